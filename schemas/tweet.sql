@@ -21,7 +21,8 @@ CREATE INDEX user_twitter_id ON user (twitter_id);
 CREATE TABLE file (
     id INTEGER NOT NULL PRIMARY KEY,
     digest TEXT UNIQUE NOT NULL,
-    primary_twitter_id INTEGER NULL
+    primary_twitter_id INTEGER NULL,
+    url TEXT NULL
 );
 
 CREATE TABLE tweet_file (
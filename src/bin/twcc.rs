@@ -1,8 +1,16 @@
-use cancel_culture::{cli, reports::deleted_tweets::DeletedTweetReport, wbm};
+use cancel_culture::{
+    cli,
+    reports::{
+        self,
+        deleted_tweets::{self, DeletedTweetReport},
+    },
+    util::{csv, diff, existence::ExistenceCache, parse_tweet_ref},
+    wbm,
+};
 use chrono::{DateTime, SubsecRound, Utc};
 use clap::Parser;
 use egg_mode::{tweet::Tweet, user::TwitterUser};
-use egg_mode_extras::{client::TokenType, util::extract_status_id};
+use egg_mode_extras::client::TokenType;
 use futures::{StreamExt, TryStreamExt};
 use itertools::Itertools;
 use std::borrow::Cow;
@@ -11,6 +19,9 @@ use std::fs::File;
 use std::io::Read;
 
 const CDX_PAGE_LIMIT: usize = 150000;
+const REPORT_FILE_PREFIX: &str = "report";
+const STORE_BATCH_SIZE: usize = 100;
+const SAVE_DELAY: std::time::Duration = std::time::Duration::from_secs(10);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -38,6 +49,20 @@ pub enum Error {
     TimestampFieldCollision(serde_json::Value),
     #[error("Invalid profile JSON")]
     InvalidProfileJson(serde_json::Value),
+    #[error("Failure writing report file")]
+    ReportFile(#[source] std::io::Error),
+    #[error("Failure saving media file")]
+    MediaFile(#[source] std::io::Error),
+    #[error("Failure writing URLs file")]
+    UrlsFile(#[source] std::io::Error),
+    #[error("Failure reading IDs file")]
+    IdsFile(#[source] std::io::Error),
+    #[error("Existence cache error")]
+    ExistenceCache(#[from] rusqlite::Error),
+    #[error("Wayback Machine browser client error")]
+    WaybackBrowser(#[from] fantoccini::error::CmdError),
+    #[error("Tweet database error")]
+    TweetStore(#[from] wbm::tweet::db::TweetStoreError),
 }
 
 #[tokio::main]
@@ -47,6 +72,13 @@ async fn main() -> Result<(), Error> {
 
     let client = egg_mode_extras::Client::from_config_file(&opts.key_file).await?;
 
+    let separator = if opts.tsv {
+        Some(b'\t')
+    } else {
+        opts.separator
+    };
+    let field_separator = separator.unwrap_or(b',');
+
     match opts.command {
         SubCommand::ListFollowers {
             ids_only,
@@ -77,7 +109,7 @@ async fn main() -> Result<(), Error> {
                     .lookup_users(ids, token_type)
                     .try_collect::<Vec<_>>()
                     .await?;
-                print_user_report(&users);
+                print_user_report(&users, separator.unwrap_or(b' '));
             }
             Ok(())
         }
@@ -110,7 +142,7 @@ async fn main() -> Result<(), Error> {
                     .lookup_users(ids, token_type)
                     .try_collect::<Vec<_>>()
                     .await?;
-                print_user_report(&users);
+                print_user_report(&users, separator.unwrap_or(b' '));
             }
             Ok(())
         }
@@ -125,7 +157,7 @@ async fn main() -> Result<(), Error> {
                     .lookup_users(ids, TokenType::App)
                     .try_collect::<Vec<_>>()
                     .await?;
-                print_user_report(&users);
+                print_user_report(&users, separator.unwrap_or(b' '));
             }
             Ok(())
         }
@@ -161,6 +193,42 @@ async fn main() -> Result<(), Error> {
             }
             Ok(())
         }
+        SubCommand::BlockDiff { ref old, ref new } => {
+            let old = diff::parse_ids(&std::fs::read_to_string(old).map_err(Error::IdsFile)?);
+            let new = diff::parse_ids(&std::fs::read_to_string(new).map_err(Error::IdsFile)?);
+            let changes = diff::id_diff(&old, &new);
+
+            let screen_names = client
+                .lookup_users(
+                    changes.added.iter().chain(&changes.removed).copied(),
+                    TokenType::App,
+                )
+                .map_ok(|user| (user.id, user.screen_name))
+                .try_collect::<HashMap<_, _>>()
+                .await?;
+
+            let labeled = changes
+                .added
+                .iter()
+                .map(|id| ("blocked", id))
+                .chain(changes.removed.iter().map(|id| ("unblocked", id)));
+
+            for (change, id) in labeled {
+                println!(
+                    "{}",
+                    csv::format_record_with_separator(
+                        [
+                            change,
+                            &id.to_string(),
+                            screen_names.get(id).map(|name| name.as_str()).unwrap_or("")
+                        ],
+                        field_separator
+                    )
+                );
+            }
+
+            Ok(())
+        }
         SubCommand::ImportBlocks => {
             let stdin = std::io::stdin();
             let mut buffer = String::new();
@@ -198,12 +266,89 @@ async fn main() -> Result<(), Error> {
             .try_for_each(|tweet| async move {
                 println!(
                     "{}",
-                    tweet_to_report(&tweet, retweets, media, withheld, false)
+                    tweet_to_report(&tweet, retweets, media, withheld, false, field_separator)
                 );
                 Ok(())
             })
             .await
             .map_err(Error::from),
+        SubCommand::ArchiveTimeline {
+            ref store,
+            ref username,
+            ref password,
+            ref browser,
+            ref host,
+            port,
+            disable_headless,
+            compression,
+            ref screen_name,
+        } => {
+            let store = wbm::store::Store::load(store)?
+                .with_compression(flate2::Compression::new(compression));
+            let downloader = wayback_rs::Downloader::default();
+
+            let urls = client
+                .user_tweets(screen_name.clone(), true, true, TokenType::App)
+                .map_ok(|tweet| {
+                    let screen_name = tweet
+                        .user
+                        .as_ref()
+                        .map(|user| user.screen_name.as_str())
+                        .unwrap_or(screen_name);
+                    format!("https://twitter.com/{}/status/{}", screen_name, tweet.id)
+                })
+                .try_collect::<Vec<_>>()
+                .await?;
+
+            log::info!("Found {} tweets for {}", urls.len(), screen_name);
+
+            let fantoccini_client = cancel_culture::browser::make_client_or_panic(
+                browser,
+                !disable_headless,
+                host.as_deref(),
+                port,
+            )
+            .await;
+
+            let mut wayback_client = wayback_rs::browser::Client::new(fantoccini_client);
+            wayback_client.login(username, password).await?;
+            let wayback_client = futures_locks::Mutex::new(wayback_client);
+
+            let report = store
+                .archive_urls(
+                    &urls,
+                    |url| {
+                        let wayback_client = &wayback_client;
+                        async move {
+                            let saved_url = wayback_client.lock().await.save(&url).await?;
+                            tokio::time::sleep(SAVE_DELAY).await;
+
+                            // Twitter sometimes redirects the Wayback Machine to another page.
+                            Ok::<_, Error>(
+                                saved_url
+                                    .filter(|saved_url| saved_url.contains(&url))
+                                    .and_then(|saved_url| {
+                                        saved_url.split('/').find_map(wbm::util::parse_timestamp)
+                                    }),
+                            )
+                        }
+                    },
+                    |item| {
+                        let downloader = &downloader;
+                        async move { Ok(downloader.download_item(&item).await?) }
+                    },
+                )
+                .await?;
+
+            log::info!(
+                "Saved {}, skipped {}, failed {}",
+                report.saved,
+                report.skipped,
+                report.failed
+            );
+
+            Ok(())
+        }
         SubCommand::ListTweetsJson { id, count } => {
             client
                 .user_tweets(id, true, true, TokenType::App)
@@ -244,11 +389,23 @@ async fn main() -> Result<(), Error> {
                         Some(tweet) => {
                             println!(
                                 "{}",
-                                tweet_to_report(&tweet, retweets, media, withheld, true)
+                                tweet_to_report(
+                                    &tweet,
+                                    retweets,
+                                    media,
+                                    withheld,
+                                    true,
+                                    field_separator
+                                )
                             );
                         }
                         None => {
-                            println!("{},0{}", id, ",".repeat(comma_count));
+                            let mut fields = vec![id.to_string(), "0".to_string()];
+                            fields.resize(2 + comma_count, String::new());
+                            println!(
+                                "{}",
+                                csv::format_record_with_separator(&fields, field_separator)
+                            );
                         }
                     }
 
@@ -258,7 +415,7 @@ async fn main() -> Result<(), Error> {
                 .map_err(Error::from)
         }
         SubCommand::LookupReply { query } => {
-            let reply_id = extract_status_id(&query).ok_or_else(|| Error::TweetIdParse(query))?;
+            let reply_id = parse_tweet_ref(&query).ok_or_else(|| Error::TweetIdParse(query))?;
             match client.lookup_reply_parent(reply_id, TokenType::App).await? {
                 Some((user, id)) => {
                     println!("https://twitter.com/{}/status/{}", user, id);
@@ -376,7 +533,10 @@ async fn main() -> Result<(), Error> {
 
             Ok(())
         }
-        SubCommand::CheckExistence => {
+        SubCommand::CheckExistence {
+            ref cache,
+            cache_ttl_hours,
+        } => {
             let stdin = std::io::stdin();
             let mut buffer = String::new();
             let mut handle = stdin.lock();
@@ -386,14 +546,176 @@ async fn main() -> Result<(), Error> {
                 .split_whitespace()
                 .flat_map(|input| input.parse::<u64>().ok());
 
-            client
-                .lookup_tweets(ids, TokenType::App)
-                .try_for_each(|(id, tweet)| async move {
-                    println!("{},{}", id, if tweet.is_some() { "1" } else { "0" });
-                    Ok(())
+            let print_result = |id: u64, exists: bool| {
+                println!(
+                    "{}",
+                    csv::format_record_with_separator(
+                        &[id.to_string(), if exists { "1" } else { "0" }.to_string()],
+                        field_separator
+                    )
+                );
+            };
+
+            match cache {
+                Some(cache_path) => {
+                    let cache = ExistenceCache::open(cache_path)?;
+                    let now = Utc::now();
+                    let (cached, stale) =
+                        cache.partition(ids, now, chrono::Duration::hours(cache_ttl_hours))?;
+
+                    log::info!(
+                        "Using {} cached results, checking {}",
+                        cached.len(),
+                        stale.len()
+                    );
+
+                    for (id, exists) in cached {
+                        print_result(id, exists);
+                    }
+
+                    let cache = &cache;
+
+                    client
+                        .lookup_tweets(stale, TokenType::App)
+                        .map_err(Error::from)
+                        .try_for_each(|(id, tweet)| async move {
+                            cache.record(id, tweet.is_some(), now)?;
+                            print_result(id, tweet.is_some());
+                            Ok(())
+                        })
+                        .await?;
+                }
+                None => {
+                    client
+                        .lookup_tweets(ids, TokenType::App)
+                        .try_for_each(|(id, tweet)| async move {
+                            print_result(id, tweet.is_some());
+                            Ok(())
+                        })
+                        .await?;
+                }
+            }
+
+            Ok(())
+        }
+        SubCommand::Backfill { ref db } => {
+            let stdin = std::io::stdin();
+            let mut buffer = String::new();
+            let mut handle = stdin.lock();
+            handle.read_to_string(&mut buffer).map_err(Error::Stdin)?;
+
+            let ids = buffer
+                .split_whitespace()
+                .flat_map(|input| input.parse::<u64>().ok())
+                .collect::<Vec<_>>();
+
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+            let report = wbm::tweet::backfill_live_tweets(
+                &tweet_store,
+                client
+                    .lookup_tweets(ids, TokenType::App)
+                    .map_err(Error::from),
+                Utc::now(),
+                &Default::default(),
+            )
+            .await?;
+
+            log::info!(
+                "Stored {} tweets ({} unavailable)",
+                report.stored,
+                report.unavailable
+            );
+
+            Ok(())
+        }
+        SubCommand::CoverageReport {
+            ref store,
+            ref cdx,
+            parallelism,
+            ref screen_name,
+        } => {
+            let store = wbm::store::Store::load(store)?;
+            let items = load_status_items(cdx.as_deref(), screen_name).await?;
+
+            let cdx_ids = items
+                .iter()
+                .filter_map(|item| match wbm::util::classify_item(item) {
+                    wbm::util::ItemKind::Status(id) => Some(id),
+                    _ => None,
                 })
+                .collect::<HashSet<_>>();
+            let author_ids = store.author_tweet_ids(screen_name, parallelism).await?;
+
+            let mut embedded_only = author_ids.difference(&cdx_ids).collect::<Vec<_>>();
+            embedded_only.sort_unstable();
+
+            log::info!(
+                "{} tweets by {} in the store, {} in the CDX index, {} only in other pages",
+                author_ids.len(),
+                screen_name,
+                cdx_ids.len(),
+                embedded_only.len()
+            );
+
+            for id in embedded_only {
+                println!("{}", id);
+            }
+
+            Ok(())
+        }
+        SubCommand::DeletionStats {
+            ref cdx,
+            ref screen_name,
+        } => {
+            let items = load_status_items(cdx.as_deref(), screen_name).await?;
+            let snapshots = wbm::util::first_valid_snapshots(&items);
+
+            let lookups = client
+                .lookup_tweets(snapshots.keys().copied(), TokenType::App)
+                .try_collect::<Vec<_>>()
                 .await?;
 
+            let counts_by_year = wbm::util::deletion_counts_by_year(
+                lookups.into_iter().filter_map(|(id, result)| {
+                    snapshots
+                        .get(&id)
+                        .map(|archived_at| (*archived_at, result.is_none()))
+                }),
+            );
+
+            let mut overall = wbm::util::DeletionCounts::default();
+
+            for (year, counts) in counts_by_year {
+                overall.total += counts.total;
+                overall.deleted += counts.deleted;
+
+                println!(
+                    "{}",
+                    csv::format_record_with_separator(
+                        &[
+                            year.to_string(),
+                            counts.total.to_string(),
+                            counts.deleted.to_string(),
+                            format!("{:.4}", counts.rate())
+                        ],
+                        field_separator
+                    )
+                );
+            }
+
+            println!(
+                "{}",
+                csv::format_record_with_separator(
+                    &[
+                        "all".to_string(),
+                        overall.total.to_string(),
+                        overall.deleted.to_string(),
+                        format!("{:.4}", overall.rate())
+                    ],
+                    field_separator
+                )
+            );
+
             Ok(())
         }
         SubCommand::DeletedTweets {
@@ -401,23 +723,56 @@ async fn main() -> Result<(), Error> {
             report,
             include_failed,
             ref store,
+            compression,
             ref cdx,
+            ref ids_file,
+            deletion_window,
+            page_size,
+            no_live_check,
+            show_context,
+            download_media,
+            ref urls_file,
+            include_live_urls,
             ref screen_name,
         } => {
-            let index_client = wayback_rs::cdx::IndexClient::default();
+            let interrupt = cli::Interrupt::install();
             let downloader = wayback_rs::Downloader::default();
-            let mut items = match cdx {
-                Some(cdx_path) => {
-                    let cdx_file = File::open(cdx_path).map_err(Error::CdxJson)?;
-                    wayback_rs::cdx::IndexClient::load_json(cdx_file)?
-                }
-                None => {
-                    let url = format!("twitter.com/{}/status/*", screen_name);
-                    index_client
-                        .stream_search(&url, CDX_PAGE_LIMIT)
-                        .try_collect::<Vec<_>>()
-                        .await?
+            // Whether each tweet is still available, so that no ID is looked up twice.
+            let mut live_status = HashMap::<u64, bool>::new();
+            let mut items = match ids_file {
+                Some(ids_path) => {
+                    let ids_file = File::open(ids_path).map_err(Error::IdsFile)?;
+                    let ids = wbm::util::read_status_ids(std::io::BufReader::new(ids_file))
+                        .map_err(Error::IdsFile)?;
+
+                    live_status = client
+                        .lookup_tweets(ids.iter().copied(), TokenType::App)
+                        .map_ok(|(id, tweet)| (id, tweet.is_some()))
+                        .try_collect::<HashMap<_, _>>()
+                        .await?;
+
+                    // Only deleted tweets are reported, so we only need snapshots for those.
+                    let deleted_ids = ids
+                        .iter()
+                        .copied()
+                        .filter(|id| live_status.get(id) == Some(&false))
+                        .collect::<Vec<_>>();
+
+                    log::info!(
+                        "{} of {} tweets are unavailable; checking for snapshots",
+                        deleted_ids.len(),
+                        ids.len()
+                    );
+
+                    let index_client = wayback_rs::cdx::IndexClient::default();
+                    let index_client = &index_client;
+
+                    wbm::util::status_snapshots(screen_name, &deleted_ids, |query| async move {
+                        index_client.search(&query, None, None).await
+                    })
+                    .await?
                 }
+                None => load_status_items(cdx.as_deref(), screen_name).await?,
             };
 
             items.sort_unstable_by_key(|item| item.url.clone());
@@ -425,14 +780,32 @@ async fn main() -> Result<(), Error> {
             let results = items.into_iter().group_by(|item| item.url.clone());
 
             let store = match store {
-                Some(dir) => Some(wbm::store::Store::load(dir)?),
+                Some(dir) => Some(
+                    wbm::store::Store::load(dir)?
+                        .with_compression(flate2::Compression::new(compression)),
+                ),
                 None => None,
             };
 
+            let mut windows: HashMap<u64, wbm::util::DeletionWindow> = HashMap::new();
+
             let mut candidates = results
                 .into_iter()
                 .flat_map(|(k, vs)| {
-                    extract_status_id(&k).and_then(|id| {
+                    parse_tweet_ref(&k).and_then(|id| {
+                        let vs = vs.collect::<Vec<_>>();
+
+                        if deletion_window {
+                            if let Some(window) = wbm::util::deletion_window(&vs) {
+                                // The same status may appear under several URLs.
+                                let merged = match windows.get(&id) {
+                                    Some(previous) => merge_deletion_windows(*previous, window),
+                                    None => window,
+                                };
+                                windows.insert(id, merged);
+                            }
+                        }
+
                         // We currently exclude redirects here, which represent retweets.
                         let valid = vs
                             .into_iter()
@@ -466,17 +839,29 @@ async fn main() -> Result<(), Error> {
                 }
             }
 
-            let deleted_status = client
-                .lookup_tweets(by_id.iter().map(|(k, _)| *k), TokenType::App)
-                .try_collect::<Vec<_>>()
-                .await?;
+            let unchecked = by_id
+                .keys()
+                .copied()
+                .filter(|id| !live_status.contains_key(id))
+                .collect::<Vec<_>>();
 
-            let mut deleted = deleted_status
-                .into_iter()
-                .filter(|(_, v)| v.is_none())
+            if !unchecked.is_empty() {
+                live_status.extend(
+                    client
+                        .lookup_tweets(unchecked, TokenType::App)
+                        .map_ok(|(id, tweet)| (id, tweet.is_some()))
+                        .try_collect::<Vec<_>>()
+                        .await?,
+                );
+            }
+
+            let mut deleted = by_id
+                .keys()
+                .copied()
+                .filter(|id| live_status.get(id) == Some(&false))
                 .collect::<Vec<_>>();
 
-            deleted.sort_by_key(|(k, _)| *k);
+            deleted.sort_unstable();
 
             use cancel_culture::browser::twitter::parser::BrowserTweet;
 
@@ -484,7 +869,7 @@ async fn main() -> Result<(), Error> {
 
             if let Some(s) = store.as_ref() {
                 let mut items = Vec::with_capacity(by_id.len());
-                for (id, _) in &deleted {
+                for id in &deleted {
                     if let Some(item) = by_id.get(id) {
                         if s.read(&item.digest).unwrap_or_default().is_none() {
                             items.push(item.clone());
@@ -493,13 +878,25 @@ async fn main() -> Result<(), Error> {
                 }
 
                 log::info!("Saving {} items to store", items.len());
-                s.save_all(&downloader, &items, true, 4).await?;
+
+                // We save in batches so that an interruption only waits for the current batch.
+                for batch in interrupt.take_until(items.chunks(STORE_BATCH_SIZE)) {
+                    s.save_all(&downloader, batch, true, 4).await?;
+                }
             }
 
             let mut empty_items = vec![];
+            // Snapshots for the link dump file, in the order they're listed on standard output.
+            let mut link_dump_items = vec![];
+            // Screen names for all parsed tweets (used to describe reply context).
+            let mut screen_names = HashMap::<u64, String>::new();
 
-            for (id, _) in deleted {
+            for id in interrupt.take_until(deleted) {
                 if let Some(item) = by_id.get(&id) {
+                    if urls_file.is_some() {
+                        link_dump_items.push((id, item));
+                    }
+
                     if report {
                         if let Some(content) = match store {
                             Some(ref store) => match store.read(&item.digest) {
@@ -555,6 +952,19 @@ async fn main() -> Result<(), Error> {
                             }
 
                             for tweet in tweets {
+                                if show_context {
+                                    screen_names.insert(tweet.id, tweet.user_screen_name.clone());
+
+                                    // Archived reply pages tell us who the parent's author is.
+                                    if let Some((parent_id, parent_screen_name)) =
+                                        tweet.parent_id.zip(tweet.reply_to_screen_names.first())
+                                    {
+                                        screen_names
+                                            .entry(parent_id)
+                                            .or_insert_with(|| parent_screen_name.clone());
+                                    }
+                                }
+
                                 if tweet.user_screen_name.to_lowercase()
                                     == *screen_name.to_lowercase()
                                 {
@@ -572,69 +982,207 @@ async fn main() -> Result<(), Error> {
                                 }
                             }
                         }
+                    } else if deletion_window {
+                        let window = windows.get(&id);
+                        println!(
+                            "https://web.archive.org/web/{}/{} {} {}",
+                            wbm::util::format_timestamp(item.archived_at),
+                            item.url,
+                            window
+                                .map(|window| wbm::util::format_timestamp(window.last_seen_alive))
+                                .unwrap_or_else(|| "-".to_string()),
+                            window
+                                .and_then(|window| window.first_seen_gone)
+                                .map(wbm::util::format_timestamp)
+                                .unwrap_or_else(|| "-".to_string())
+                        );
                     } else {
                         println!(
                             "https://web.archive.org/web/{}/{}",
-                            item.timestamp(),
+                            wbm::util::format_timestamp(item.archived_at),
                             item.url
                         );
                     }
                 }
             }
 
+            if let Some(path) = urls_file {
+                let file = File::create(path).map_err(Error::UrlsFile)?;
+                let count = wbm::util::write_link_dump(
+                    std::io::BufWriter::new(file),
+                    screen_name,
+                    link_dump_items,
+                    include_live_urls,
+                )
+                .map_err(Error::UrlsFile)?;
+
+                log::info!("Wrote {} URLs to {}", count, path);
+            }
+
+            if download_media {
+                if let Some(s) = store.as_ref() {
+                    let urls = report_items
+                        .values()
+                        .flat_map(|(tweet, _)| tweet.media.iter().cloned())
+                        .collect::<Vec<_>>();
+
+                    log::info!("Saving {} media files to store", urls.len());
+
+                    let saved = s
+                        .save_media(&urls, |url| async move {
+                            reqwest::get(&url).await?.error_for_status()?.bytes().await
+                        })
+                        .await
+                        .map_err(Error::MediaFile)?;
+
+                    log::info!("Saved {} new media files", saved.len());
+                }
+            }
+
+            if interrupt.is_interrupted() {
+                log::warn!(
+                    "Interrupted: reporting on {} tweets collected so far",
+                    report_items.len()
+                );
+            }
+
             if report {
                 let mut report_items_vec = report_items.iter().collect::<Vec<_>>();
                 report_items_vec.sort_unstable_by_key(|(k, _)| -(**k as i64));
 
-                let deleted_status = client
-                    .lookup_tweets(report_items_vec.iter().map(|(k, _)| **k), TokenType::App)
-                    .map_ok(|(k, v)| (k, v.is_some()))
-                    .try_collect::<HashMap<_, _>>()
-                    .await?;
+                let deleted_status = if no_live_check {
+                    None
+                } else {
+                    // Most of these were already checked above.
+                    let unchecked = report_items_vec
+                        .iter()
+                        .map(|(k, _)| **k)
+                        .filter(|id| !live_status.contains_key(id))
+                        .collect::<Vec<_>>();
+
+                    if !unchecked.is_empty() {
+                        live_status.extend(
+                            client
+                                .lookup_tweets(unchecked, TokenType::App)
+                                .map_ok(|(id, tweet)| (id, tweet.is_some()))
+                                .try_collect::<Vec<_>>()
+                                .await?,
+                        );
+                    }
 
-                let deleted_count = deleted_status.iter().filter(|(_, v)| !*v).count();
-                let undeleted_count = report_items_vec.len() - deleted_count;
+                    Some(&live_status)
+                };
 
-                let report = DeletedTweetReport::new(screen_name, deleted_count, undeleted_count);
+                let report = match deleted_status {
+                    Some(deleted_status) => {
+                        let deleted_count = report_items_vec
+                            .iter()
+                            .filter(|(k, _)| deleted_status.get(k) == Some(&false))
+                            .count();
+                        let undeleted_count = report_items_vec.len() - deleted_count;
 
-                println!("{}", report);
+                        DeletedTweetReport::new(screen_name, deleted_count, undeleted_count)
+                    }
+                    None => DeletedTweetReport::unchecked(screen_name, report_items_vec.len()),
+                };
+
+                if show_context && !no_live_check {
+                    let unresolved = report_items_vec
+                        .iter()
+                        .filter_map(|(_, (tweet, _))| tweet.parent_id)
+                        .filter(|parent_id| !screen_names.contains_key(parent_id))
+                        .collect::<HashSet<_>>();
+
+                    if !unresolved.is_empty() {
+                        let parents = client
+                            .lookup_tweets(unresolved.into_iter(), TokenType::App)
+                            .try_collect::<Vec<_>>()
+                            .await?;
+
+                        for (parent_id, parent) in parents {
+                            if let Some(user) = parent.and_then(|parent| parent.user) {
+                                screen_names.insert(parent_id, user.screen_name);
+                            }
+                        }
+                    }
+                }
 
-                for (id, (tweet, item)) in report_items_vec {
-                    let time = tweet.time.format("%e %B %Y");
+                let mut lines = Vec::with_capacity(report_items_vec.len());
 
-                    if *deleted_status.get(id).unwrap_or(&false) {
-                        println!(
-                            "* [{}](https://web.archive.org/web/{}/{}) ([live](https://twitter.com/{}/status/{})): {} <!--{}-->",
-                            time,
-                            item.timestamp(),
-                            item.url,
-                            tweet.user_screen_name,
-                            tweet.id,
-                            escape_tweet_text(&tweet.text),
-                            tweet.id
-                        );
+                for (id, (tweet, item)) in report_items_vec {
+                    let window = if deletion_window {
+                        windows
+                            .get(id)
+                            .map(|window| {
+                                format!(
+                                    " _(last seen {}, first seen deleted {})_",
+                                    window.last_seen_alive.format("%Y-%m-%d"),
+                                    window
+                                        .first_seen_gone
+                                        .map(|gone| gone.format("%Y-%m-%d").to_string())
+                                        .unwrap_or_else(|| "unknown".to_string())
+                                )
+                            })
+                            .unwrap_or_default()
                     } else {
-                        println!(
-                            "* [{}](https://web.archive.org/web/{}/{}): {} <!--{}-->",
-                            time,
-                            item.timestamp(),
-                            item.url,
-                            escape_tweet_text(&tweet.text),
-                            tweet.id
-                        );
+                        String::new()
+                    };
+
+                    let live = deleted_status
+                        .as_ref()
+                        .and_then(|deleted_status| deleted_status.get(id))
+                        .copied()
+                        .unwrap_or(false);
+
+                    let mut line = deleted_tweets::format_line(
+                        tweet,
+                        &wbm::util::format_timestamp(item.archived_at),
+                        &item.url,
+                        live,
+                        &window,
+                    );
+
+                    if show_context {
+                        if let Some(context) =
+                            deleted_tweets::format_context_line(tweet, &screen_names)
+                        {
+                            line.push('\n');
+                            line.push_str(&context);
+                        }
                     }
+
+                    lines.push(line);
                 }
 
+                let mut failed = vec![];
+
                 if include_failed && !empty_items.is_empty() {
-                    println!("\n{} URLs could not be parsed:\n", empty_items.len());
+                    failed.push(format!(
+                        "\n{} URLs could not be parsed:\n",
+                        empty_items.len()
+                    ));
 
                     for item in empty_items {
-                        println!(
+                        failed.push(format!(
                             "* [{}](https://web.archive.org/web/{}/{})",
                             item.url,
-                            item.timestamp(),
+                            wbm::util::format_timestamp(item.archived_at),
                             item.url
-                        );
+                        ));
+                    }
+                }
+
+                match page_size {
+                    Some(page_size) => {
+                        write_report_pages(screen_name, &report, &lines, &failed, page_size)
+                            .map_err(Error::ReportFile)?
+                    }
+                    None => {
+                        println!("{}", report);
+
+                        for line in lines.iter().chain(failed.iter()) {
+                            println!("{}", line);
+                        }
                     }
                 }
             }
@@ -646,14 +1194,109 @@ async fn main() -> Result<(), Error> {
     }
 }
 
-fn print_user_report(users: &[TwitterUser]) {
+fn write_report_pages(
+    screen_name: &str,
+    report: &DeletedTweetReport,
+    lines: &[String],
+    failed: &[String],
+    page_size: usize,
+) -> std::io::Result<()> {
+    use std::io::Write;
+
+    let pages = reports::paginate(REPORT_FILE_PREFIX, lines, page_size);
+    let page_count = pages.len();
+
+    for (i, (file_name, page_lines)) in pages.iter().enumerate() {
+        let mut file = File::create(file_name)?;
+        writeln!(
+            file,
+            "## Deleted tweets for {} (page {} of {})\n",
+            screen_name,
+            i + 1,
+            page_count
+        )?;
+
+        for line in page_lines.iter() {
+            writeln!(file, "{}", line)?;
+        }
+    }
+
+    let index_file_name = format!("{}-index.md", REPORT_FILE_PREFIX);
+    let mut index = File::create(&index_file_name)?;
+    writeln!(index, "{}", report)?;
+    writeln!(index, "### Pages\n")?;
+
+    for (i, (file_name, page_lines)) in pages.iter().enumerate() {
+        writeln!(
+            index,
+            "* [Page {}]({}) ({} tweets)",
+            i + 1,
+            file_name,
+            page_lines.len()
+        )?;
+    }
+
+    for line in failed {
+        writeln!(index, "{}", line)?;
+    }
+
+    log::info!("Wrote {} report pages and {}", page_count, index_file_name);
+
+    Ok(())
+}
+
+/// Load CDX results for a user's status URLs, either from a JSON file or the CDX API.
+async fn load_status_items(
+    cdx: Option<&str>,
+    screen_name: &str,
+) -> Result<Vec<wayback_rs::Item>, Error> {
+    match cdx {
+        Some(cdx_path) => {
+            let cdx_file = File::open(cdx_path).map_err(Error::CdxJson)?;
+            Ok(wayback_rs::cdx::IndexClient::load_json(cdx_file)?)
+        }
+        None => {
+            let url = format!("twitter.com/{}/status/*", screen_name);
+            Ok(wayback_rs::cdx::IndexClient::default()
+                .stream_search(&url, CDX_PAGE_LIMIT)
+                .try_collect::<Vec<_>>()
+                .await?)
+        }
+    }
+}
+
+fn print_user_report(users: &[TwitterUser], separator: u8) {
     for user in users {
-        println!("{} {} {}", user.id, user.screen_name, user.followers_count);
+        println!(
+            "{}",
+            csv::format_record_with_separator(
+                &[
+                    user.id.to_string(),
+                    user.screen_name.clone(),
+                    user.followers_count.to_string()
+                ],
+                separator
+            )
+        );
     }
 }
 
-fn escape_tweet_text(text: &str) -> String {
-    text.replace(r"\'", "'").replace('\n', " ")
+fn merge_deletion_windows(
+    a: wbm::util::DeletionWindow,
+    b: wbm::util::DeletionWindow,
+) -> wbm::util::DeletionWindow {
+    let last_seen_alive = a.last_seen_alive.max(b.last_seen_alive);
+    let first_seen_gone = a
+        .first_seen_gone
+        .into_iter()
+        .chain(b.first_seen_gone)
+        .filter(|gone| *gone > last_seen_alive)
+        .min();
+
+    wbm::util::DeletionWindow {
+        last_seen_alive,
+        first_seen_gone,
+    }
 }
 
 #[derive(Parser)]
@@ -665,6 +1308,13 @@ struct Opts {
     /// Level of verbosity
     #[clap(short, long, global = true, action = clap::ArgAction::Count)]
     verbose: u8,
+    /// Field separator for reports (fields containing it are quoted; defaults to a comma, or a
+    /// space for user reports)
+    #[clap(long, global = true, value_parser = csv::parse_separator)]
+    separator: Option<u8>,
+    /// Separate report fields with tabs
+    #[clap(long, global = true, conflicts_with = "separator")]
+    tsv: bool,
     #[clap(subcommand)]
     command: SubCommand,
 }
@@ -678,7 +1328,40 @@ enum SubCommand {
     /// Get the URL of a tweet given the URL or status ID of a reply
     LookupReply { query: String },
     /// Check whether a list of status IDs (from stdin) still exist
-    CheckExistence,
+    CheckExistence {
+        /// SQLite file recording previous results (IDs checked recently aren't looked up again)
+        #[clap(long)]
+        cache: Option<String>,
+        /// How long cached results are used before IDs are checked again
+        #[clap(long, default_value = "24", requires = "cache")]
+        cache_ttl_hours: i64,
+    },
+    /// Add any still-available tweets from a list of status IDs (from stdin) to a tweet database
+    Backfill {
+        /// The database file
+        #[clap(long)]
+        db: String,
+    },
+    /// List a user's tweets that appear in a store only embedded in other pages
+    CoverageReport {
+        /// Local store directory for downloaded Wayback files
+        #[clap(short = 's', long)]
+        store: String,
+        /// Optional JSON file path for CDX results (useful for large accounts)
+        #[clap(short = 'c', long)]
+        cdx: Option<String>,
+        /// Level of parallelism for parsing store items
+        #[clap(short = 'p', long, default_value = "6")]
+        parallelism: usize,
+        screen_name: String,
+    },
+    /// Print the fraction of a user's archived tweets that have been deleted, by year archived
+    DeletionStats {
+        /// Optional JSON file path for CDX results (useful for large accounts)
+        #[clap(short = 'c', long)]
+        cdx: Option<String>,
+        screen_name: String,
+    },
     /// List Wayback Machine URLs for all deleted tweets by a user
     DeletedTweets {
         #[clap(short = 'l', long)]
@@ -693,9 +1376,40 @@ enum SubCommand {
         /// Local store directory for downloaded Wayback files
         #[clap(short = 's', long)]
         store: Option<String>,
+        /// Gzip compression level (0-9) for files added to the store
+        #[clap(
+            long,
+            default_value_t = wbm::store::DEFAULT_COMPRESSION_LEVEL,
+            value_parser = clap::value_parser!(u32).range(0..=9)
+        )]
+        compression: u32,
         /// Optional JSON file path for CDX results (useful for large accounts)
         #[clap(short = 'c', long)]
         cdx: Option<String>,
+        /// File of status IDs or tweet URLs to check (one per line) instead of scanning the CDX index
+        #[clap(long, conflicts_with = "cdx")]
+        ids_file: Option<String>,
+        /// Include the last snapshot showing each tweet and the first 404 snapshot after it
+        #[clap(long)]
+        deletion_window: bool,
+        /// Split the report into files with at most this many tweets each (plus an index file)
+        #[clap(long)]
+        page_size: Option<usize>,
+        /// Don't check whether reported tweets are currently live
+        #[clap(long)]
+        no_live_check: bool,
+        /// Indicate which tweet each reply was replying to
+        #[clap(long)]
+        show_context: bool,
+        /// Download images attached to reported tweets into the store
+        #[clap(long, requires_all = ["store", "report"])]
+        download_media: bool,
+        /// Also write the Wayback Machine URLs to this file (in both report and URL-only modes)
+        #[clap(long)]
+        urls_file: Option<String>,
+        /// Include each tweet's twitter.com URL in the URLs file
+        #[clap(long, requires = "urls_file")]
+        include_live_urls: bool,
         screen_name: String,
     },
     /// Print a list of all users who follow you (or someone else)
@@ -742,6 +1456,35 @@ enum SubCommand {
         /// The user whose tweets you want to list
         screen_name: String,
     },
+    /// Save a new Wayback Machine snapshot of each of a user's tweets to a local store
+    ArchiveTimeline {
+        /// Local store directory for the snapshots (tweets already in the store are skipped)
+        #[clap(short = 's', long)]
+        store: String,
+        /// Wayback Machine username
+        #[clap(short = 'u', long)]
+        username: String,
+        /// Wayback Machine password
+        #[clap(short = 'x', long)]
+        password: String,
+        #[clap(short = 'b', long, default_value = "chrome")]
+        browser: String,
+        #[clap(long)]
+        host: Option<String>,
+        #[clap(short = 'p', long)]
+        port: Option<u16>,
+        #[clap(short = 'n', long)]
+        disable_headless: bool,
+        /// Gzip compression level (0-9) for stored snapshots
+        #[clap(
+            long,
+            default_value_t = wbm::store::DEFAULT_COMPRESSION_LEVEL,
+            value_parser = clap::value_parser!(u32).range(0..=9)
+        )]
+        compression: u32,
+        /// The user whose tweets you want to archive
+        screen_name: String,
+    },
     ListTweetsJson {
         id: u64,
         #[clap(long)]
@@ -761,6 +1504,15 @@ enum SubCommand {
     },
     /// Block a list of user IDs (from stdin)
     ImportBlocks,
+    /// Compare two block list snapshots (files of IDs) and print who was blocked and unblocked
+    BlockDiff {
+        /// Earlier list of blocked IDs
+        #[clap(long)]
+        old: String,
+        /// Later list of blocked IDs
+        #[clap(long)]
+        new: String,
+    },
     /// List everyone you follow or who follows you who is not a mutual
     ListUnmutuals,
 }
@@ -771,6 +1523,7 @@ fn tweet_to_report(
     media: bool,
     withheld: bool,
     include_status: bool,
+    separator: u8,
 ) -> String {
     let id = tweet.id;
 
@@ -791,33 +1544,33 @@ fn tweet_to_report(
         })
         .unwrap_or_default();
 
-    let mut result = id.to_string();
+    let mut fields = vec![id.to_string()];
 
     if include_status {
-        result.push_str(",1");
+        fields.push("1".to_string());
     }
 
     if retweets {
-        result.push(',');
-        if let Some((id, user_id, screen_name)) = retweet_info {
-            result.push_str(&format!("{};{};{}", id, user_id, screen_name));
-        }
+        fields.push(
+            retweet_info
+                .map(|(id, user_id, screen_name)| format!("{};{};{}", id, user_id, screen_name))
+                .unwrap_or_default(),
+        );
     }
     if media {
-        result.push_str(&format!(",{}", media_info.join(";")));
+        fields.push(media_info.join(";"));
     }
     if withheld {
-        result.push_str(&format!(
-            ",{}",
+        fields.push(
             tweet
                 .withheld_in_countries
                 .as_ref()
                 .map(|codes| codes.join(";"))
-                .unwrap_or_default()
-        ));
+                .unwrap_or_default(),
+        );
     }
 
-    result
+    csv::format_record_with_separator(&fields, separator)
 }
 
 fn timestamp_json(value: &mut serde_json::Value, now: DateTime<Utc>) -> Result<(), Error> {
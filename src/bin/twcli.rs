@@ -1,4 +1,11 @@
-use cancel_culture::cli;
+use cancel_culture::{
+    cli,
+    util::{
+        csv::RecordWriter,
+        json::JsonWriter,
+        screen_names::{resolve_screen_names, ScreenNameCache},
+    },
+};
 use chrono::Utc;
 use clap::Parser;
 use egg_mode::user::UserID;
@@ -35,7 +42,10 @@ async fn main() -> Void {
                 }
             }
         }
-        SubCommand::UserJson { timestamp } => {
+        SubCommand::UserJson {
+            timestamp,
+            json_array,
+        } => {
             let stdin = std::io::stdin();
             let handle = stdin.lock();
             let ids = handle
@@ -45,31 +55,31 @@ async fn main() -> Void {
                 .unwrap();
 
             let users = client.lookup_users_json(ids, TokenType::App);
-            let timestamp = timestamp.as_ref();
-
-            users
-                .try_for_each(|mut user| async move {
-                    if let Some(fields) = user.as_object_mut() {
-                        if let Some(timestamp_field_name) = timestamp {
-                            if let Some(previous_value) = fields.insert(
-                                timestamp_field_name.clone(),
-                                serde_json::json!(Utc::now().timestamp()),
-                            ) {
-                                log::warn!(
-                                    "Timestamp field collision: \"{}\" was {}",
-                                    timestamp_field_name,
-                                    previous_value
-                                );
-                            }
+            futures::pin_mut!(users);
+            let mut writer = JsonWriter::new(std::io::stdout().lock(), json_array);
+
+            while let Some(mut user) = users.try_next().await? {
+                if let Some(fields) = user.as_object_mut() {
+                    if let Some(timestamp_field_name) = &timestamp {
+                        if let Some(previous_value) = fields.insert(
+                            timestamp_field_name.clone(),
+                            serde_json::json!(Utc::now().timestamp()),
+                        ) {
+                            log::warn!(
+                                "Timestamp field collision: \"{}\" was {}",
+                                timestamp_field_name,
+                                previous_value
+                            );
                         }
-                    } else {
-                        log::warn!("Not a JSON object: {}", user);
                     }
+                } else {
+                    log::warn!("Not a JSON object: {}", user);
+                }
 
-                    println!("{}", user);
-                    Ok(())
-                })
-                .await?
+                writer.write(&user)?;
+            }
+
+            writer.finish()?;
         }
         SubCommand::UserInfo { db, md } => {
             let stdin = std::io::stdin();
@@ -118,9 +128,7 @@ async fn main() -> Void {
                     );
                 }
             } else {
-                let mut writer = csv::WriterBuilder::new()
-                    .flexible(true)
-                    .from_writer(std::io::stdout());
+                let mut writer = RecordWriter::stdout();
 
                 for result in results {
                     let record = vec![
@@ -137,9 +145,48 @@ async fn main() -> Void {
                             .join(";"),
                     ];
 
-                    writer.write_record(record)?;
+                    writer.write_fields(record)?;
                 }
+
+                writer.flush()?;
+            }
+        }
+        SubCommand::ResolveScreenNames { cache } => {
+            let stdin = std::io::stdin();
+            let names = stdin
+                .lock()
+                .lines()
+                .map(|line| line.map(|name| name.trim().trim_start_matches('@').to_string()))
+                .filter(|name| name.as_ref().map_or(true, |name| !name.is_empty()))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let cache = cache.map(ScreenNameCache::open).transpose()?;
+            let client = &client;
+
+            let resolved = resolve_screen_names(&names, cache.as_ref(), |chunk| async move {
+                client
+                    .lookup_users(chunk, TokenType::App)
+                    .map_ok(|user| (user.screen_name, user.id))
+                    .try_collect::<Vec<_>>()
+                    .await
+                    .map_err(Box::<dyn std::error::Error>::from)
+            })
+            .await?;
+
+            let mut writer = RecordWriter::stdout();
+
+            for name in names.iter().unique() {
+                let id = resolved
+                    .get(name)
+                    .copied()
+                    .flatten()
+                    .map(|id| id.to_string())
+                    .unwrap_or_default();
+
+                writer.write_fields([name.as_str(), id.as_str()])?;
             }
+
+            writer.flush()?;
         }
         SubCommand::ScreenNames {
             include_screen_name,
@@ -235,6 +282,12 @@ struct Opts {
 
 #[derive(Parser)]
 enum SubCommand {
+    /// Resolve screen names (one per line on standard input) to user IDs
+    ResolveScreenNames {
+        /// SQLite database for caching resolved screen names
+        #[clap(long)]
+        cache: Option<String>,
+    },
     ScreenNames {
         #[clap(long)]
         include_screen_name: bool,
@@ -253,5 +306,8 @@ enum SubCommand {
         /// Timestamp field name to add to Twitter JSON object
         #[clap(short, long)]
         timestamp: Option<String>,
+        /// Print a single JSON array instead of one object per line
+        #[clap(long)]
+        json_array: bool,
     },
 }
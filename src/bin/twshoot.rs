@@ -1,10 +1,13 @@
-use cancel_culture::browser;
+use cancel_culture::{
+    browser::{self, twitter::ScreenshotOutputs},
+    util::parse_tweet_ref,
+};
 use clap::Parser;
-use image::DynamicImage;
 use std::path::PathBuf;
 use std::time::Duration;
 
 const LOADING_DELAY: Duration = Duration::from_millis(1500);
+const CROP_FAILURE_EXIT_CODE: i32 = 2;
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -18,47 +21,93 @@ async fn main() -> Result<(), Error> {
     )
     .await;
 
-    if let Some(status_id) = opts
-        .status
-        .parse::<u64>()
-        .ok()
-        .or_else(|| egg_mode_extras::util::extract_status_id(&opts.status))
-    {
-        let full_name = &format!("{}-full.png", status_id);
-        let crop_name = &format!("{}.png", status_id);
-
-        let mut full_path = PathBuf::new();
-        let mut crop_path = PathBuf::new();
-
-        if let Some(directory) = opts.directory {
-            full_path.push(&directory);
-            crop_path.push(&directory);
-        }
+    if let Some(status_id) = parse_tweet_ref(&opts.status) {
+        let outputs = ScreenshotOutputs::from_flags(opts.crop_only, opts.full_only);
+        let base_path = opts.directory.map(PathBuf::from).unwrap_or_default();
+        let captured_at = chrono::Utc::now();
 
-        full_path.push(full_name);
-        crop_path.push(crop_name);
+        let options = browser::twitter::ScreenshotOptions {
+            width: opts.width,
+            height: opts.height,
+            max_auto_width: opts.max_width,
+            timeout: Duration::from_secs(opts.timeout),
+            wait_for_load: Some(LOADING_DELAY),
+            browser: opts.browser.clone(),
+            headless: !opts.disable_headless,
+            host: opts.host.clone(),
+            port: opts.port,
+            crop_mode: if opts.include_actions {
+                browser::twitter::CropMode::IncludeActions
+            } else {
+                browser::twitter::CropMode::TextOnly
+            },
+            ..Default::default()
+        };
 
-        let img = browser::twitter::shoot_tweet(
-            &mut client,
-            status_id,
-            opts.width,
-            opts.height,
-            Some(LOADING_DELAY),
-        )
-        .await?;
+        // Both images come from the same page load.
+        let (full_image, clipping) =
+            browser::twitter::screenshot_tweet_full_and_cropped(&mut client, status_id, &options)
+                .await?;
+        let full_image = Some(full_image).filter(|_| outputs.includes_full());
+        let clipping = Some(clipping).filter(|_| outputs.includes_crop());
 
-        img.save(full_path)
-            .map_err(browser::twitter::ScreenshotError::from)?;
+        let cropped = clipping
+            .as_ref()
+            .map(|clipping| clipping.cropped)
+            .unwrap_or(false);
+        let (full_name, crop_name) = outputs.file_names(status_id, cropped);
 
-        let as_rgba = img.into_rgba8();
+        if let Some((name, image)) = full_name.zip(full_image.as_ref()) {
+            image
+                .save(base_path.join(name))
+                .map_err(browser::twitter::ScreenshotError::from)?;
+        }
 
-        if let Some((x, y, w, h)) = browser::twitter::crop_tweet(&as_rgba) {
-            let clipping = DynamicImage::ImageRgba8(as_rgba).crop(x, y, w, h);
+        if let Some((name, clipping)) = crop_name.zip(clipping.as_ref()) {
             clipping
-                .save(crop_path)
+                .image
+                .save(base_path.join(name))
                 .map_err(browser::twitter::ScreenshotError::from)?;
-        } else {
+        }
+
+        if opts.pdf {
+            let image = match (clipping, full_image) {
+                (Some(clipping), _) if clipping.cropped => Some(clipping.image),
+                (_, Some(full_image)) => Some(full_image),
+                (Some(clipping), None) => Some(clipping.image),
+                (None, None) => None,
+            };
+
+            if let Some(image) = image {
+                let metadata = browser::pdf::PdfMetadata {
+                    url: Some(format!("https://twitter.com/i/web/status/{}", status_id)),
+                    captured_at: Some(captured_at),
+                };
+                let pdf = browser::pdf::to_pdf(&image, &metadata).map_err(Error::Pdf)?;
+
+                std::fs::write(base_path.join(format!("{}.pdf", status_id)), pdf)
+                    .map_err(Error::Pdf)?;
+            }
+        }
+
+        if opts.json {
+            let source = client.source().await.map_err(Error::Source)?;
+            let doc = scraper::Html::parse_document(&source);
+
+            match browser::twitter::parser::extract_tweet(&doc, status_id) {
+                Some(tweet) => {
+                    let json = serde_json::to_vec_pretty(&tweet).map_err(Error::Json)?;
+
+                    std::fs::write(base_path.join(format!("{}.json", status_id)), json)
+                        .map_err(Error::JsonFile)?;
+                }
+                None => eprintln!("Unable to extract tweet {} from page", status_id),
+            }
+        }
+
+        if outputs.includes_crop() && !cropped {
             eprintln!("Unable to crop tweet");
+            std::process::exit(CROP_FAILURE_EXIT_CODE);
         }
 
         Ok(())
@@ -73,6 +122,14 @@ pub enum Error {
     TweetIdParse(String),
     #[error("Screenshot error")]
     Screenshot(#[from] browser::twitter::ScreenshotError),
+    #[error("Unable to write PDF")]
+    Pdf(#[source] std::io::Error),
+    #[error("Unable to read page source")]
+    Source(#[source] fantoccini::error::CmdError),
+    #[error("Unable to serialize tweet")]
+    Json(#[source] serde_json::Error),
+    #[error("Unable to write tweet JSON")]
+    JsonFile(#[source] std::io::Error),
 }
 
 #[derive(Parser)]
@@ -92,6 +149,32 @@ struct Opts {
     width: u32,
     #[clap(long, default_value = "4000")]
     height: u32,
+    /// Widen the window up to this width if the tweet's content overflows
+    #[clap(long)]
+    max_width: Option<u32>,
     #[clap(short, long, default_value = "chrome")]
     browser: String,
+    /// Seconds to wait for the page to load before giving up
+    #[clap(short, long, default_value = "30")]
+    timeout: u64,
+    /// Only save the cropped tweet
+    #[clap(long, conflicts_with_all = ["full_only", "both"])]
+    crop_only: bool,
+    /// Only save the full window screenshot
+    #[clap(long, conflicts_with_all = ["crop_only", "both"])]
+    full_only: bool,
+    /// Save both the cropped tweet and the full window screenshot (the default)
+    ///
+    /// Unless only the full screenshot is requested, the exit status is 2 if cropping fails.
+    #[clap(long)]
+    both: bool,
+    /// Keep the reply, retweet, and like buttons when cropping the tweet
+    #[clap(long)]
+    include_actions: bool,
+    /// Also save the tweet as a PDF with its URL and the capture time in a footer
+    #[clap(long)]
+    pdf: bool,
+    /// Also save the tweet's text, author, and timestamp (extracted from the page) as JSON
+    #[clap(long)]
+    json: bool,
 }
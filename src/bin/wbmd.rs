@@ -1,6 +1,12 @@
-use cancel_culture::{cli, wbm, wbm::valid};
+use cancel_culture::{
+    cli,
+    util::{csv::RecordWriter, diff::DiffSegment},
+    wbm,
+    wbm::valid,
+};
 use clap::Parser;
 use futures::StreamExt;
+use std::collections::HashMap;
 use std::path::Path;
 use wayback_rs::digest;
 
@@ -131,11 +137,57 @@ async fn main() -> Void {
                 }
             }
         }
-        SubCommand::SaveTweets { db, store } => {
+        SubCommand::Promote { dir, other } => {
+            let store = valid::ValidStore::new(dir);
+            let report = store.promote_from(other, opts.parallelism).await?;
+
+            log::info!(
+                "Promoted: {}; failed: {}; skipped: {}",
+                report.promoted,
+                report.failed,
+                report.skipped
+            );
+        }
+        SubCommand::SaveTweets {
+            db,
+            store,
+            progress_interval,
+            collapse_spaces,
+            normalize_newlines,
+            unicode_nfc,
+        } => {
             let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
             let valid_store = valid::ValidStore::new(store);
+            let start = std::time::Instant::now();
+            let normalize = wbm::tweet::db::NormalizeOptions {
+                collapse_spaces,
+                normalize_newlines,
+                unicode_nfc,
+            };
+
+            let progress = wbm::tweet::export_tweets_with_progress(
+                &valid_store,
+                &tweet_store,
+                &normalize,
+                progress_interval,
+                |progress| {
+                    log::info!(
+                        "Files: {}; tweets: {}; failures: {} ({:.1} files per second)",
+                        progress.files,
+                        progress.tweets,
+                        progress.failures,
+                        progress.files as f64 / start.elapsed().as_secs_f64()
+                    )
+                },
+            )
+            .await?;
 
-            wbm::tweet::export_tweets(&valid_store, &tweet_store).await?;
+            log::info!(
+                "Done; files: {}; tweets: {}; failures: {}",
+                progress.files,
+                progress.tweets,
+                progress.failures
+            );
         }
         SubCommand::Get { db } => {
             let status_ids = cli::read_stdin()?
@@ -147,22 +199,21 @@ async fn main() -> Void {
             let mut results = tweet_store.get_tweet(&status_ids).await?;
             results.sort_by_key(|(tweet, _)| (tweet.id, tweet.user_id));
 
-            let mut out = csv::WriterBuilder::new().from_writer(std::io::stdout());
-            let space_re = regex::Regex::new(r" +").unwrap();
+            let mut out = RecordWriter::stdout();
 
-            for (tweet, digest) in results {
-                out.write_record(&[
+            for (tweet, _) in results {
+                out.write_fields(&[
                     tweet.id.to_string(),
                     tweet.time.timestamp().to_string(),
                     tweet.user_id.to_string(),
                     tweet.user_screen_name,
-                    space_re
-                        .replace_all(&tweet.text.trim().replace('\n', "\\n"), " ")
-                        .to_string(),
+                    tweet.text,
                 ])?;
             }
+
+            out.flush()?;
         }
-        SubCommand::Replies { db } => {
+        SubCommand::Replies { db, page_size } => {
             let users = cli::read_stdin()?
                 .lines()
                 .map(|line| {
@@ -177,22 +228,35 @@ async fn main() -> Void {
             let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
 
             for (user_twitter_id, screen_name) in users {
-                let results = tweet_store
-                    .get_replies(user_twitter_id, &screen_name)
-                    .await?;
+                let mut page = wbm::tweet::db::Page {
+                    limit: page_size,
+                    offset: 0,
+                };
 
-                for (twitter_id, reply_twitter_id, reply_user_twitter_id, reply_screen_name) in
-                    results
-                {
-                    println!(
-                        "{},{},{},{},{},{}",
-                        screen_name,
-                        reply_screen_name,
-                        user_twitter_id,
-                        reply_user_twitter_id,
-                        twitter_id,
-                        reply_twitter_id
-                    );
+                loop {
+                    let results = tweet_store
+                        .get_replies(user_twitter_id, &screen_name, page)
+                        .await?;
+                    let count = results.len();
+
+                    for (twitter_id, reply_twitter_id, reply_user_twitter_id, reply_screen_name) in
+                        results
+                    {
+                        println!(
+                            "{},{},{},{},{},{}",
+                            screen_name,
+                            reply_screen_name,
+                            user_twitter_id,
+                            reply_user_twitter_id,
+                            twitter_id,
+                            reply_twitter_id
+                        );
+                    }
+
+                    match page.limit {
+                        Some(limit) if limit > 0 && count == limit => page.offset += limit,
+                        _ => break,
+                    }
                 }
             }
         }
@@ -229,6 +293,92 @@ async fn main() -> Void {
                     .await?;
             }
         }
+        SubCommand::Conversations { db, user_a, user_b } => {
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+
+            for edge in tweet_store.conversations_between(user_a, user_b).await? {
+                println!(
+                    "{},{},{},{},{},{}",
+                    edge.parent_id,
+                    edge.parent_ts.timestamp_millis(),
+                    edge.parent_user_id,
+                    edge.reply_id,
+                    edge.reply_ts.timestamp_millis(),
+                    edge.reply_user_id,
+                );
+            }
+        }
+        SubCommand::Diff { db, cdx, id } => {
+            let archived_at = match cdx {
+                Some(path) => {
+                    let items =
+                        wayback_rs::cdx::IndexClient::load_json(std::fs::File::open(path)?)?;
+                    wbm::util::archive_times(&items)
+                }
+                None => HashMap::new(),
+            };
+
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+            let diffs = tweet_store
+                .diff_versions(id, |digest| archived_at.get(digest).cloned())
+                .await?;
+
+            for diff in diffs {
+                println!(
+                    "{} ({}) -> {} ({})",
+                    diff.from_digest,
+                    format_archived_at(diff.from_archived_at),
+                    diff.to_digest,
+                    format_archived_at(diff.to_archived_at)
+                );
+
+                for segment in diff.segments {
+                    match segment {
+                        DiffSegment::Removed(text) => println!("- {}", text),
+                        DiffSegment::Added(text) => println!("+ {}", text),
+                        DiffSegment::Same(_) => {}
+                    }
+                }
+            }
+        }
+        SubCommand::FindEdited {
+            db,
+            cdx,
+            min_gap_minutes,
+        } => {
+            let items = wayback_rs::cdx::IndexClient::load_json(std::fs::File::open(cdx)?)?;
+            let archived_at = wbm::util::archive_times(&items);
+
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+            let edited = tweet_store
+                .find_edited(chrono::Duration::minutes(min_gap_minutes), |digest| {
+                    archived_at.get(digest).cloned()
+                })
+                .await?;
+
+            for id in edited {
+                println!("{}", id);
+            }
+        }
+        SubCommand::Compact { db } => {
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+            let report = tweet_store.compact().await?;
+
+            log::info!(
+                "Merged {} groups; removed {} rows",
+                report.groups,
+                report.removed
+            );
+        }
+        SubCommand::ExportJson { db } => {
+            let tweet_store = wbm::tweet::db::TweetStore::new(db, false)?;
+            let stdout = std::io::stdout();
+            let count = tweet_store
+                .export_json(std::io::BufWriter::new(stdout.lock()))
+                .await?;
+
+            log::info!("Exported {} rows", count);
+        }
         SubCommand::ScreenNames { db } => {
             let users = cli::read_stdin()?
                 .lines()
@@ -260,6 +410,12 @@ async fn main() -> Void {
     Ok(())
 }
 
+fn format_archived_at(archived_at: Option<chrono::NaiveDateTime>) -> String {
+    archived_at
+        .map(wbm::util::format_timestamp)
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
 #[derive(Parser)]
 #[clap(name = "wbmd", version, author)]
 struct Opts {
@@ -326,6 +482,15 @@ enum SubCommand {
         #[clap(short, long)]
         input: String,
     },
+    /// Verify files in another directory and move those with valid digests into the store
+    Promote {
+        /// The base directory
+        #[clap(short, long)]
+        dir: String,
+        /// The directory containing files to verify
+        #[clap(short, long)]
+        other: String,
+    },
     SaveTweets {
         /// The database file
         #[clap(short, long)]
@@ -333,7 +498,20 @@ enum SubCommand {
         /// The base directory
         #[clap(short, long)]
         store: String,
+        /// Log progress after this many files (0 to disable)
+        #[clap(long, default_value = "1000")]
+        progress_interval: usize,
+        /// Collapse runs of spaces in tweet text
+        #[clap(long)]
+        collapse_spaces: bool,
+        /// Normalize line endings in tweet text
+        #[clap(long)]
+        normalize_newlines: bool,
+        /// Apply Unicode NFC normalization to tweet text
+        #[clap(long)]
+        unicode_nfc: bool,
     },
+    /// Print stored tweets as CSV (text is printed as stored; see `SaveTweets --collapse-spaces`)
     Get {
         /// The database file
         #[clap(short, long)]
@@ -343,12 +521,60 @@ enum SubCommand {
         /// The database file
         #[clap(short, long)]
         db: String,
+        /// Read replies from the database in pages of this size
+        #[clap(long)]
+        page_size: Option<usize>,
     },
     Interactions {
         /// The database file
         #[clap(short, long)]
         db: String,
     },
+    /// List replies between two users (in either direction) in time order
+    Conversations {
+        /// The database file
+        #[clap(short, long)]
+        db: String,
+        /// Twitter user ID
+        user_a: u64,
+        /// Twitter user ID
+        user_b: u64,
+    },
+    /// Show the differences between archived versions of a tweet
+    Diff {
+        /// The database file
+        #[clap(short, long)]
+        db: String,
+        /// Optional CDX JSON file used to order versions by archive time
+        #[clap(short, long)]
+        cdx: Option<String>,
+        /// The status ID
+        id: u64,
+    },
+    /// List status IDs whose text changed between snapshots archived some time apart
+    FindEdited {
+        /// The database file
+        #[clap(short, long)]
+        db: String,
+        /// CDX JSON file providing archive times for the stored files
+        #[clap(short, long)]
+        cdx: String,
+        /// Ignore differences between snapshots archived within this many minutes
+        #[clap(long, default_value = "10")]
+        min_gap_minutes: i64,
+    },
+    /// Merge tweet rows that differ only in whitespace
+    Compact {
+        /// The database file
+        #[clap(short, long)]
+        db: String,
+    },
+    /// Export all tweets as newline-delimited JSON
+    ExportJson {
+        /// The database file
+        #[clap(short, long)]
+        db: String,
+    },
     ScreenNames {
         /// The database file
         #[clap(short, long)]
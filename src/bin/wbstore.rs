@@ -1,12 +1,17 @@
 use cancel_culture::{
     cli,
-    wbm::store::{Error, Store},
+    util::csv::RecordWriter,
+    wbm::{
+        self, server,
+        store::{Error, Store},
+        valid::{write_check_report, CheckStatus, ValidStore},
+    },
 };
 use clap::Parser;
 use flate2::{write::GzEncoder, Compression, GzBuilder};
 use futures::StreamExt;
-use std::collections::HashSet;
 use std::fs::File;
+use std::sync::Arc;
 use wayback_rs::Item;
 
 #[tokio::main]
@@ -14,7 +19,7 @@ async fn main() -> Result<(), Error> {
     let opts: Opts = Opts::parse();
     let _ = cli::init_logging(opts.verbose).unwrap();
 
-    let store = Store::load(opts.store_dir)?;
+    let store = Store::load(opts.store_dir)?.with_compression(Compression::new(opts.compression));
 
     match opts.command {
         SubCommand::Export(ExportQuery { name, query }) => {
@@ -32,6 +37,15 @@ async fn main() -> Result<(), Error> {
                 })
                 .await;
         }
+        SubCommand::Recover => {
+            let orphans = store.recover_orphans().await?;
+
+            for path in &orphans {
+                println!("{}", path.display());
+            }
+
+            log::info!("Moved {} unindexed data files", orphans.len());
+        }
         SubCommand::ComputeDigestsRaw => {
             store
                 .compute_all_digests_stream(opts.parallelism)
@@ -43,7 +57,20 @@ async fn main() -> Result<(), Error> {
                 .await;
         }
         SubCommand::Merge(MergeCommand { base, incoming }) => {
-            let exclusions = Store::merge_data(&base, &incoming)?;
+            let (exclusions, stats) = Store::merge_data(&base, &incoming)?;
+
+            eprintln!(
+                "{} incoming files excluded: {} identical, {} with bad digests, {} smaller with \
+                 both digests bad; {} kept: {} with no collision, {} replacing bad base files",
+                stats.excluded(),
+                stats.identical,
+                stats.incoming_bad,
+                stats.smaller_excluded,
+                stats.no_collision + stats.base_bad,
+                stats.no_collision,
+                stats.base_bad
+            );
+
             for exclusion in exclusions {
                 match exclusion.into_os_string().into_string() {
                     Ok(p) => println!("{}", p),
@@ -62,115 +89,162 @@ async fn main() -> Result<(), Error> {
                 log::warn!("{} does not exist", value);
             }
         }
-        SubCommand::ListValid(CheckValidCommand { dir }) => {
-            use std::fs::read_dir;
-
-            let mut sub_dirs = read_dir(dir)?.collect::<std::result::Result<Vec<_>, _>>()?;
-            sub_dirs.sort_by_key(|entry| entry.file_name());
-            let mut dir_names = HashSet::new();
-            dir_names.extend(('2'..='7').map(|c| c.to_string()));
-            dir_names.extend(('A'..='Z').map(|c| c.to_string()));
-
-            for entry in sub_dirs {
-                let name = entry.file_name().into_string().unwrap();
-                if entry.file_type()?.is_dir() {
-                    if dir_names.contains(&name) {
-                        let files = read_dir(entry.path())?;
-
-                        for file_result in files {
-                            let file_entry = file_result?;
-                            let file_name = file_entry.file_name().into_string().unwrap();
-
-                            if file_entry.file_type()?.is_file() {
-                                if file_name.starts_with(&name) {
-                                    match file_entry.path().file_stem().and_then(|os| os.to_str()) {
-                                        Some(stem) => println!("{}", stem),
-                                        None => {
-                                            log::error!("Skipping invalid file name: {}", file_name)
-                                        }
-                                    }
-                                } else {
-                                    log::error!("Skipping invalid file name: {}", file_name);
-                                }
-                            } else {
-                                log::error!("Expected directory, found: {}", file_name);
-                            }
-                        }
-                    } else {
-                        log::error!("Unexpected directory: {}", name);
-                    }
-                } else {
-                    log::error!("Expected directory, found file: {}", name);
+        SubCommand::Fetch(FetchCommand { url }) => {
+            let (timestamp, original) = match wbm::util::parse_wayback_url(&url) {
+                Some(parts) => parts,
+                None => {
+                    log::error!("Invalid Wayback Machine URL: {}", url);
+                    log::logger().flush();
+                    std::process::exit(1);
                 }
-            }
-        }
-        SubCommand::CheckValid(CheckValidCommand { dir }) => {
-            use std::fs::read_dir;
+            };
+            let timestamp = wbm::util::format_timestamp(timestamp);
+
+            let items = wayback_rs::cdx::IndexClient::default()
+                .search(&original, Some(&timestamp), None)
+                .await?;
 
-            let mut sub_dirs = read_dir(dir)?.collect::<std::result::Result<Vec<_>, _>>()?;
-            sub_dirs.sort_by_key(|entry| entry.file_name());
-            let mut dir_names = HashSet::new();
-            dir_names.extend(('2'..='7').map(|c| c.to_string()));
-            dir_names.extend(('A'..='Z').map(|c| c.to_string()));
+            match items
+                .into_iter()
+                .find(|item| wbm::util::format_timestamp(item.archived_at) == timestamp)
+            {
+                Some(item) => {
+                    let downloader = wayback_rs::Downloader::default();
+                    let digest = store
+                        .fetch(&item, |item| async move {
+                            downloader.download_item(&item).await
+                        })
+                        .await?;
 
+                    log::info!("Added {} with digest {}", item.url, digest);
+                }
+                None => {
+                    log::error!(
+                        "No snapshot of {} at {} in the CDX index",
+                        original,
+                        timestamp
+                    );
+                    log::logger().flush();
+                    std::process::exit(1);
+                }
+            }
+        }
+        SubCommand::Has(HasDigest { value }) => {
+            if store.contains_digest(&value).await {
+                for item in store.items_by_digest(&value).await {
+                    println!("{}", item.url);
+                }
+            } else {
+                log::warn!("{} is not in the store", value);
+                log::logger().flush();
+                std::process::exit(1);
+            }
+        }
+        SubCommand::ListValid(ListValidCommand { dir }) => {
+            for result in ValidStore::new(dir).paths() {
+                match result {
+                    Ok((name, _)) => println!("{}", name),
+                    Err(error) => log::error!("{}", error),
+                }
+            }
+        }
+        SubCommand::CheckValid(CheckValidCommand { dir, report }) => {
+            let store = ValidStore::new(dir);
+            let mut results = vec![];
             let mut valid = 0;
             let mut invalid = 0;
 
-            for entry in sub_dirs {
-                let name = entry.file_name().into_string().unwrap();
-                log::info!("Checking: {}", name);
-                if entry.file_type()?.is_dir() {
-                    if dir_names.contains(&name) {
-                        let files = read_dir(entry.path())?;
-
-                        for file_result in files {
-                            let file_entry = file_result?;
-                            let file_name = file_entry.file_name().into_string().unwrap();
-
-                            if file_entry.file_type()?.is_file() {
-                                if file_name.starts_with(&name) {
-                                    let mut file = File::open(file_entry.path())?;
-                                    match Store::compute_digest_gz(&mut file) {
-                                        Ok(actual) => {
-                                            let expected = format!("{}.gz", actual);
-
-                                            if file_name != expected {
-                                                invalid += 1;
-                                                log::error!("Invalid file: {}/{}", name, file_name);
-                                            } else {
-                                                valid += 1;
-                                            }
-                                        }
-                                        Err(error) => {
-                                            log::error!(
-                                                "Error reading file: {} ({:?})",
-                                                file_name,
-                                                error
-                                            );
-                                        }
-                                    }
-                                } else {
-                                    log::error!("Skipping invalid file name: {}", file_name);
-                                }
-                            } else {
-                                log::error!("Expected directory, found: {}", file_name);
+            let mut stream = Box::pin(store.check_all_parallel(opts.parallelism));
+
+            while let Some(result) = stream.next().await {
+                match result {
+                    Ok(result) => {
+                        match result.status {
+                            CheckStatus::Valid => valid += 1,
+                            CheckStatus::Invalid => {
+                                invalid += 1;
+                                log::error!("Invalid file: {}", result.expected);
                             }
+                            CheckStatus::Error => {}
+                        }
+
+                        if report.is_some() {
+                            results.push(result);
                         }
-                    } else {
-                        log::error!("Unexpected directory: {}", name);
                     }
-                } else {
-                    log::error!("Expected directory, found file: {}", name);
+                    Err(error) => log::error!("{}", error),
                 }
             }
 
             log::info!("Valid: {}; invalid: {}", valid, invalid);
+
+            if let Some(report_path) = report {
+                write_check_report(File::create(report_path)?, &results)?;
+            }
         }
-        SubCommand::Digest => {
-            let content = cli::read_stdin()?;
-            let mut bytes = content.as_bytes();
-            let digest = Store::compute_digest(&mut bytes)?;
-            println!("{}", digest);
+        SubCommand::Missing(MissingCommand { cdx }) => {
+            let items = wayback_rs::cdx::IndexClient::load_json(File::open(cdx)?)?;
+
+            let mut out = RecordWriter::stdout();
+
+            for item in store.missing_items(&items).await {
+                out.write_fields(&[
+                    item.url.clone(),
+                    wbm::util::format_timestamp(item.archived_at),
+                ])?;
+            }
+
+            out.flush()?;
+        }
+        SubCommand::Shard(ShardCommand { count, out_prefix }) => {
+            for (i, digests) in store.shard_plan(count).await.into_iter().enumerate() {
+                let name = format!("{}-{}", out_prefix, i);
+                log::info!("Exporting {} items to {}.tgz", digests.len(), name);
+
+                let file = File::create(format!("{}.tgz", name))?;
+                let encoder = GzEncoder::new(file, Compression::default());
+                store
+                    .export_digests(&name, encoder, &digests.into_iter().collect())
+                    .await?;
+            }
+        }
+        SubCommand::Serve(ServeCommand { port }) => {
+            let listener = std::net::TcpListener::bind(("127.0.0.1", port))?;
+            log::info!("Serving store at http://{}", listener.local_addr()?);
+
+            if let Err(error) = server::serve(Arc::new(store), listener).await {
+                log::error!("Server error: {:?}", error);
+            }
+        }
+        SubCommand::Digest(DigestCommand { file, url }) => {
+            let bytes = match (file, url) {
+                (Some(path), _) => Some(std::fs::read(path)?),
+                (None, Some(url)) => Some(
+                    reqwest::get(url)
+                        .await?
+                        .error_for_status()?
+                        .bytes()
+                        .await?
+                        .to_vec(),
+                ),
+                (None, None) => None,
+            };
+
+            match bytes {
+                Some(bytes) => match Store::compute_content_digests(&bytes)? {
+                    (raw, Some(decompressed)) => {
+                        println!("raw: {}", raw);
+                        println!("gz: {}", decompressed);
+                    }
+                    (raw, None) => println!("{}", raw),
+                },
+                None => {
+                    let content = cli::read_stdin()?;
+                    let mut bytes = content.as_bytes();
+                    let digest = Store::compute_digest(&mut bytes)?;
+                    println!("{}", digest);
+                }
+            }
         }
     }
 
@@ -191,6 +265,13 @@ struct Opts {
     /// Level of parallelism
     #[clap(short, long, default_value = "6")]
     parallelism: usize,
+    /// Gzip compression level (0-9) for new files
+    #[clap(
+        long,
+        default_value_t = wbm::store::DEFAULT_COMPRESSION_LEVEL,
+        value_parser = clap::value_parser!(u32).range(0..=9)
+    )]
+    compression: u32,
     #[clap(subcommand)]
     command: SubCommand,
 }
@@ -203,10 +284,20 @@ enum SubCommand {
     ComputeDigestsRaw,
     Merge(MergeCommand),
     Check(CheckDigest),
-    /// Compute digest for the input from stdin
-    Digest,
+    Has(HasDigest),
+    Fetch(FetchCommand),
+    /// Compute digest for a file, a URL, or the input from stdin
+    Digest(DigestCommand),
     CheckValid(CheckValidCommand),
-    ListValid(CheckValidCommand),
+    ListValid(ListValidCommand),
+    Missing(MissingCommand),
+    Serve(ServeCommand),
+    Shard(ShardCommand),
+    /// Move data files that aren't in the contents file into the orphans directory
+    ///
+    /// These are left behind by interrupted downloads. Don't run this while another process is
+    /// writing to the store.
+    Recover,
 }
 
 /// Export an archive for items whose URL contains the query string
@@ -237,6 +328,31 @@ struct CheckDigest {
     value: String,
 }
 
+/// Check whether the store's index contains a digest (exits with 1 if not)
+#[derive(Parser)]
+struct HasDigest {
+    /// Digest to look up
+    value: String,
+}
+
+/// Download a single snapshot, verify its digest, and add it to the store
+#[derive(Parser)]
+struct FetchCommand {
+    /// Wayback Machine URL (e.g. https://web.archive.org/web/20190916233235/https://twitter.com/...)
+    url: String,
+}
+
+/// Compute the digest of some content
+#[derive(Parser)]
+struct DigestCommand {
+    /// File to read (gzipped or raw)
+    #[clap(short, long, conflicts_with = "url")]
+    file: Option<String>,
+    /// URL to download
+    #[clap(short, long)]
+    url: Option<String>,
+}
+
 /// Re-download broken files
 #[derive(Parser)]
 struct FixCommand {
@@ -254,6 +370,44 @@ struct CheckValidCommand {
     /// Base directory
     #[clap(short, long)]
     dir: String,
+    /// Optional CSV file for a report on every file checked
+    #[clap(short, long)]
+    report: Option<String>,
+}
+
+/// List a directory of known valid files
+#[derive(Parser)]
+struct ListValidCommand {
+    /// Base directory
+    #[clap(short, long)]
+    dir: String,
+}
+
+/// List items in a CDX JSON file that aren't in the store
+#[derive(Parser)]
+struct MissingCommand {
+    /// CDX JSON file
+    #[clap(short, long)]
+    cdx: String,
+}
+
+/// Serve the store's contents over HTTP on localhost
+#[derive(Parser)]
+struct ServeCommand {
+    /// Port to listen on
+    #[clap(short = 'P', long, default_value = "8080")]
+    port: u16,
+}
+
+/// Split the store into archives with roughly equal sizes
+#[derive(Parser)]
+struct ShardCommand {
+    /// Number of archives
+    #[clap(short, long)]
+    count: usize,
+    /// Prefix for archive names (each is written to {prefix}-{index}.tgz)
+    #[clap(short, long)]
+    out_prefix: String,
 }
 
 async fn save_export_tgz(store: &Store, name: &str, query: &str) -> Result<(), Error> {
@@ -1,3 +1,4 @@
+pub mod pdf;
 mod scroller;
 pub mod twitter;
 
@@ -6,12 +7,28 @@ pub use scroller::Scroller;
 use fantoccini::error::NewSessionError;
 use fantoccini::{Client, ClientBuilder};
 
+/// The browser backends supported by [`make_client`].
+pub const BROWSERS: [&str; 2] = ["chrome", "firefox"];
+
 pub async fn make_client(
     name: &str,
     headless: bool,
     host: Option<&str>,
     port: Option<u16>,
 ) -> Result<Client, NewSessionError> {
+    make_scaled_client(name, headless, host, port, 1.0).await
+}
+
+/// Create a client with the given device pixel ratio (for higher-resolution screenshots).
+pub async fn make_scaled_client(
+    name: &str,
+    headless: bool,
+    host: Option<&str>,
+    port: Option<u16>,
+    scale: f64,
+) -> Result<Client, NewSessionError> {
+    let scaled = (scale - 1.0).abs() > f64::EPSILON;
+
     match name {
         "firefox" => {
             let mut caps = serde_json::map::Map::new();
@@ -20,7 +37,14 @@ pub async fn make_client(
             } else {
                 serde_json::json!([])
             };
-            let opts = { serde_json::json!({ "args": args }) };
+            let opts = if scaled {
+                serde_json::json!({
+                    "args": args,
+                    "prefs": { "layout.css.devPixelsPerPx": scale.to_string() }
+                })
+            } else {
+                serde_json::json!({ "args": args })
+            };
             caps.insert("moz:firefoxOptions".to_string(), opts.clone());
             ClientBuilder::rustls()
                 .capabilities(caps)
@@ -29,7 +53,7 @@ pub async fn make_client(
         }
         "chrome" => {
             let mut caps = serde_json::map::Map::new();
-            let args = if headless {
+            let mut args = if headless {
                 serde_json::json!([
                     "--headless",
                     "--disable-gpu",
@@ -39,6 +63,11 @@ pub async fn make_client(
             } else {
                 serde_json::json!(["--disable-gpu", "--no-sandbox", "--disable-dev-shm-usage"])
             };
+            if scaled {
+                if let Some(values) = args.as_array_mut() {
+                    values.push(format!("--force-device-scale-factor={}", scale).into());
+                }
+            }
             let opts = serde_json::json!({
                 "args": args,
                 "binary":
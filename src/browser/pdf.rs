@@ -0,0 +1,246 @@
+use chrono::{DateTime, Utc};
+use flate2::{write::ZlibEncoder, Compression};
+use image::DynamicImage;
+use std::io::Write;
+
+const FOOTER_FONT_SIZE: u32 = 10;
+const FOOTER_LINE_HEIGHT: u32 = 14;
+const FOOTER_MARGIN: u32 = 8;
+
+/// Metadata shown in the footer of a PDF capture.
+#[derive(Clone, Debug, Default)]
+pub struct PdfMetadata {
+    pub url: Option<String>,
+    pub captured_at: Option<DateTime<Utc>>,
+}
+
+impl PdfMetadata {
+    fn footer_lines(&self) -> Vec<String> {
+        let mut lines = vec![];
+
+        if let Some(url) = &self.url {
+            lines.push(url.clone());
+        }
+
+        if let Some(captured_at) = self.captured_at {
+            lines.push(format!(
+                "Captured at {}",
+                captured_at.format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+        }
+
+        lines
+    }
+}
+
+/// Render an image as a single-page PDF, with the metadata as (selectable) footer text.
+///
+/// The page is sized so that each pixel is one point.
+pub fn to_pdf(image: &DynamicImage, metadata: &PdfMetadata) -> std::io::Result<Vec<u8>> {
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(rgb.as_raw())?;
+    let image_data = encoder.finish()?;
+
+    let lines = metadata.footer_lines();
+    let footer_height = if lines.is_empty() {
+        0
+    } else {
+        lines.len() as u32 * FOOTER_LINE_HEIGHT + 2 * FOOTER_MARGIN
+    };
+    let page_height = height + footer_height;
+
+    let mut content = format!(
+        "q {} 0 0 {} 0 {} cm /Im0 Do Q\n",
+        width, height, footer_height
+    );
+
+    for (i, line) in lines.iter().enumerate() {
+        let y = footer_height - FOOTER_MARGIN - (i as u32 + 1) * FOOTER_LINE_HEIGHT + 4;
+        content.push_str(&format!(
+            "BT /F1 {} Tf {} {} Td ({}) Tj ET\n",
+            FOOTER_FONT_SIZE,
+            FOOTER_MARGIN,
+            y,
+            escape_text(line)
+        ));
+    }
+
+    let mut writer = PdfWriter::new();
+
+    writer.object(b"<< /Type /Catalog /Pages 2 0 R >>");
+    writer.object(b"<< /Type /Pages /Kids [3 0 R] /Count 1 >>");
+    writer.object(
+        format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R \
+             /Resources << /XObject << /Im0 5 0 R >> /Font << /F1 6 0 R >> >> >>",
+            width, page_height
+        )
+        .as_bytes(),
+    );
+    writer.stream("", content.as_bytes());
+    writer.stream(
+        &format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8 /Filter /FlateDecode",
+            width, height
+        ),
+        &image_data,
+    );
+    writer.object(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica >>");
+
+    let mut info = String::from("<< /Producer (cancel-culture)");
+    if let Some(url) = &metadata.url {
+        info.push_str(&format!(" /Title ({})", escape_text(url)));
+    }
+    if let Some(captured_at) = metadata.captured_at {
+        info.push_str(&format!(
+            " /CreationDate (D:{}Z)",
+            captured_at.format("%Y%m%d%H%M%S")
+        ));
+    }
+    info.push_str(" >>");
+    writer.object(info.as_bytes());
+
+    Ok(writer.finish())
+}
+
+/// Escape text for a PDF literal string (non-ASCII characters are replaced).
+fn escape_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\\' | '(' | ')' => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' '..='~' => result.push(c),
+            _ => result.push('?'),
+        }
+    }
+
+    result
+}
+
+/// Writes numbered objects and the cross-reference table.
+///
+/// The last object is used as the document information dictionary.
+struct PdfWriter {
+    buffer: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn new() -> PdfWriter {
+        PdfWriter {
+            buffer: b"%PDF-1.4\n".to_vec(),
+            offsets: vec![],
+        }
+    }
+
+    fn start_object(&mut self) {
+        self.offsets.push(self.buffer.len());
+        self.buffer
+            .extend_from_slice(format!("{} 0 obj\n", self.offsets.len()).as_bytes());
+    }
+
+    fn object(&mut self, body: &[u8]) {
+        self.start_object();
+        self.buffer.extend_from_slice(body);
+        self.buffer.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn stream(&mut self, dictionary: &str, data: &[u8]) {
+        self.start_object();
+        self.buffer.extend_from_slice(
+            format!("<< {} /Length {} >>\nstream\n", dictionary, data.len()).as_bytes(),
+        );
+        self.buffer.extend_from_slice(data);
+        self.buffer.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let xref_offset = self.buffer.len();
+        let count = self.offsets.len() + 1;
+
+        self.buffer
+            .extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", count).as_bytes());
+
+        for offset in &self.offsets {
+            self.buffer
+                .extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+        }
+
+        self.buffer.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+                count,
+                count - 1,
+                xref_offset
+            )
+            .as_bytes(),
+        );
+
+        self.buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PdfMetadata;
+    use chrono::{TimeZone, Utc};
+    use image::{DynamicImage, RgbaImage};
+
+    #[test]
+    fn test_to_pdf() {
+        let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            40,
+            20,
+            image::Rgba([29, 161, 242, 255]),
+        ));
+        let metadata = PdfMetadata {
+            url: Some("https://twitter.com/jdegoes/status/1170761943067631621".to_string()),
+            captured_at: Some(Utc.with_ymd_and_hms(2021, 1, 2, 3, 4, 5).unwrap()),
+        };
+
+        let pdf = super::to_pdf(&image, &metadata).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(pdf.starts_with(b"%PDF-1.4\n"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Subtype /Image /Width 40 /Height 20"));
+        assert!(text.contains("(https://twitter.com/jdegoes/status/1170761943067631621) Tj"));
+        assert!(text.contains("(Captured at 2021-01-02 03:04:05 UTC) Tj"));
+
+        // The startxref offset points to the cross-reference table, whose entries point to
+        // the objects.
+        let xref_offset = text
+            .lines()
+            .rev()
+            .nth(1)
+            .and_then(|line| line.parse::<usize>().ok())
+            .unwrap();
+
+        assert!(pdf[xref_offset..].starts_with(b"xref\n0 8\n"));
+
+        let entries = String::from_utf8_lossy(&pdf[xref_offset..])
+            .lines()
+            .skip(3)
+            .take(7)
+            .map(|line| line[..10].parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+
+        for (i, offset) in entries.into_iter().enumerate() {
+            assert!(pdf[offset..].starts_with(format!("{} 0 obj\n", i + 1).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_escape_text() {
+        assert_eq!(super::escape_text("a (b) \\ c"), "a \\(b\\) \\\\ c");
+        assert_eq!(super::escape_text("caf\u{e9}"), "caf?");
+    }
+}
@@ -3,23 +3,126 @@ pub mod search;
 mod tweet_lister;
 pub use tweet_lister::TweetLister;
 
-use fantoccini::error::CmdError;
+use fantoccini::elements::Element;
+use fantoccini::error::{CmdError, ErrorStatus, WebDriver};
 use fantoccini::{Client, Locator};
-use image::{DynamicImage, GenericImageView, Rgba};
+use futures::Future;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use std::time::Duration;
 
 const HEADING_LOC: Locator = Locator::XPath("//main//h1[@role='heading']");
 
-pub async fn status_exists(client: &mut Client, id: u64) -> Result<bool, CmdError> {
+/// Default time to wait for an element to appear before giving up.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait for an element to appear, failing with `CmdError::WaitTimeout` after `timeout`.
+pub async fn wait_for_element(
+    client: &Client,
+    locator: Locator<'_>,
+    timeout: Duration,
+) -> Result<Element, CmdError> {
+    client.wait().at_most(timeout).for_element(locator).await
+}
+
+/// Number of times to retry a browser step that fails because the page re-rendered.
+const STALE_ELEMENT_RETRIES: usize = 2;
+
+fn is_stale_element(error: &CmdError) -> bool {
+    matches!(
+        error,
+        CmdError::Standard(WebDriver {
+            error: ErrorStatus::StaleElementReference,
+            ..
+        })
+    )
+}
+
+/// Run a browser step, retrying it if it fails with a stale-element error.
+async fn retry_stale<T, F, Fut>(mut step: F) -> Result<T, CmdError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, CmdError>>,
+{
+    let mut attempts = 0;
+
+    loop {
+        match step().await {
+            Err(error) if is_stale_element(&error) && attempts < STALE_ELEMENT_RETRIES => {
+                attempts += 1;
+                log::warn!("Stale element; retrying (attempt {})", attempts + 1);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Run a browser step whose failure shouldn't abort the larger operation.
+async fn non_fatal<T, F, Fut>(description: &str, step: F) -> Option<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, CmdError>>,
+{
+    match retry_stale(step).await {
+        Ok(value) => Some(value),
+        Err(error) => {
+            log::warn!("Ignoring failure to {}: {:?}", description, error);
+            None
+        }
+    }
+}
+
+/// Check whether a status exists.
+///
+/// Fails with `ScreenshotError::RateLimited` if Twitter serves its error interstitial instead.
+pub async fn status_exists(
+    client: &mut Client,
+    id: u64,
+    timeout: Duration,
+) -> Result<bool, ScreenshotError> {
     let url = format!("https://twitter.com/tweet/status/{}", id);
 
     client.goto(&url).await?;
-    let heading = client.wait().forever().for_element(HEADING_LOC).await?;
 
-    Ok(heading
-        .attr("data-testid")
-        .await?
-        .map_or(true, |v| v != "error-detail"))
+    let client = &*client;
+    let test_id = retry_stale(|| async move {
+        wait_for_element(client, HEADING_LOC, timeout)
+            .await?
+            .attr("data-testid")
+            .await
+    })
+    .await;
+    let test_id = check_for_interstitial(client, test_id).await?;
+
+    Ok(test_id.map_or(true, |v| v != "error-detail"))
+}
+
+/// Check whether the current page is Twitter's "Something went wrong" interstitial.
+pub async fn is_error_interstitial(client: &Client) -> Result<bool, CmdError> {
+    let source = client.source().await?;
+
+    Ok(parser::is_error_interstitial(
+        &scraper::Html::parse_document(&source),
+    ))
+}
+
+/// Replace the result of a page load with `ScreenshotError::RateLimited` if the page is the error
+/// interstitial (which may also cause waiting for the tweet to time out).
+async fn check_for_interstitial<T>(
+    client: &Client,
+    result: Result<T, CmdError>,
+) -> Result<T, ScreenshotError> {
+    match result {
+        Ok(_) | Err(CmdError::WaitTimeout) => {
+            if is_error_interstitial(client).await? {
+                Err(ScreenshotError::RateLimited)
+            } else {
+                Ok(result?)
+            }
+        }
+        Err(error) => Err(error.into()),
+    }
 }
 
 pub async fn is_logged_in(client: &mut Client) -> Result<bool, CmdError> {
@@ -28,21 +131,28 @@ pub async fn is_logged_in(client: &mut Client) -> Result<bool, CmdError> {
     Ok(current.as_str() == "https://twitter.com/home")
 }
 
-pub async fn log_in(client: &mut Client, username: &str, password: &str) -> Result<bool, CmdError> {
+pub async fn log_in(
+    client: &mut Client,
+    username: &str,
+    password: &str,
+    timeout: Duration,
+) -> Result<bool, CmdError> {
     client.goto("https://twitter.com/login").await?;
 
-    let username_input = client
-        .wait()
-        .forever()
-        .for_element(Locator::Css("input[name='session[username_or_email]']"))
-        .await?;
+    let username_input = wait_for_element(
+        client,
+        Locator::Css("input[name='session[username_or_email]']"),
+        timeout,
+    )
+    .await?;
     username_input.send_keys(username).await?;
 
-    let password_input = client
-        .wait()
-        .forever()
-        .for_element(Locator::Css("input[name='session[password]']"))
-        .await?;
+    let password_input = wait_for_element(
+        client,
+        Locator::Css("input[name='session[password]']"),
+        timeout,
+    )
+    .await?;
     password_input
         .send_keys(&(String::from(password) + "\n"))
         .await?;
@@ -50,40 +160,169 @@ pub async fn log_in(client: &mut Client, username: &str, password: &str) -> Resu
     is_logged_in(client).await
 }
 
-pub async fn shoot_tweet_bytes(
+async fn load_tweet_page(
     client: &mut Client,
     status_id: u64,
     width: u32,
     height: u32,
     wait_for_load: Option<Duration>,
-) -> Result<Vec<u8>, fantoccini::error::CmdError> {
+    timeout: Duration,
+) -> Result<(), CmdError> {
     client.set_window_size(width, height).await?;
 
     let url = format!("https://twitter.com/tweet/status/{}", status_id);
     client.goto(&url).await?;
 
-    let locator = fantoccini::Locator::XPath("//main//h1[@role='heading']");
-    client.wait().forever().for_element(locator).await?;
+    wait_for_element(client, HEADING_LOC, timeout).await?;
 
     if let Some(duration) = wait_for_load {
         tokio::time::sleep(duration).await;
     }
 
-    // There may be a cookies layer. If so we hide it.
-    client
+    // There may be a cookies layer. If so we hide it (but it's fine if this fails).
+    let client = &*client;
+    non_fatal("hide cookie layer", || hide_cookie_layer(client)).await;
+
+    Ok(())
+}
+
+/// Hide the cookie consent layer, if there is one.
+///
+/// Returns `false` if the page has no layers to hide (which is common for logged-in sessions).
+pub async fn hide_cookie_layer(client: &Client) -> Result<bool, CmdError> {
+    let hidden = client
         .execute(
-            "document.getElementById('layers').children[0].style.display = 'none';",
+            "const layers = document.getElementById('layers');
+             if (!layers || !layers.children.length) { return false; }
+             layers.children[0].style.display = 'none';
+             return true;",
             vec![],
         )
         .await?;
 
+    Ok(hidden.as_bool().unwrap_or(false))
+}
+
+pub async fn shoot_tweet_bytes(
+    client: &mut Client,
+    status_id: u64,
+    width: u32,
+    height: u32,
+    wait_for_load: Option<Duration>,
+    timeout: Duration,
+) -> Result<Vec<u8>, fantoccini::error::CmdError> {
+    load_tweet_page(client, status_id, width, height, wait_for_load, timeout).await?;
+
     client.screenshot().await
 }
 
+/// Screenshot only the tweet's `article` element.
+///
+/// Returns `None` if the element can't be located on the page.
+pub async fn shoot_tweet_element_bytes(
+    client: &mut Client,
+    status_id: u64,
+    width: u32,
+    height: u32,
+    wait_for_load: Option<Duration>,
+    timeout: Duration,
+) -> Result<Option<Vec<u8>>, fantoccini::error::CmdError> {
+    load_tweet_page(client, status_id, width, height, wait_for_load, timeout).await?;
+
+    loaded_tweet_element_bytes(client, status_id).await
+}
+
+/// Screenshot the tweet's `article` element on the current page.
+async fn loaded_tweet_element_bytes(
+    client: &Client,
+    status_id: u64,
+) -> Result<Option<Vec<u8>>, fantoccini::error::CmdError> {
+    let xpath = format!(
+        "//main//article[@data-testid='tweet'][.//a[contains(@href, '/status/{}')]]",
+        status_id
+    );
+
+    let xpath = &xpath;
+
+    retry_stale(|| async move {
+        match client.find(Locator::XPath(xpath)).await {
+            Ok(element) => element.screenshot().await.map(Some),
+            Err(error) if error.is_miss() => Ok(None),
+            Err(error) => Err(error),
+        }
+    })
+    .await
+}
+
+/// Compute a window width that fits content that overflows its element, up to a maximum.
+fn fitted_width(width: u32, scroll_width: u32, client_width: u32, max_width: u32) -> u32 {
+    if scroll_width > client_width {
+        (width + (scroll_width - client_width)).min(max_width.max(width))
+    } else {
+        width
+    }
+}
+
+/// Widen the window if the tweet's content overflows the tweet element (up to `max_width`).
+///
+/// The page must already be loaded. Returns the new window width.
+pub async fn fit_window_to_tweet(
+    client: &mut Client,
+    width: u32,
+    height: u32,
+    max_width: u32,
+) -> Result<u32, CmdError> {
+    let measurement = client
+        .execute(
+            "const article = document.querySelector(\"main article[data-testid='tweet']\");
+             return article ? [article.scrollWidth, article.clientWidth] : null;",
+            vec![],
+        )
+        .await?;
+
+    let widths = measurement.as_array().and_then(|values| {
+        values
+            .first()
+            .and_then(|value| value.as_u64())
+            .zip(values.get(1).and_then(|value| value.as_u64()))
+    });
+
+    match widths {
+        Some((scroll_width, client_width)) => {
+            let new_width =
+                fitted_width(width, scroll_width as u32, client_width as u32, max_width);
+
+            if new_width != width {
+                log::info!(
+                    "Resizing window from {} to {} to fit tweet",
+                    width,
+                    new_width
+                );
+                client.set_window_size(new_width, height).await?;
+            }
+
+            Ok(new_width)
+        }
+        None => Ok(width),
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 pub enum ScreenshotError {
+    #[error("Timed out waiting for the page to load")]
+    Timeout,
+    #[error("Invalid tweet URL or status ID: {0}")]
+    InvalidTweet(String),
+    #[error("Unable to crop tweet")]
+    Crop,
+    #[error("Twitter served an error page instead of the tweet (try again later)")]
+    RateLimited,
+    #[error("Unsupported browser: {0}")]
+    UnsupportedBrowser(String),
+    #[error("Browser connection error")]
+    Connection(#[from] fantoccini::error::NewSessionError),
     #[error("Download error")]
-    Download(#[from] fantoccini::error::CmdError),
+    Download(fantoccini::error::CmdError),
     #[error("Image decoding error")]
     ImageDecoding(#[from] image::error::ImageError),
 }
@@ -94,12 +333,421 @@ pub async fn shoot_tweet(
     width: u32,
     height: u32,
     wait_for_load: Option<Duration>,
+    timeout: Duration,
 ) -> Result<DynamicImage, ScreenshotError> {
-    let bytes = shoot_tweet_bytes(client, status_id, width, height, wait_for_load).await?;
+    let bytes = shoot_tweet_bytes(client, status_id, width, height, wait_for_load, timeout).await;
+    let bytes = check_for_interstitial(client, bytes).await?;
 
     Ok(image::load_from_memory(&bytes)?)
 }
 
+/// A screenshot of a tweet.
+#[derive(Clone, Debug)]
+pub struct Screenshot {
+    pub image: DynamicImage,
+    /// Whether the image was cropped to the tweet (if not it's the full window screenshot).
+    pub cropped: bool,
+}
+
+/// Which image files to write when capturing a tweet.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ScreenshotOutputs {
+    /// Both the full window screenshot and the cropped tweet.
+    #[default]
+    Both,
+    CropOnly,
+    FullOnly,
+}
+
+impl ScreenshotOutputs {
+    pub fn from_flags(crop_only: bool, full_only: bool) -> ScreenshotOutputs {
+        match (crop_only, full_only) {
+            (true, false) => ScreenshotOutputs::CropOnly,
+            (false, true) => ScreenshotOutputs::FullOnly,
+            _ => ScreenshotOutputs::Both,
+        }
+    }
+
+    pub fn includes_full(&self) -> bool {
+        *self != ScreenshotOutputs::CropOnly
+    }
+
+    pub fn includes_crop(&self) -> bool {
+        *self != ScreenshotOutputs::FullOnly
+    }
+
+    /// The names of the full and cropped files to write for a status.
+    ///
+    /// No cropped file is written if cropping failed.
+    pub fn file_names(&self, status_id: u64, cropped: bool) -> (Option<String>, Option<String>) {
+        (
+            Some(format!("{}-full.png", status_id)).filter(|_| self.includes_full()),
+            Some(format!("{}.png", status_id)).filter(|_| self.includes_crop() && cropped),
+        )
+    }
+}
+
+/// Crop a window screenshot to the tweet, returning the full image if cropping fails.
+pub fn crop_or_full(image: RgbaImage) -> Screenshot {
+    crop_or_full_with_mode(image, CropMode::TextOnly)
+}
+
+/// Crop a window screenshot to the tweet using the given mode, returning the full image if
+/// cropping fails.
+pub fn crop_or_full_with_mode(image: RgbaImage, mode: CropMode) -> Screenshot {
+    let (width, height) = image.dimensions();
+
+    match crop_tweet_with_mode(&image, mode) {
+        Some((x, y, w, h))
+            if w > 0
+                && h > 0
+                && x as u64 + w as u64 <= width as u64
+                && y as u64 + h as u64 <= height as u64 =>
+        {
+            Screenshot {
+                image: DynamicImage::ImageRgba8(image).crop(x, y, w, h),
+                cropped: true,
+            }
+        }
+        rectangle => {
+            log::warn!(
+                "Unable to crop screenshot (found {:?}); using full image",
+                rectangle
+            );
+
+            Screenshot {
+                image: DynamicImage::ImageRgba8(image),
+                cropped: false,
+            }
+        }
+    }
+}
+
+/// Screenshot just the tweet, falling back to cropping a window screenshot if the tweet element
+/// can't be located.
+///
+/// The tweet element includes the action bar, so the fallback crop uses
+/// [`CropMode::IncludeActions`]. If the fallback is needed and cropping fails, the full window
+/// screenshot is returned.
+pub async fn shoot_tweet_cropped(
+    client: &mut Client,
+    status_id: u64,
+    width: u32,
+    height: u32,
+    wait_for_load: Option<Duration>,
+    timeout: Duration,
+) -> Result<Screenshot, ScreenshotError> {
+    let loaded = load_tweet_page(client, status_id, width, height, wait_for_load, timeout).await;
+    check_for_interstitial(client, loaded).await?;
+
+    shoot_loaded_tweet_cropped(client, status_id, CropMode::IncludeActions).await
+}
+
+/// Screenshot just the tweet on the current page.
+///
+/// The tweet element always includes the action bar, so it's only used for
+/// [`CropMode::IncludeActions`] (falling back to cropping a window screenshot from the same page
+/// load if it can't be located). For [`CropMode::TextOnly`] the window screenshot is cropped.
+async fn shoot_loaded_tweet_cropped(
+    client: &Client,
+    status_id: u64,
+    mode: CropMode,
+) -> Result<Screenshot, ScreenshotError> {
+    if mode == CropMode::IncludeActions {
+        if let Some(bytes) = loaded_tweet_element_bytes(client, status_id).await? {
+            return Ok(Screenshot {
+                image: image::load_from_memory(&bytes)?,
+                cropped: true,
+            });
+        }
+
+        log::warn!(
+            "Unable to locate tweet element for {}; cropping window screenshot",
+            status_id
+        );
+    }
+
+    let bytes = client.screenshot().await?;
+
+    Ok(crop_or_full_with_mode(
+        image::load_from_memory(&bytes)?.into_rgba8(),
+        mode,
+    ))
+}
+
+/// Twitter's display themes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Theme {
+    Light,
+    Dim,
+    LightsOut,
+}
+
+impl Theme {
+    fn night_mode(&self) -> u8 {
+        match self {
+            Theme::Light => 0,
+            Theme::Dim => 1,
+            Theme::LightsOut => 2,
+        }
+    }
+}
+
+/// Configuration for [`screenshot_tweet`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScreenshotOptions {
+    pub width: u32,
+    pub height: u32,
+    /// If set, widen the window (up to this width) when the tweet's content overflows.
+    pub max_auto_width: Option<u32>,
+    pub theme: Theme,
+    /// How much of the tweet to keep (the tweet element is captured directly for
+    /// [`CropMode::IncludeActions`], and the window screenshot is cropped for
+    /// [`CropMode::TextOnly`]).
+    pub crop_mode: CropMode,
+    /// Device pixel ratio.
+    pub scale: f64,
+    pub timeout: Duration,
+    /// Additional time to wait after the page loads (for images, etc.).
+    pub wait_for_load: Option<Duration>,
+    /// Either `chrome` or `firefox`.
+    pub browser: String,
+    pub headless: bool,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl Default for ScreenshotOptions {
+    fn default() -> Self {
+        ScreenshotOptions {
+            width: 800,
+            height: 4000,
+            max_auto_width: None,
+            theme: Theme::Light,
+            crop_mode: CropMode::TextOnly,
+            scale: 1.0,
+            timeout: DEFAULT_WAIT_TIMEOUT,
+            wait_for_load: Some(Duration::from_millis(1500)),
+            browser: "chrome".to_string(),
+            headless: true,
+            host: None,
+            port: None,
+        }
+    }
+}
+
+/// Screenshot a tweet (given its URL or status ID), cropped to the tweet.
+///
+/// This manages the WebDriver session, which is closed before returning. If cropping fails, the
+/// full window screenshot is returned (and `cropped` is `false`).
+///
+/// ```no_run
+/// use cancel_culture::browser::twitter::{screenshot_tweet, ScreenshotOptions, Theme};
+///
+/// # async fn example() -> Result<(), Box<dyn std::error::Error>> {
+/// let options = ScreenshotOptions {
+///     theme: Theme::Dim,
+///     scale: 2.0,
+///     ..Default::default()
+/// };
+///
+/// let screenshot =
+///     screenshot_tweet("https://twitter.com/jdegoes/status/1170761943067631621", options).await?;
+/// screenshot.image.save("1170761943067631621.png")?;
+/// # Ok(())
+/// # }
+/// ```
+pub async fn screenshot_tweet(
+    url_or_id: &str,
+    options: ScreenshotOptions,
+) -> Result<Screenshot, ScreenshotError> {
+    let status_id = crate::util::parse_tweet_ref(url_or_id)
+        .ok_or_else(|| ScreenshotError::InvalidTweet(url_or_id.to_string()))?;
+
+    if !super::BROWSERS.contains(&options.browser.as_str()) {
+        return Err(ScreenshotError::UnsupportedBrowser(options.browser));
+    }
+
+    let mut client = super::make_scaled_client(
+        &options.browser,
+        options.headless,
+        options.host.as_deref(),
+        options.port,
+        options.scale,
+    )
+    .await?;
+
+    let result = screenshot_tweet_with_client(&mut client, status_id, &options).await;
+
+    if let Err(error) = client.close().await {
+        log::warn!("Error closing browser session: {:?}", error);
+    }
+
+    result
+}
+
+/// Load a tweet's page, applying the theme and automatic width options.
+///
+/// Returns the width of the window.
+async fn load_tweet_page_with_options(
+    client: &mut Client,
+    status_id: u64,
+    options: &ScreenshotOptions,
+) -> Result<u32, ScreenshotError> {
+    if options.theme != Theme::Light {
+        client.goto("https://twitter.com/").await?;
+        client
+            .execute(
+                &format!(
+                    "document.cookie = 'night_mode={}; domain=.twitter.com; path=/';",
+                    options.theme.night_mode()
+                ),
+                vec![],
+            )
+            .await?;
+    }
+
+    let loaded = load_tweet_page(
+        client,
+        status_id,
+        options.width,
+        options.height,
+        options.wait_for_load,
+        options.timeout,
+    )
+    .await;
+    check_for_interstitial(client, loaded).await?;
+
+    match options.max_auto_width {
+        Some(max_width) => {
+            Ok(fit_window_to_tweet(client, options.width, options.height, max_width).await?)
+        }
+        None => Ok(options.width),
+    }
+}
+
+/// Screenshot a tweet, cropped to the tweet, using an existing client.
+///
+/// The browser and scale options are ignored, since they apply to the client.
+pub async fn screenshot_tweet_with_client(
+    client: &mut Client,
+    status_id: u64,
+    options: &ScreenshotOptions,
+) -> Result<Screenshot, ScreenshotError> {
+    load_tweet_page_with_options(client, status_id, options).await?;
+
+    shoot_loaded_tweet_cropped(client, status_id, options.crop_mode).await
+}
+
+/// Take a window screenshot of a tweet and crop the same image, using an existing client.
+///
+/// The page is only loaded once, so both images show the same state. Returns the full image
+/// and the cropped screenshot (which is the full image if cropping fails).
+pub async fn screenshot_tweet_full_and_cropped(
+    client: &mut Client,
+    status_id: u64,
+    options: &ScreenshotOptions,
+) -> Result<(DynamicImage, Screenshot), ScreenshotError> {
+    load_tweet_page_with_options(client, status_id, options).await?;
+
+    let full = image::load_from_memory(&client.screenshot().await?)?;
+    let cropped = crop_or_full_with_mode(full.to_rgba8(), options.crop_mode);
+
+    Ok((full, cropped))
+}
+
+/// Pacing between captures when screenshotting many tweets in one session.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BatchPacing {
+    pub per_tweet_delay: Duration,
+    /// A random amount of time up to this value is added to each delay.
+    pub jitter: Option<Duration>,
+}
+
+impl BatchPacing {
+    fn delay<R: Rng>(&self, rng: &mut R) -> Duration {
+        match self.jitter {
+            Some(jitter) if !jitter.is_zero() => {
+                let jitter_millis = rng.gen_range(0..=jitter.as_millis() as u64);
+                self.per_tweet_delay + Duration::from_millis(jitter_millis)
+            }
+            _ => self.per_tweet_delay,
+        }
+    }
+}
+
+/// Waits between (but not before the first of) a series of captures.
+struct BatchPacer<S, R> {
+    pacing: BatchPacing,
+    sleep: S,
+    rng: R,
+    started: bool,
+}
+
+impl<S, Fut, R> BatchPacer<S, R>
+where
+    S: FnMut(Duration) -> Fut,
+    Fut: Future<Output = ()>,
+    R: Rng,
+{
+    fn new(pacing: BatchPacing, sleep: S, rng: R) -> Self {
+        BatchPacer {
+            pacing,
+            sleep,
+            rng,
+            started: false,
+        }
+    }
+
+    async fn before_capture(&mut self) {
+        if self.started {
+            let delay = self.pacing.delay(&mut self.rng);
+
+            if !delay.is_zero() {
+                (self.sleep)(delay).await;
+            }
+        }
+
+        self.started = true;
+    }
+}
+
+/// Screenshot a sequence of tweets using an existing client, waiting between captures.
+///
+/// Failures are returned alongside the successful captures rather than ending the batch.
+pub async fn shoot_tweets(
+    client: &mut Client,
+    status_ids: &[u64],
+    options: &ScreenshotOptions,
+    pacing: BatchPacing,
+) -> Vec<(u64, Result<Screenshot, ScreenshotError>)> {
+    let mut pacer = BatchPacer::new(pacing, tokio::time::sleep, StdRng::from_entropy());
+    let mut results = Vec::with_capacity(status_ids.len());
+
+    for status_id in status_ids {
+        pacer.before_capture().await;
+
+        let result = screenshot_tweet_with_client(client, *status_id, options).await;
+
+        if let Err(error) = &result {
+            log::warn!("Unable to capture tweet {}: {:?}", status_id, error);
+        }
+
+        results.push((*status_id, result));
+    }
+
+    results
+}
+
+impl From<CmdError> for ScreenshotError {
+    fn from(error: CmdError) -> Self {
+        match error {
+            CmdError::WaitTimeout => ScreenshotError::Timeout,
+            other => ScreenshotError::Download(other),
+        }
+    }
+}
+
 const RGBA_WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
 
 // TODO: Figure out why this is necessary for finding the right edge in some cases.
@@ -108,8 +756,44 @@ fn is_very_light_gray(pixel: &Rgba<u8>) -> bool {
     pixel.0[0] >= threshhold && pixel.0[1] >= threshhold && pixel.0[2] >= threshhold
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum CropError {
+    #[error("Unable to read image file")]
+    File(#[from] std::io::Error),
+    #[error("Image decoding error")]
+    ImageDecoding(#[from] image::error::ImageError),
+    #[error("Unable to locate tweet in image")]
+    NotFound,
+}
+
+/// Compute the crop rectangle (x, y, width, height) for an existing screenshot file.
+pub fn crop_rectangle_from_path<P: AsRef<std::path::Path>>(
+    path: P,
+) -> Result<(u32, u32, u32, u32), CropError> {
+    let image = image::io::Reader::open(path)?.decode()?.into_rgba8();
+
+    crop_tweet(&image).ok_or(CropError::NotFound)
+}
+
+/// How much of the tweet to keep when cropping a screenshot.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum CropMode {
+    /// Keep the action bar (reply, retweet, and like buttons and counts).
+    IncludeActions,
+    /// Stop at the gray line above the action bar.
+    #[default]
+    TextOnly,
+}
+
 pub fn crop_tweet<I: GenericImageView<Pixel = Rgba<u8>>>(
     buffer: &I,
+) -> Option<(u32, u32, u32, u32)> {
+    crop_tweet_with_mode(buffer, CropMode::TextOnly)
+}
+
+pub fn crop_tweet_with_mode<I: GenericImageView<Pixel = Rgba<u8>>>(
+    buffer: &I,
+    mode: CropMode,
 ) -> Option<(u32, u32, u32, u32)> {
     let w = buffer.width();
     let h = buffer.height();
@@ -193,16 +877,24 @@ pub fn crop_tweet<I: GenericImageView<Pixel = Rgba<u8>>>(
             // The first line represents the bottom of the tweet, including the actions.
             while i < h {
                 if buffer.get_pixel(left, i) != RGBA_WHITE {
-                    lower_edge = Some(i - 1);
+                    lower_edge = i.checked_sub(1);
                     break;
                 }
                 i += 1;
             }
 
             upper_edge.zip(lower_edge).and_then(|(upper, lower)| {
+                let width = right.checked_sub(left)?;
+
+                if mode == CropMode::IncludeActions {
+                    return lower
+                        .checked_sub(upper)
+                        .map(|height| (left, upper, width, height));
+                }
+
                 // We move up two pixels because of a new double line.
                 // This should be fairly robust, since the target will always be higher anyway.
-                i = lower - 2;
+                i = lower.checked_sub(2)?;
 
                 let middle = left + (right - left) / 2;
                 let mut base = None;
@@ -210,28 +902,180 @@ pub fn crop_tweet<I: GenericImageView<Pixel = Rgba<u8>>>(
                 // Finally move up until you hit another gray line.
                 while i > 0 {
                     if buffer.get_pixel(middle, i) != RGBA_WHITE {
-                        base = Some(i - 2);
+                        base = i.checked_sub(2);
                         break;
                     }
 
                     i -= 1;
                 }
 
-                base.map(|b| (left, upper, right - left, b - upper))
+                base.and_then(|b| b.checked_sub(upper))
+                    .map(|height| (left, upper, width, height))
             })
         })
 }
 
 #[cfg(test)]
 mod tests {
+    use fantoccini::error::{CmdError, ErrorStatus, WebDriver};
     use image::io::Reader;
     use image::RgbaImage;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::cell::Cell;
     use std::path::Path;
 
     fn load_image<P: AsRef<Path>>(path: P) -> RgbaImage {
         Reader::open(path).unwrap().decode().unwrap().into_rgba8()
     }
 
+    #[test]
+    fn screenshot_options_default() {
+        let options = super::ScreenshotOptions::default();
+
+        assert_eq!(options.width, 800);
+        assert_eq!(options.height, 4000);
+        assert_eq!(options.max_auto_width, None);
+        assert_eq!(options.theme, super::Theme::Light);
+        assert_eq!(options.crop_mode, super::CropMode::TextOnly);
+        assert_eq!(options.scale, 1.0);
+        assert_eq!(options.timeout, super::DEFAULT_WAIT_TIMEOUT);
+        assert_eq!(options.browser, "chrome");
+        assert!(options.headless);
+        assert_eq!(options.host, None);
+        assert_eq!(options.port, None);
+    }
+
+    #[tokio::test]
+    async fn screenshot_tweet_unsupported_browser() {
+        let options = super::ScreenshotOptions {
+            browser: "netscape".to_string(),
+            ..Default::default()
+        };
+
+        let result = super::screenshot_tweet("20", options).await;
+
+        assert!(matches!(
+            result,
+            Err(super::ScreenshotError::UnsupportedBrowser(browser)) if browser == "netscape"
+        ));
+    }
+
+    fn stale_element_error() -> CmdError {
+        CmdError::Standard(WebDriver {
+            error: ErrorStatus::StaleElementReference,
+            message: "stale element reference".into(),
+            stacktrace: String::new(),
+            data: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn retry_stale() {
+        let attempts = Cell::new(0);
+
+        let result = super::retry_stale(|| {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                if attempt < 3 {
+                    Err(stale_element_error())
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 3);
+
+        attempts.set(0);
+        let result = super::retry_stale(|| {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(stale_element_error()) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.get(), super::STALE_ELEMENT_RETRIES + 1);
+    }
+
+    #[tokio::test]
+    async fn non_fatal_cookie_step() {
+        let hidden = super::non_fatal("hide cookie layer", || async {
+            Err::<(), _>(stale_element_error())
+        })
+        .await;
+
+        assert!(hidden.is_none());
+
+        let result = super::non_fatal("hide cookie layer", || async { Ok::<_, CmdError>(1) }).await;
+
+        assert_eq!(result, Some(1));
+    }
+
+    #[tokio::test]
+    async fn batch_pacer() {
+        let pacing = super::BatchPacing {
+            per_tweet_delay: std::time::Duration::from_millis(500),
+            jitter: Some(std::time::Duration::from_millis(100)),
+        };
+        let sleeps = std::cell::RefCell::new(vec![]);
+        let mut pacer = super::BatchPacer::new(
+            pacing,
+            |delay| {
+                sleeps.borrow_mut().push(delay);
+                async {}
+            },
+            StdRng::seed_from_u64(0),
+        );
+
+        for _ in 0..3 {
+            pacer.before_capture().await;
+        }
+
+        let sleeps = sleeps.into_inner();
+
+        assert_eq!(sleeps.len(), 2);
+        assert!(sleeps
+            .iter()
+            .all(|delay| delay.as_millis() >= 500 && delay.as_millis() <= 600));
+    }
+
+    #[test]
+    fn batch_pacing_delay() {
+        let pacing = super::BatchPacing {
+            per_tweet_delay: std::time::Duration::from_secs(1),
+            jitter: Some(std::time::Duration::from_millis(250)),
+        };
+
+        let mut rng = StdRng::seed_from_u64(0);
+
+        for _ in 0..100 {
+            let delay = pacing.delay(&mut rng).as_millis();
+            assert!((1000..=1250).contains(&delay), "{}", delay);
+        }
+
+        let no_jitter = super::BatchPacing {
+            jitter: None,
+            ..pacing
+        };
+
+        assert_eq!(no_jitter.delay(&mut rng).as_millis(), 1000);
+        assert_eq!(
+            super::BatchPacing::default().delay(&mut rng),
+            std::time::Duration::default()
+        );
+    }
+
+    #[test]
+    fn fitted_width() {
+        assert_eq!(super::fitted_width(800, 598, 598, 1600), 800);
+        assert_eq!(super::fitted_width(800, 798, 598, 1600), 1000);
+        assert_eq!(super::fitted_width(800, 1998, 598, 1600), 1600);
+        assert_eq!(super::fitted_width(800, 1998, 598, 600), 800);
+    }
+
     #[test]
     fn crop_tweet() {
         let examples = vec![
@@ -249,4 +1093,102 @@ mod tests {
             assert_eq!(super::crop_tweet(&load_image(path)), expected);
         }
     }
+
+    #[test]
+    fn screenshot_outputs() {
+        use super::ScreenshotOutputs;
+
+        let full = Some("123-full.png".to_string());
+        let crop = Some("123.png".to_string());
+
+        let cases = vec![
+            ((false, false), true, (full.clone(), crop.clone())),
+            ((false, false), false, (full.clone(), None)),
+            ((true, false), true, (None, crop)),
+            ((true, false), false, (None, None)),
+            ((false, true), true, (full.clone(), None)),
+            ((false, true), false, (full, None)),
+        ];
+
+        for ((crop_only, full_only), cropped, expected) in cases {
+            let outputs = ScreenshotOutputs::from_flags(crop_only, full_only);
+
+            assert_eq!(outputs.file_names(123, cropped), expected);
+        }
+
+        assert_eq!(ScreenshotOutputs::default(), ScreenshotOutputs::Both);
+        assert!(!ScreenshotOutputs::FullOnly.includes_crop());
+        assert!(!ScreenshotOutputs::CropOnly.includes_full());
+    }
+
+    #[test]
+    fn crop_or_full() {
+        let blank = RgbaImage::from_pixel(100, 50, image::Rgba([255, 255, 255, 255]));
+        let mut black_top = blank.clone();
+        for x in 0..100 {
+            black_top.put_pixel(x, 0, image::Rgba([0, 0, 0, 255]));
+        }
+
+        for image in [blank, black_top] {
+            let screenshot = super::crop_or_full(image.clone());
+
+            assert!(!screenshot.cropped);
+            assert_eq!(screenshot.image.into_rgba8(), image);
+        }
+
+        let image = load_image("examples/images/703033780689199104-full.png");
+        let screenshot = super::crop_or_full(image);
+
+        assert!(screenshot.cropped);
+        assert_eq!(
+            (screenshot.image.width(), screenshot.image.height()),
+            (1195, 494)
+        );
+    }
+
+    #[test]
+    fn crop_rectangle_from_path() {
+        assert_eq!(
+            super::crop_rectangle_from_path("examples/images/703033780689199104-full.png").unwrap(),
+            (253, 99, 1195, 494)
+        );
+        assert_eq!(
+            super::crop_rectangle_from_path("examples/images/1503631923154984960-full.png")
+                .unwrap(),
+            (253, 99, 1195, 1184)
+        );
+        assert!(matches!(
+            super::crop_rectangle_from_path("examples/images/missing.png"),
+            Err(super::CropError::File(_))
+        ));
+    }
+
+    #[test]
+    fn crop_tweet_with_mode() {
+        let examples = vec![
+            (
+                "examples/images/703033780689199104-full.png",
+                (253, 99, 1195, 494),
+                (253, 99, 1195, 590),
+            ),
+            (
+                "examples/images/1503631923154984960-full.png",
+                (253, 99, 1195, 1184),
+                (253, 99, 1195, 1282),
+            ),
+        ];
+
+        for (path, text_only, include_actions) in examples {
+            let image = load_image(path);
+
+            assert_eq!(
+                super::crop_tweet_with_mode(&image, super::CropMode::TextOnly),
+                Some(text_only)
+            );
+            assert_eq!(
+                super::crop_tweet_with_mode(&image, super::CropMode::IncludeActions),
+                Some(include_actions)
+            );
+        }
+    }
 }
@@ -9,6 +9,7 @@ use scraper::node::Node;
 use scraper::selector::Selector;
 use scraper::Html;
 use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
 use std::io::Read;
 
 #[derive(Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
@@ -20,6 +21,12 @@ pub struct BrowserTweet {
     pub user_screen_name: String,
     pub user_name: String,
     pub text: String,
+    /// URLs for attached images (currently only available for tweets from the old web UI).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub media: Vec<String>,
+    /// Screen names from the "Replying to" line (the first is the author of the parent tweet).
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub reply_to_screen_names: Vec<String>,
 }
 
 impl BrowserTweet {
@@ -40,9 +47,21 @@ impl BrowserTweet {
             user_screen_name,
             user_name,
             text,
+            media: vec![],
+            reply_to_screen_names: vec![],
         }
     }
 
+    pub fn with_media(mut self, media: Vec<String>) -> BrowserTweet {
+        self.media = media;
+        self
+    }
+
+    pub fn with_reply_to_screen_names(mut self, screen_names: Vec<String>) -> BrowserTweet {
+        self.reply_to_screen_names = screen_names;
+        self
+    }
+
     fn new_with_timestamp(
         id: u64,
         parent_id: Option<u64>,
@@ -64,6 +83,31 @@ impl BrowserTweet {
     }
 }
 
+/// Returned when converting a tweet from the API that doesn't include its user.
+#[derive(thiserror::Error, Debug, Eq, PartialEq)]
+#[error("Missing user for tweet {0}")]
+pub struct MissingUserError(pub u64);
+
+/// Convert a tweet from the Twitter API (egg-mode has already selected the full text for
+/// extended tweets).
+impl TryFrom<&egg_mode::tweet::Tweet> for BrowserTweet {
+    type Error = MissingUserError;
+
+    fn try_from(tweet: &egg_mode::tweet::Tweet) -> Result<BrowserTweet, Self::Error> {
+        let user = tweet.user.as_ref().ok_or(MissingUserError(tweet.id))?;
+
+        Ok(BrowserTweet::new(
+            tweet.id,
+            tweet.in_reply_to_status_id,
+            tweet.created_at,
+            user.id,
+            user.screen_name.clone(),
+            user.name.clone(),
+            tweet.text.clone(),
+        ))
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct TweetUserJson {
     id: u64,
@@ -105,12 +149,16 @@ impl TweetJson {
 
 lazy_static! {
     static ref TIME_SEL: Selector = Selector::parse("small.time span._timestamp").unwrap();
+    static ref TIME_ELEMENT_SEL: Selector = Selector::parse("time[datetime]").unwrap();
     static ref TEXT_SEL: Selector = Selector::parse("p.tweet-text").unwrap();
     static ref TWEET_DIV_SEL: Selector = Selector::parse("div.tweet").unwrap();
+    static ref REPLYING_TO_SEL: Selector =
+        Selector::parse("div.ReplyingToContextBelowAuthor a.js-user-profile-link").unwrap();
+    static ref MEDIA_SEL: Selector =
+        Selector::parse("div.AdaptiveMedia-photoContainer[data-image-url]").unwrap();
     static ref DESCRIPTION_SEL: Selector =
         Selector::parse("meta[property='og:description']").unwrap();
     static ref CANONICAL_SEL: Selector = Selector::parse("link[rel='canonical']").unwrap();
-    static ref STATUS_ID_RE: regex::Regex = regex::Regex::new(r"/status/(\d+)$").unwrap();
     static ref PHC_DIV_SEL: Selector = Selector::parse("div.ProfileHeaderCard").unwrap();
     static ref PHC_SCREEN_NAME_SEL: Selector =
         Selector::parse("a.ProfileHeaderCard-screennameLink").unwrap();
@@ -124,6 +172,18 @@ lazy_static! {
         Selector::parse("span.ProfileHeaderCard-birthdateText").unwrap();
 }
 
+/// The screen name from a profile link, which may be relative, absolute, or rewritten by the
+/// Wayback Machine (e.g. `/web/20190908/https://twitter.com/jdegoes`).
+fn href_screen_name(href: &str) -> Option<String> {
+    href.split(['?', '#'])
+        .next()?
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .filter(|screen_name| !screen_name.is_empty())
+        .map(|screen_name| screen_name.to_string())
+}
+
 pub fn parse_html<R: Read>(input: &mut R) -> Result<Html, std::io::Error> {
     let parser = driver::parse_document(Html::new_document(), ParseOpts::default()).from_utf8();
 
@@ -144,20 +204,38 @@ pub fn extract_description(doc: &Html) -> Option<String> {
     res.into_iter().next()
 }
 
+pub fn extract_canonical_url(doc: &Html) -> Option<String> {
+    doc.select(&CANONICAL_SEL)
+        .filter_map(|el| el.value().attr("href"))
+        .next()
+        .map(|href| href.to_string())
+}
+
 pub fn extract_canonical_status_id(doc: &Html) -> Option<u64> {
     doc.select(&CANONICAL_SEL)
         .filter_map(|el| {
-            el.value().attr("href").and_then(|href| {
-                STATUS_ID_RE.captures(href).and_then(|captures| {
-                    captures
-                        .get(1)
-                        .and_then(|capture| capture.as_str().parse::<u64>().ok())
-                })
-            })
+            el.value()
+                .attr("href")
+                .and_then(crate::util::parse_tweet_ref)
         })
         .next()
 }
 
+/// The message Twitter shows instead of a tweet when it's rate limiting requests.
+const ERROR_INTERSTITIAL_TEXT: &str = "Something went wrong. Try reloading.";
+
+/// Check whether a page is Twitter's "Something went wrong" interstitial.
+pub fn is_error_interstitial(doc: &Html) -> bool {
+    let text = doc
+        .root_element()
+        .text()
+        .flat_map(|text| text.split_whitespace())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    text.contains(ERROR_INTERSTITIAL_TEXT)
+}
+
 pub fn extract_tweets(doc: &Html) -> Vec<BrowserTweet> {
     match extract_postings(doc) {
         Ok(Some(postings)) => postings
@@ -181,6 +259,13 @@ pub fn extract_tweets(doc: &Html) -> Vec<BrowserTweet> {
     }
 }
 
+/// Extract a specific tweet from a page (for example to save alongside a screenshot).
+pub fn extract_tweet(doc: &Html, status_id: u64) -> Option<BrowserTweet> {
+    extract_tweets(doc)
+        .into_iter()
+        .find(|tweet| tweet.id == status_id)
+}
+
 pub fn extract_phcs(doc: &Html) -> Vec<(String, String, String, String, String, Option<String>)> {
     doc.select(&PHC_DIV_SEL)
         .filter_map(|el| extract_phc(&el))
@@ -230,6 +315,16 @@ pub fn extract_tweet_json(content: &str) -> Option<BrowserTweet> {
     t.ok().map(|v| v.into_browser_tweet())
 }
 
+/// Parse the ISO-8601 `datetime` attribute of a `<time>` element (used by newer pages instead of
+/// `data-time-ms`).
+pub fn parse_time_element(element_ref: ElementRef) -> Option<DateTime<Utc>> {
+    element_ref
+        .value()
+        .attr("datetime")
+        .and_then(|value| DateTime::parse_from_rfc3339(value.trim()).ok())
+        .map(|time| time.with_timezone(&Utc))
+}
+
 fn extract_div_tweet(element_ref: &ElementRef) -> Option<BrowserTweet> {
     let element = element_ref.value();
 
@@ -244,11 +339,21 @@ fn extract_div_tweet(element_ref: &ElementRef) -> Option<BrowserTweet> {
         .and_then(|v| v.parse::<u64>().ok());
     let user_screen_name = element.attr("data-screen-name");
     let user_name = element.attr("data-name");
-    let timestamp = element_ref.select(&TIME_SEL).next().and_then(|el| {
-        el.value()
-            .attr("data-time-ms")
-            .and_then(|v| v.parse::<i64>().ok())
-    });
+    let timestamp = element_ref
+        .select(&TIME_SEL)
+        .next()
+        .and_then(|el| {
+            el.value()
+                .attr("data-time-ms")
+                .and_then(|v| v.parse::<i64>().ok())
+        })
+        .or_else(|| {
+            element_ref
+                .select(&TIME_ELEMENT_SEL)
+                .next()
+                .and_then(parse_time_element)
+                .map(|time| time.timestamp_millis())
+        });
     let text = element_ref.select(&TEXT_SEL).next().map(|el| {
         let mut result = String::new();
 
@@ -288,6 +393,16 @@ fn extract_div_tweet(element_ref: &ElementRef) -> Option<BrowserTweet> {
 
         result.trim().to_string()
     });
+    let media = element_ref
+        .select(&MEDIA_SEL)
+        .filter_map(|el| el.value().attr("data-image-url"))
+        .map(|url| url.to_string())
+        .collect::<Vec<_>>();
+    let reply_to_screen_names = element_ref
+        .select(&REPLYING_TO_SEL)
+        .filter_map(|el| el.value().attr("href"))
+        .filter_map(href_screen_name)
+        .collect::<Vec<_>>();
 
     id.zip(user_id)
         .zip(Some(parent_id.unwrap_or(0)))
@@ -310,6 +425,8 @@ fn extract_div_tweet(element_ref: &ElementRef) -> Option<BrowserTweet> {
                     user_name.trim().to_string(),
                     text,
                 )
+                .with_media(media)
+                .with_reply_to_screen_names(reply_to_screen_names)
             },
         )
 }
@@ -318,7 +435,7 @@ fn extract_div_tweet(element_ref: &ElementRef) -> Option<BrowserTweet> {
 mod tests {
     use chrono::{TimeZone, Utc};
     use flate2::read::GzDecoder;
-    use scraper::Html;
+    use scraper::{Html, Selector};
     use std::fs::{read_to_string, File};
     use std::io::Read;
 
@@ -336,6 +453,21 @@ mod tests {
             super::extract_canonical_status_id(&doc),
             Some(1170761943067631621)
         );
+        assert_eq!(
+            super::extract_canonical_url(&doc),
+            Some("https://twitter.com/jdegoes/status/1170761943067631621".to_string())
+        );
+    }
+
+    #[test]
+    fn is_error_interstitial() {
+        let interstitial = read_to_string("examples/html/interstitial.html").unwrap();
+        let tweet = read_to_string("examples/html/1323554460765925376.html").unwrap();
+
+        assert!(super::is_error_interstitial(&Html::parse_document(
+            &interstitial
+        )));
+        assert!(!super::is_error_interstitial(&Html::parse_document(&tweet)));
     }
 
     #[test]
@@ -355,6 +487,100 @@ mod tests {
         assert_eq!(super::extract_tweets(&doc).len(), 11);
     }
 
+    #[test]
+    fn extract_tweets_reply_to_screen_names() {
+        let file = File::open("examples/wayback/53SGIJNJMTP6S626CVRCHFTX3OEWXB3E.gz").unwrap();
+        let mut gz = GzDecoder::new(file);
+        let mut html = String::new();
+
+        gz.read_to_string(&mut html).unwrap();
+
+        let doc = Html::parse_document(&html);
+        let reply_to_screen_names = |id: u64| {
+            super::extract_tweet(&doc, id)
+                .map(|tweet| tweet.reply_to_screen_names)
+                .unwrap()
+        };
+
+        assert!(reply_to_screen_names(1170332978741948418).is_empty());
+        assert_eq!(reply_to_screen_names(1170708733019611138), vec!["jdegoes"]);
+        assert_eq!(
+            reply_to_screen_names(1170764987171651584),
+            vec!["nuttycom", "jdegoes"]
+        );
+    }
+
+    #[test]
+    fn extract_tweets_reply_to_screen_names_absolute_hrefs() {
+        let html = r#"<html><body>
+            <div class="tweet" data-tweet-id="1170764987171651584" data-user-id="6510972"
+                data-screen-name="travisbrown" data-name="Travis Brown">
+                <small class="time"><span class="_timestamp" data-time-ms="1568047236000"></span></small>
+                <div class="ReplyingToContextBelowAuthor">
+                    Replying to
+                    <a class="pretty-link js-user-profile-link"
+                        href="/web/20190909000000/https://twitter.com/nuttycom"><span class="username">@<b>nuttycom</b></span></a>
+                    <a class="pretty-link js-user-profile-link"
+                        href="https://twitter.com/jdegoes/"><span class="username">@<b>jdegoes</b></span></a>
+                    <a class="pretty-link js-user-profile-link"
+                        href="/djspiewak?lang=en"><span class="username">@<b>djspiewak</b></span></a>
+                </div>
+                <p class="tweet-text">Agreed</p>
+            </div>
+        </body></html>"#;
+
+        let tweets = super::extract_tweets(&Html::parse_document(html));
+
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(
+            tweets[0].reply_to_screen_names,
+            vec!["nuttycom", "jdegoes", "djspiewak"]
+        );
+    }
+
+    #[test]
+    fn extract_tweet_sidecar() {
+        let html = read_to_string("examples/html/1323554460765925376.html").unwrap();
+        let doc = Html::parse_document(&html);
+
+        assert_eq!(super::extract_tweet(&doc, 1), None);
+
+        let tweet = super::extract_tweet(&doc, 1323554460765925376).unwrap();
+        let json = serde_json::to_value(&tweet).unwrap();
+
+        assert_eq!(json["id"], 1323554460765925376u64);
+        assert_eq!(json["time"], "2020-11-03T09:15:48Z");
+        assert_eq!(json["user_id"], 6510972);
+        assert_eq!(json["user_screen_name"], "travisbrown");
+        assert_eq!(json["user_name"], "Travis Brown");
+        assert!(json["text"]
+            .as_str()
+            .unwrap()
+            .starts_with("Generating a deleted tweet report like this is now one command"));
+    }
+
+    #[test]
+    fn extract_tweets_media() {
+        let html = r#"<html><body>
+            <div class="tweet" data-tweet-id="1170761943067631621" data-user-id="346665007"
+                data-screen-name="jdegoes" data-name="John A De Goes">
+                <small class="time"><span class="_timestamp" data-time-ms="1568047236000"></span></small>
+                <p class="tweet-text">Look at this</p>
+                <div class="AdaptiveMedia-photoContainer js-adaptive-photo"
+                    data-image-url="https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg"></div>
+            </div>
+        </body></html>"#;
+
+        let tweets = super::extract_tweets(&Html::parse_document(html));
+
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(tweets[0].text, "Look at this");
+        assert_eq!(
+            tweets[0].media,
+            vec!["https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg"]
+        );
+    }
+
     #[test]
     fn extract_tweets_json() {
         let contents = read_to_string("examples/json/890659426796945408.json").unwrap();
@@ -373,4 +599,72 @@ mod tests {
 
         assert_eq!(super::extract_tweet_json(&contents), Some(expected));
     }
+
+    #[test]
+    fn parse_time_element() {
+        let doc = Html::parse_fragment(
+            "<a href=\"/ChiefScientist/status/1529417013425905666\">\
+             <time datetime=\"2022-05-25T09:25:12.000Z\">May 25, 2022</time></a>\
+             <time datetime=\"2022-05-25T11:25:12+02:00\">May 25, 2022</time>\
+             <time datetime=\"May 25\">May 25</time>",
+        );
+        let selector = Selector::parse("time").unwrap();
+        let times = doc
+            .select(&selector)
+            .map(super::parse_time_element)
+            .collect::<Vec<_>>();
+        let expected = Utc.with_ymd_and_hms(2022, 5, 25, 9, 25, 12).unwrap();
+
+        assert_eq!(times, vec![Some(expected), Some(expected), None]);
+    }
+
+    #[test]
+    fn extract_tweets_time_element() {
+        let doc = Html::parse_document(
+            "<div class=\"tweet\" data-tweet-id=\"1529417013425905666\" \
+             data-user-id=\"1051208286\" data-screen-name=\"ChiefScientist\" \
+             data-name=\"Ed Kmett\"><time datetime=\"2022-05-25T09:25:12.000Z\">May 25</time>\
+             <p class=\"tweet-text\">Hello</p></div>",
+        );
+        let tweets = super::extract_tweets(&doc);
+
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(
+            tweets[0].time,
+            Utc.with_ymd_and_hms(2022, 5, 25, 9, 25, 12).unwrap()
+        );
+        assert_eq!(tweets[0].text, "Hello");
+    }
+
+    #[test]
+    fn browser_tweet_from_api_tweet() {
+        use std::convert::TryFrom;
+
+        let contents = read_to_string("examples/json/890661113871495168.json").unwrap();
+        let tweet = serde_json::from_str::<egg_mode::tweet::Tweet>(&contents).unwrap();
+        let expected = super::BrowserTweet::new(
+            890661113871495168,
+            Some(890659426796945408),
+            Utc.with_ymd_and_hms(2017, 7, 27, 19, 52, 11).unwrap(),
+            849768899772133376,
+            "DrupalLeaks".to_string(),
+            "DrupalLeaks".to_string(),
+            "@DrupalLeaks Part 1 covered the security team, the association, and the people who \
+             decide who gets to speak at DrupalCon. Part 2 will go further, so send us what you \
+             know before the end of August."
+                .to_string(),
+        );
+
+        // The truncated compatibility text is replaced by the full text.
+        assert!(tweet.truncated);
+        assert_eq!(super::BrowserTweet::try_from(&tweet), Ok(expected));
+
+        let mut without_user = tweet;
+        without_user.user = None;
+
+        assert_eq!(
+            super::BrowserTweet::try_from(&without_user),
+            Err(super::MissingUserError(890661113871495168))
+        );
+    }
 }
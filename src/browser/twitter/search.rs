@@ -1,11 +1,38 @@
 use super::super::Scroller;
+use super::{Screenshot, ScreenshotError};
 use chrono::{format::ParseError, NaiveDate};
 use fantoccini::{Client, Locator};
 use futures::{future::BoxFuture, FutureExt};
+use lazy_static::lazy_static;
 use regex::Regex;
+use scraper::{Html, Selector};
+use std::collections::HashSet;
 use std::time::Duration;
 use tokio::time::sleep;
 
+/// Parse the status ID from a tweet link (either relative or absolute).
+pub fn parse_status_href(href: &str) -> Option<u64> {
+    if href.starts_with('/') {
+        crate::util::parse_tweet_ref(&format!("https://twitter.com{}", href))
+    } else {
+        crate::util::parse_tweet_ref(href)
+    }
+}
+
+/// Extract all distinct status IDs linked from a page, in order of appearance.
+pub fn extract_status_ids(doc: &Html) -> Vec<u64> {
+    lazy_static! {
+        static ref LINK_SEL: Selector = Selector::parse("a[href*='/status/']").unwrap();
+    }
+
+    let mut seen = HashSet::new();
+
+    doc.select(&LINK_SEL)
+        .filter_map(|element| element.value().attr("href").and_then(parse_status_href))
+        .filter(|id| seen.insert(*id))
+        .collect()
+}
+
 pub struct UserTweetSearch {
     screen_name: String,
     from: NaiveDate,
@@ -123,3 +150,128 @@ impl Scroller for UserTweetSearch {
         .boxed()
     }
 }
+
+/// A search for an arbitrary query (for example a hashtag).
+pub struct QuerySearch {
+    search_url: String,
+}
+
+impl QuerySearch {
+    const NO_RESULTS_LOC: Locator<'static> =
+        Locator::XPath("//span[contains(text(), 'No results for ')]");
+
+    pub fn new(query: &str) -> Self {
+        QuerySearch {
+            search_url: format!(
+                "https://twitter.com/search?q={}&src=typed_query&f=live",
+                url::form_urlencoded::byte_serialize(query.as_bytes()).collect::<String>()
+            ),
+        }
+    }
+
+    /// Load all results for the search and take a screenshot of each tweet.
+    ///
+    /// The result for each status ID is returned, and screenshots that couldn't be cropped are
+    /// included with `cropped` set to `false`.
+    pub async fn shoot_all(
+        &self,
+        client: &mut Client,
+        width: u32,
+        height: u32,
+        timeout: Duration,
+    ) -> Result<Vec<(u64, Result<Screenshot, ScreenshotError>)>, anyhow::Error> {
+        let ids = self.extract_all(client).await?;
+        let mut result = Vec::with_capacity(ids.len());
+
+        for id in ids {
+            let screenshot =
+                super::shoot_tweet_cropped(client, id, width, height, None, timeout).await;
+
+            match &screenshot {
+                Ok(screenshot) if !screenshot.cropped => log::warn!("Unable to crop tweet {}", id),
+                Err(error) => log::error!("Unable to capture tweet {}: {:?}", id, error),
+                _ => {}
+            }
+
+            result.push((id, screenshot));
+        }
+
+        Ok(result)
+    }
+}
+
+impl Scroller for QuerySearch {
+    type Item = u64;
+    type Err = anyhow::Error;
+
+    fn init<'a>(&'a self, client: &'a mut Client) -> BoxFuture<'a, Result<bool, Self::Err>> {
+        async move {
+            client.goto(&self.search_url).await?;
+            sleep(Duration::from_millis(750)).await;
+            log::info!("Checking: {}", self.search_url);
+
+            Ok(client.find_all(Self::NO_RESULTS_LOC).await?.is_empty())
+        }
+        .boxed()
+    }
+
+    fn extract<'a>(
+        &'a self,
+        client: &'a mut Client,
+    ) -> BoxFuture<'a, Result<Vec<Self::Item>, Self::Err>> {
+        async move {
+            // Parsing the page source avoids a WebDriver request for every link.
+            let source = client.source().await?;
+
+            Ok(extract_status_ids(&Html::parse_document(&source)))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use scraper::Html;
+
+    #[test]
+    fn test_extract_status_ids() {
+        let html = Html::parse_document(
+            &std::fs::read_to_string("examples/html/search-results.html").unwrap(),
+        );
+
+        assert_eq!(
+            super::extract_status_ids(&html),
+            vec![
+                1521484346545901568,
+                1302847271688523778,
+                1169217405425455105,
+                1270099974559154177
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_status_href() {
+        let cases = vec![
+            ("/jack/status/20", Some(20)),
+            ("https://twitter.com/jack/status/20", Some(20)),
+            ("https://mobile.twitter.com/jack/status/20", Some(20)),
+            ("https://x.com/jack/status/20", Some(20)),
+            ("/jack/status/20/photo/1", Some(20)),
+            ("/jack", None),
+            ("https://example.com/jack/status/20", None),
+        ];
+
+        for (href, expected) in cases {
+            assert_eq!(super::parse_status_href(href), expected, "{}", href);
+        }
+    }
+
+    #[test]
+    fn test_query_search_url() {
+        assert_eq!(
+            super::QuerySearch::new("#scalaisdead").search_url,
+            "https://twitter.com/search?q=%23scalaisdead&src=typed_query&f=live"
+        );
+    }
+}
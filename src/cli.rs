@@ -1,5 +1,7 @@
 use simplelog::LevelFilter;
 use std::io::Read;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 
 fn select_log_level_filter(verbosity: u8) -> LevelFilter {
     match verbosity {
@@ -28,3 +30,76 @@ pub fn read_stdin() -> Result<String, std::io::Error> {
     handle.read_to_string(&mut buffer)?;
     Ok(buffer)
 }
+
+/// Tracks Ctrl-C signals so that long-running commands can stop early and save their progress.
+#[derive(Clone, Default)]
+pub struct Interrupt {
+    count: Arc<AtomicUsize>,
+}
+
+impl Interrupt {
+    /// Install a Ctrl-C handler.
+    ///
+    /// The first signal only marks the returned value as interrupted, and a second signal exits
+    /// the process immediately.
+    pub fn install() -> Interrupt {
+        let interrupt = Interrupt::default();
+        let handler = interrupt.clone();
+
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if handler.signal() {
+                    log::warn!(
+                        "Interrupted; finishing up (press Ctrl-C again to exit immediately)"
+                    );
+                } else {
+                    std::process::exit(130);
+                }
+            }
+        });
+
+        interrupt
+    }
+
+    /// Record a signal, returning `true` if it is the first.
+    pub fn signal(&self) -> bool {
+        self.count.fetch_add(1, Ordering::SeqCst) == 0
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.count.load(Ordering::SeqCst) > 0
+    }
+
+    /// Take items from the iterator until an interruption is signaled.
+    pub fn take_until<'a, I: IntoIterator + 'a>(
+        &'a self,
+        items: I,
+    ) -> impl Iterator<Item = I::Item> + 'a {
+        items
+            .into_iter()
+            .take_while(move |_| !self.is_interrupted())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interrupt;
+
+    #[test]
+    fn test_interrupt_take_until() {
+        let interrupt = Interrupt::default();
+        let mut processed = vec![];
+
+        for i in interrupt.take_until(0..10) {
+            processed.push(i);
+
+            if i == 3 {
+                assert!(interrupt.signal());
+            }
+        }
+
+        assert_eq!(processed, vec![0, 1, 2, 3]);
+        assert!(interrupt.is_interrupted());
+        assert!(!interrupt.signal());
+    }
+}
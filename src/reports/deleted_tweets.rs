@@ -1,13 +1,18 @@
 use super::Report;
+use crate::browser::twitter::parser::BrowserTweet;
 use serde::Serialize;
+use std::collections::HashMap;
 use std::fmt::{Display, Error, Formatter};
 use std::result::Result;
 
 static TEMPLATE: &str = "## Deleted tweets for {screen_name}
 
-The list below includes {deleted_count} deleted tweets by
+{{if live_checked}}The list below includes {deleted_count} deleted tweets by
 [{screen_name}](https://twitter.com/{screen_name}).
-
+{{else}}The list below includes {deleted_count} archived tweets by
+[{screen_name}](https://twitter.com/{screen_name}). These tweets have not been checked against the
+Twitter API, so some of them may not currently be deleted.
+{{endif}}
 {{if undeleted_exist}}There are also {undeleted_count} tweets that are indicated as not currently
 deleted by the Twitter API that have been scraped from pages of deleted tweets (as replies, etc.).
 These possibly undeleted tweets are included for context and are indicated by a _(live)_ link.
@@ -35,6 +40,7 @@ pub struct DeletedTweetReport<'a> {
     deleted_count: usize,
     undeleted_count: usize,
     undeleted_exist: bool,
+    live_checked: bool,
 }
 
 impl<'a> DeletedTweetReport<'a> {
@@ -48,8 +54,79 @@ impl<'a> DeletedTweetReport<'a> {
             deleted_count,
             undeleted_count,
             undeleted_exist: undeleted_count > 0,
+            live_checked: true,
         }
     }
+
+    /// A report for tweets whose current status hasn't been checked.
+    pub fn unchecked(screen_name: &'a str, count: usize) -> DeletedTweetReport<'a> {
+        DeletedTweetReport {
+            screen_name,
+            deleted_count: count,
+            undeleted_count: 0,
+            undeleted_exist: false,
+            live_checked: false,
+        }
+    }
+}
+
+/// Format a report entry for an archived tweet, with a link to the tweet if it's live.
+pub fn format_line(
+    tweet: &BrowserTweet,
+    timestamp: &str,
+    url: &str,
+    live: bool,
+    suffix: &str,
+) -> String {
+    let time = tweet.time.format("%e %B %Y");
+
+    if live {
+        format!(
+            "* [{}](https://web.archive.org/web/{}/{}) ([live](https://twitter.com/{}/status/{})): {}{} <!--{}-->",
+            time,
+            timestamp,
+            url,
+            tweet.user_screen_name,
+            tweet.id,
+            escape_tweet_text(&tweet.text),
+            suffix,
+            tweet.id
+        )
+    } else {
+        format!(
+            "* [{}](https://web.archive.org/web/{}/{}): {}{} <!--{}-->",
+            time,
+            timestamp,
+            url,
+            escape_tweet_text(&tweet.text),
+            suffix,
+            tweet.id
+        )
+    }
+}
+
+/// Format a context line for a reply, using the parent's author's screen name if it's known.
+pub fn format_context_line(
+    tweet: &BrowserTweet,
+    screen_names: &HashMap<u64, String>,
+) -> Option<String> {
+    tweet
+        .parent_id
+        .filter(|parent_id| *parent_id != tweet.id)
+        .map(|parent_id| match screen_names.get(&parent_id) {
+            Some(screen_name) => format!(
+                "    * _In reply to [a tweet by @{}](https://twitter.com/{}/status/{})_",
+                screen_name, screen_name, parent_id
+            ),
+            None => format!(
+                "    * _In reply to [status {}](https://twitter.com/i/web/status/{})_",
+                parent_id, parent_id
+            ),
+        })
+}
+
+fn escape_tweet_text(text: &str) -> String {
+    text.replace(r"\'", "'").replace('\n', " ")
 }
 
 impl<'a> Report for DeletedTweetReport<'a> {
@@ -66,3 +143,76 @@ impl<'a> Display for DeletedTweetReport<'a> {
         write!(f, "{}", self.render())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::DeletedTweetReport;
+    use crate::browser::twitter::parser::BrowserTweet;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_format_context_line() {
+        let mut tweet = BrowserTweet::new(
+            1302847271688523779,
+            Some(1302847271688523778),
+            Utc.timestamp_millis(1599458084000),
+            1051208286,
+            "ChiefScientist".to_string(),
+            "Ed Kmett".to_string(),
+            "@travisbrown Hi.".to_string(),
+        );
+        let mut screen_names = HashMap::new();
+
+        assert_eq!(
+            super::format_context_line(&tweet, &screen_names),
+            Some(
+                "    * _In reply to [status 1302847271688523778](https://twitter.com/i/web/status/1302847271688523778)_"
+                    .to_string()
+            )
+        );
+
+        screen_names.insert(1302847271688523778, "travisbrown".to_string());
+
+        assert_eq!(
+            super::format_context_line(&tweet, &screen_names),
+            Some(
+                "    * _In reply to [a tweet by @travisbrown](https://twitter.com/travisbrown/status/1302847271688523778)_"
+                    .to_string()
+            )
+        );
+
+        tweet.parent_id = None;
+
+        assert_eq!(super::format_context_line(&tweet, &screen_names), None);
+    }
+
+    #[test]
+    fn test_unchecked_report() {
+        let tweet = BrowserTweet::new(
+            1302847271688523778,
+            None,
+            Utc.timestamp_millis(1599457984000),
+            1051208286,
+            "ChiefScientist".to_string(),
+            "Ed Kmett".to_string(),
+            "This is\na tweet.".to_string(),
+        );
+
+        let report = DeletedTweetReport::unchecked("ChiefScientist", 1).to_string();
+        let line = super::format_line(
+            &tweet,
+            "20200907055304",
+            "https://twitter.com/ChiefScientist/status/1302847271688523778",
+            false,
+            "",
+        );
+
+        assert!(report.contains("includes 1 archived tweets"));
+        assert!(!report.contains("_(live)_"));
+        assert_eq!(
+            line,
+            "* [ 7 September 2020](https://web.archive.org/web/20200907055304/https://twitter.com/ChiefScientist/status/1302847271688523778): This is a tweet. <!--1302847271688523778-->"
+        );
+    }
+}
@@ -1,4 +1,6 @@
 pub mod deleted_tweets;
+pub mod store_index;
+pub mod tweet;
 
 use serde::Serialize;
 use tinytemplate::{error::Result, TinyTemplate};
@@ -18,3 +20,34 @@ pub trait Report: Serialize {
             .unwrap_or_else(|err| panic!("Cannot render {}: {}", Self::title(), err))
     }
 }
+
+/// Split report items into pages of at most `page_size` items, preserving their order.
+///
+/// Each page is paired with a file name of the form `{prefix}-001.md`.
+pub fn paginate<'a, T>(prefix: &str, items: &'a [T], page_size: usize) -> Vec<(String, &'a [T])> {
+    items
+        .chunks(page_size.max(1))
+        .enumerate()
+        .map(|(i, page)| (format!("{}-{:03}.md", prefix, i + 1), page))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_paginate() {
+        let items = (0..25).rev().collect::<Vec<_>>();
+        let pages = super::paginate("report", &items, 10);
+
+        assert_eq!(pages.len(), 3);
+        assert_eq!(pages[0].0, "report-001.md");
+        assert_eq!(pages[2].0, "report-003.md");
+        assert_eq!(pages[0].1, &(15..25).rev().collect::<Vec<_>>()[..]);
+        assert_eq!(pages[1].1.first(), Some(&14));
+        assert_eq!(pages[1].1.last(), Some(&5));
+        assert_eq!(pages[2].1, &[4, 3, 2, 1, 0]);
+
+        assert_eq!(super::paginate("report", &items, 25).len(), 1);
+        assert!(super::paginate::<u64>("report", &[], 10).is_empty());
+    }
+}
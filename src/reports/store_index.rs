@@ -0,0 +1,56 @@
+use super::Report;
+use serde::Serialize;
+use std::fmt::{Display, Error, Formatter};
+use std::result::Result;
+
+static TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head><meta charset=\"utf-8\"><title>Wayback Machine store</title></head>
+<body>
+<h1>Wayback Machine store</h1>
+<p>{item_count} items</p>
+<ul>
+{{for item in items}}<li><a href=\"/{item.digest}\">{item.url}</a> ({item.timestamp}, {item.mime_type}, {item.status})</li>
+{{endfor}}</ul>
+</body>
+</html>
+";
+
+#[derive(Serialize)]
+pub struct StoreIndexItem {
+    pub url: String,
+    pub timestamp: String,
+    pub digest: String,
+    pub mime_type: String,
+    pub status: String,
+}
+
+#[derive(Serialize)]
+pub struct StoreIndexReport {
+    item_count: usize,
+    items: Vec<StoreIndexItem>,
+}
+
+impl StoreIndexReport {
+    pub fn new(items: Vec<StoreIndexItem>) -> StoreIndexReport {
+        StoreIndexReport {
+            item_count: items.len(),
+            items,
+        }
+    }
+}
+
+impl Report for StoreIndexReport {
+    fn title() -> &'static str {
+        "Store index"
+    }
+    fn template() -> &'static str {
+        TEMPLATE
+    }
+}
+
+impl Display for StoreIndexReport {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}", self.render())
+    }
+}
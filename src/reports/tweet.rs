@@ -0,0 +1,171 @@
+use super::Report;
+use crate::browser::twitter::parser::BrowserTweet;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt::{Display, Error, Formatter};
+use std::result::Result;
+
+const LINK_PATTERN: &str =
+    r"((?:https?://)?pic\.twitter\.com/\w+)|(https?://[^\s<>]+)|@(\w{1,15})|#(\w+)";
+
+static TEMPLATE: &str = "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{user_name} (@{screen_name}): {id}</title>
+</head>
+<body>
+<article class=\"tweet\">
+<header>
+<a class=\"user\" href=\"https://twitter.com/{screen_name}\">{user_name} (@{screen_name})</a>
+</header>
+{{if reply_url}}<p class=\"reply\">Replying to <a href=\"{reply_url}\">{reply_url}</a></p>
+{{endif}}<p class=\"text\">{text_html | unescaped}</p>
+{{for url in media}}<p class=\"media\"><a href=\"{url}\">{url}</a></p>
+{{endfor}}{{for url in images}}<p class=\"media\"><a href=\"{url}\"><img src=\"{url}\"></a></p>
+{{endfor}}<footer>
+<a class=\"time\" href=\"https://twitter.com/{screen_name}/status/{id}\"><time datetime=\"{timestamp}\">{time}</time></a>
+</footer>
+</article>
+</body>
+</html>
+";
+
+#[derive(Serialize)]
+pub struct TweetPage {
+    id: u64,
+    screen_name: String,
+    user_name: String,
+    reply_url: Option<String>,
+    text_html: String,
+    media: Vec<String>,
+    images: Vec<String>,
+    timestamp: String,
+    time: String,
+}
+
+impl TweetPage {
+    pub fn new(tweet: &BrowserTweet) -> TweetPage {
+        let (text_html, media) = linkify(&tweet.text);
+
+        TweetPage {
+            id: tweet.id,
+            screen_name: tweet.user_screen_name.clone(),
+            user_name: tweet.user_name.clone(),
+            reply_url: tweet
+                .parent_id
+                .map(|id| format!("https://twitter.com/i/web/status/{}", id)),
+            text_html,
+            media,
+            images: tweet.media.clone(),
+            timestamp: tweet.time.to_rfc3339(),
+            time: tweet.time.format("%e %B %Y %H:%M:%S UTC").to_string(),
+        }
+    }
+}
+
+impl Report for TweetPage {
+    fn title() -> &'static str {
+        "Tweet page"
+    }
+    fn template() -> &'static str {
+        TEMPLATE
+    }
+}
+
+impl Display for TweetPage {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        write!(f, "{}", self.render())
+    }
+}
+
+/// Render a standalone HTML page for an archived tweet.
+pub fn render_tweet_html(tweet: &BrowserTweet) -> String {
+    TweetPage::new(tweet).render()
+}
+
+/// Escape the tweet text for HTML and turn URLs, mentions, and hashtags into links.
+///
+/// Media links (`pic.twitter.com`) are also returned separately.
+fn linkify(text: &str) -> (String, Vec<String>) {
+    lazy_static! {
+        static ref LINK_RE: Regex = Regex::new(LINK_PATTERN).unwrap();
+    }
+
+    let mut html = String::new();
+    let mut media = vec![];
+    let mut last = 0;
+
+    for captures in LINK_RE.captures_iter(text) {
+        let all = captures.get(0).unwrap();
+        tinytemplate::escape(&text[last..all.start()], &mut html);
+        last = all.end();
+
+        let href = if let Some(url) = captures.get(1) {
+            let url = if url.as_str().starts_with("http") {
+                url.as_str().to_string()
+            } else {
+                format!("https://{}", url.as_str())
+            };
+            media.push(url.clone());
+            url
+        } else if let Some(url) = captures.get(2) {
+            url.as_str().to_string()
+        } else if let Some(screen_name) = captures.get(3) {
+            format!("https://twitter.com/{}", screen_name.as_str())
+        } else {
+            format!(
+                "https://twitter.com/hashtag/{}",
+                captures.get(4).unwrap().as_str()
+            )
+        };
+
+        html.push_str("<a href=\"");
+        tinytemplate::escape(&href, &mut html);
+        html.push_str("\">");
+        tinytemplate::escape(all.as_str(), &mut html);
+        html.push_str("</a>");
+    }
+
+    tinytemplate::escape(&text[last..], &mut html);
+
+    (html, media)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::browser::twitter::parser::BrowserTweet;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_render_tweet_html() {
+        let tweet = BrowserTweet::new(
+            1302847271688523778,
+            None,
+            Utc.timestamp_millis(1599457984000),
+            1051208286,
+            "ChiefScientist".to_string(),
+            "Ed Kmett".to_string(),
+            "1 < 2 & @travisbrown says <b>hi</b> #rust https://example.com/a?b=c https://pic.twitter.com/def456 pic.twitter.com/abc123"
+                .to_string(),
+        )
+        .with_media(vec![
+            "https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg".to_string()
+        ]);
+
+        let html = super::render_tweet_html(&tweet);
+
+        assert!(html.contains("Ed Kmett (@ChiefScientist)"));
+        assert!(html.contains("https://twitter.com/ChiefScientist/status/1302847271688523778"));
+        assert!(html.contains("1 &lt; 2 &amp; "));
+        assert!(html.contains("&lt;b&gt;hi&lt;/b&gt;"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("<a href=\"https://twitter.com/travisbrown\">@travisbrown</a>"));
+        assert!(html.contains("<a href=\"https://twitter.com/hashtag/rust\">#rust</a>"));
+        assert!(html.contains("<a href=\"https://example.com/a?b=c\">"));
+        assert!(html.contains("<p class=\"media\"><a href=\"https://pic.twitter.com/abc123\">"));
+        assert!(html.contains("<p class=\"media\"><a href=\"https://pic.twitter.com/def456\">"));
+        assert!(html.contains("<img src=\"https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg\">"));
+    }
+}
@@ -0,0 +1,201 @@
+use serde::Serialize;
+use std::io::Write;
+
+/// A CSV writer with consistent quoting for use by the command-line tools.
+///
+/// Fields are quoted only when necessary (e.g. when they contain commas, quotes, or newlines), and
+/// records are not required to have the same number of fields.
+pub struct RecordWriter<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_separator(writer, b',')
+    }
+
+    /// Create a writer that separates fields with the given byte (e.g. `b'\t'`).
+    ///
+    /// Fields that contain the separator are quoted.
+    pub fn with_separator(writer: W, separator: u8) -> Self {
+        RecordWriter {
+            writer: csv::WriterBuilder::new()
+                .has_headers(false)
+                .flexible(true)
+                .delimiter(separator)
+                .quote_style(csv::QuoteStyle::Necessary)
+                .from_writer(writer),
+        }
+    }
+
+    /// Create a writer and immediately write a header row.
+    pub fn with_header<H: AsRef<[u8]>>(writer: W, header: &[H]) -> csv::Result<Self> {
+        let mut result = Self::new(writer);
+        result.write_fields(header)?;
+        Ok(result)
+    }
+
+    /// Write a record (such as a tuple) whose fields can be serialized individually.
+    pub fn write<R: Serialize>(&mut self, record: &R) -> csv::Result<()> {
+        self.writer.serialize(record)
+    }
+
+    pub fn write_fields<I: IntoIterator<Item = F>, F: AsRef<[u8]>>(
+        &mut self,
+        fields: I,
+    ) -> csv::Result<()> {
+        self.writer.write_record(fields)
+    }
+
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl RecordWriter<std::io::Stdout> {
+    pub fn stdout() -> Self {
+        Self::new(std::io::stdout())
+    }
+}
+
+/// Format a single record as a line of CSV (without a line terminator).
+pub fn format_record<I: IntoIterator<Item = F>, F: AsRef<[u8]>>(fields: I) -> String {
+    format_record_with_separator(fields, b',')
+}
+
+/// Format a single record with the given field separator (without a line terminator).
+pub fn format_record_with_separator<I: IntoIterator<Item = F>, F: AsRef<[u8]>>(
+    fields: I,
+    separator: u8,
+) -> String {
+    let mut buffer = vec![];
+
+    {
+        let mut writer = RecordWriter::with_separator(&mut buffer, separator);
+        // Writing to a vector can't fail.
+        writer.write_fields(fields).unwrap();
+        writer.flush().unwrap();
+    }
+
+    let line = String::from_utf8_lossy(&buffer);
+    line.trim_end_matches(&['\r', '\n'][..]).to_string()
+}
+
+/// Parse a field separator argument, which must be a single ASCII character (or `\t` or `tab`).
+pub fn parse_separator(input: &str) -> Result<u8, String> {
+    match input {
+        "\\t" | "tab" => Ok(b'\t'),
+        _ => match input.as_bytes() {
+            [byte] if byte.is_ascii() && *byte != b'"' && *byte != b'\n' => Ok(*byte),
+            _ => Err(format!("Invalid separator: {:?}", input)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordWriter;
+
+    fn parse(input: &[u8]) -> Vec<Vec<String>> {
+        parse_with_separator(input, b',')
+    }
+
+    fn parse_with_separator(input: &[u8], separator: u8) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .delimiter(separator)
+            .flexible(true)
+            .from_reader(input)
+            .records()
+            .map(|record| {
+                record
+                    .unwrap()
+                    .iter()
+                    .map(|field| field.to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_record_writer_round_trip() {
+        let tricky = vec![
+            "plain",
+            "with, comma",
+            "with \"quotes\"",
+            "with\nnewline",
+            "semi;colon",
+        ];
+        let mut buffer = vec![];
+
+        {
+            let mut writer =
+                RecordWriter::with_header(&mut buffer, &["a", "b", "c", "d", "e"]).unwrap();
+            writer.write_fields(&tricky).unwrap();
+            writer.write(&(123u64, "x,y", true)).unwrap();
+            writer.flush().unwrap();
+        }
+
+        let rows = parse(&buffer);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], vec!["a", "b", "c", "d", "e"]);
+        assert_eq!(rows[1], tricky);
+        assert_eq!(rows[2], vec!["123", "x,y", "true"]);
+    }
+
+    #[test]
+    fn test_format_record() {
+        let line = super::format_record(["1", "a \"b\"", "c,d"]);
+
+        assert_eq!(line, "1,\"a \"\"b\"\"\",\"c,d\"");
+        assert_eq!(parse(line.as_bytes()), vec![vec!["1", "a \"b\"", "c,d"]]);
+        assert_eq!(super::format_record(["1", "plain"]), "1,plain");
+    }
+
+    #[test]
+    fn test_format_record_with_separator() {
+        // Fields like those in tweet reports, where media URLs and retweet info are joined with
+        // semicolons.
+        let fields = [
+            "1503631923154984960",
+            "1503631923154984961;6510972;travisbrown",
+            "https://twitter.com/a/status/1/photo/1;https://twitter.com/a/status/1/photo/2",
+            "with\ttab",
+            "with space",
+        ];
+
+        for separator in [b',', b';', b'\t', b' '] {
+            let line = super::format_record_with_separator(fields, separator);
+
+            assert_eq!(
+                parse_with_separator(line.as_bytes(), separator),
+                vec![fields.to_vec()]
+            );
+        }
+
+        assert_eq!(
+            super::format_record_with_separator(fields, b';'),
+            "1503631923154984960;\"1503631923154984961;6510972;travisbrown\";\
+             \"https://twitter.com/a/status/1/photo/1;https://twitter.com/a/status/1/photo/2\";\
+             with\ttab;with space"
+        );
+        assert_eq!(
+            super::format_record_with_separator(["1", "travisbrown", "100"], b' '),
+            "1 travisbrown 100"
+        );
+    }
+
+    #[test]
+    fn test_parse_separator() {
+        assert_eq!(super::parse_separator(","), Ok(b','));
+        assert_eq!(super::parse_separator(";"), Ok(b';'));
+        assert_eq!(super::parse_separator("\\t"), Ok(b'\t'));
+        assert_eq!(super::parse_separator("tab"), Ok(b'\t'));
+        assert_eq!(super::parse_separator("\t"), Ok(b'\t'));
+        assert!(super::parse_separator("").is_err());
+        assert!(super::parse_separator(",,").is_err());
+        assert!(super::parse_separator("\"").is_err());
+        assert!(super::parse_separator("\u{e9}").is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashSet;
+
+/// A run of words in a word-level diff.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiffSegment {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Compute a word-level diff between two strings.
+///
+/// Words are separated by whitespace, and consecutive words with the same status are joined into a
+/// single segment (with single spaces).
+pub fn word_diff(old: &str, new: &str) -> Vec<DiffSegment> {
+    let old_words = old.split_whitespace().collect::<Vec<_>>();
+    let new_words = new.split_whitespace().collect::<Vec<_>>();
+
+    // Longest common subsequence lengths for all suffixes.
+    let mut lengths = vec![vec![0usize; new_words.len() + 1]; old_words.len() + 1];
+
+    for i in (0..old_words.len()).rev() {
+        for j in (0..new_words.len()).rev() {
+            lengths[i][j] = if old_words[i] == new_words[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut segments: Vec<DiffSegment> = vec![];
+    let mut push = |segment: DiffSegment| match (segments.last_mut(), segment) {
+        (Some(DiffSegment::Same(last)), DiffSegment::Same(word))
+        | (Some(DiffSegment::Removed(last)), DiffSegment::Removed(word))
+        | (Some(DiffSegment::Added(last)), DiffSegment::Added(word)) => {
+            last.push(' ');
+            last.push_str(&word);
+        }
+        (_, segment) => segments.push(segment),
+    };
+
+    let (mut i, mut j) = (0, 0);
+
+    while i < old_words.len() && j < new_words.len() {
+        if old_words[i] == new_words[j] {
+            push(DiffSegment::Same(old_words[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            push(DiffSegment::Removed(old_words[i].to_string()));
+            i += 1;
+        } else {
+            push(DiffSegment::Added(new_words[j].to_string()));
+            j += 1;
+        }
+    }
+
+    for word in &old_words[i..] {
+        push(DiffSegment::Removed(word.to_string()));
+    }
+
+    for word in &new_words[j..] {
+        push(DiffSegment::Added(word.to_string()));
+    }
+
+    segments
+}
+
+/// IDs added to and removed from a set (for example between two block list snapshots).
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct IdDiff {
+    pub added: Vec<u64>,
+    pub removed: Vec<u64>,
+}
+
+/// Parse whitespace-separated IDs, ignoring anything that isn't a valid ID.
+pub fn parse_ids(input: &str) -> Vec<u64> {
+    input
+        .split_whitespace()
+        .flat_map(|value| value.parse::<u64>().ok())
+        .collect()
+}
+
+/// Compute the (sorted) IDs that are only in the new list and only in the old list.
+pub fn id_diff(old: &[u64], new: &[u64]) -> IdDiff {
+    let old = old.iter().copied().collect::<HashSet<_>>();
+    let new = new.iter().copied().collect::<HashSet<_>>();
+
+    let mut added = new.difference(&old).copied().collect::<Vec<_>>();
+    let mut removed = old.difference(&new).copied().collect::<Vec<_>>();
+    added.sort_unstable();
+    removed.sort_unstable();
+
+    IdDiff { added, removed }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DiffSegment::*;
+
+    #[test]
+    fn test_id_diff() {
+        let old = super::parse_ids("1 2 3\n4\nnot-an-id\n");
+        let new = super::parse_ids("5\n3\n1\n6\n5\n");
+
+        assert_eq!(old, vec![1, 2, 3, 4]);
+        assert_eq!(
+            super::id_diff(&old, &new),
+            super::IdDiff {
+                added: vec![5, 6],
+                removed: vec![2, 4]
+            }
+        );
+        assert_eq!(super::id_diff(&new, &new), super::IdDiff::default());
+    }
+
+    #[test]
+    fn test_word_diff() {
+        assert_eq!(
+            super::word_diff("a b c d", "a x c d e"),
+            vec![
+                Same("a".to_string()),
+                Removed("b".to_string()),
+                Added("x".to_string()),
+                Same("c d".to_string()),
+                Added("e".to_string())
+            ]
+        );
+        assert_eq!(super::word_diff("", ""), vec![]);
+    }
+}
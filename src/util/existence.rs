@@ -0,0 +1,114 @@
+use super::sqlite::{SQLiteDateTime, SQLiteId};
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::path::Path;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS existence (
+        twitter_id INTEGER NOT NULL PRIMARY KEY,
+        live INTEGER NOT NULL,
+        checked_at INTEGER NOT NULL
+    );
+";
+
+const SELECT: &str = "SELECT live, checked_at FROM existence WHERE twitter_id = ?";
+const UPSERT: &str = "
+    INSERT INTO existence (twitter_id, live, checked_at) VALUES (?, ?, ?)
+        ON CONFLICT (twitter_id) DO UPDATE SET live = excluded.live, checked_at = excluded.checked_at
+";
+
+/// Cached results (status IDs with whether they existed) and IDs that need to be checked.
+pub type Partition = (Vec<(u64, bool)>, Vec<u64>);
+
+/// An on-disk record of whether tweets existed when they were last checked.
+pub struct ExistenceCache {
+    connection: Connection,
+}
+
+impl ExistenceCache {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ExistenceCache> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(ExistenceCache { connection })
+    }
+
+    /// Look up a status ID, returning whether it existed and when it was checked.
+    pub fn get(&self, id: u64) -> Result<Option<(bool, DateTime<Utc>)>> {
+        let mut select = self.connection.prepare_cached(SELECT)?;
+
+        select
+            .query_row(params![SQLiteId(id)], |row| {
+                let live: bool = row.get(0)?;
+                let checked_at: SQLiteDateTime = row.get(1)?;
+
+                Ok((live, checked_at.0))
+            })
+            .optional()
+    }
+
+    pub fn record(&self, id: u64, live: bool, checked_at: DateTime<Utc>) -> Result<()> {
+        let mut upsert = self.connection.prepare_cached(UPSERT)?;
+        upsert.execute(params![SQLiteId(id), live, SQLiteDateTime(checked_at)])?;
+
+        Ok(())
+    }
+
+    /// Split status IDs into those with results checked within the TTL and those to be checked.
+    pub fn partition<I: IntoIterator<Item = u64>>(
+        &self,
+        ids: I,
+        now: DateTime<Utc>,
+        ttl: Duration,
+    ) -> Result<Partition> {
+        let mut cached = vec![];
+        let mut stale = vec![];
+
+        for id in ids {
+            match self.get(id)? {
+                Some((live, checked_at)) if now - checked_at < ttl => cached.push((id, live)),
+                _ => stale.push(id),
+            }
+        }
+
+        Ok((cached, stale))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ExistenceCache;
+    use chrono::{Duration, TimeZone, Utc};
+
+    #[test]
+    fn test_existence_cache_ttl() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ExistenceCache::open(dir.path().join("existence.db")).unwrap();
+        let checked_at = Utc.timestamp_millis_opt(1599457984000).unwrap();
+        let ttl = Duration::hours(24);
+
+        cache.record(1, true, checked_at).unwrap();
+        cache.record(2, false, checked_at).unwrap();
+
+        let (cached, stale) = cache
+            .partition(vec![1, 2, 3], checked_at + Duration::hours(1), ttl)
+            .unwrap();
+
+        assert_eq!(cached, vec![(1, true), (2, false)]);
+        assert_eq!(stale, vec![3]);
+
+        let (cached, stale) = cache
+            .partition(vec![1, 2, 3], checked_at + Duration::hours(25), ttl)
+            .unwrap();
+
+        assert!(cached.is_empty());
+        assert_eq!(stale, vec![1, 2, 3]);
+
+        // Re-checking updates the result and time.
+        let rechecked_at = checked_at + Duration::hours(25);
+        cache.record(1, false, rechecked_at).unwrap();
+
+        assert_eq!(cache.get(1).unwrap(), Some((false, rechecked_at)));
+        assert_eq!(cache.get(3).unwrap(), None);
+    }
+}
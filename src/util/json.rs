@@ -0,0 +1,106 @@
+use serde::Serialize;
+use std::io::Write;
+
+/// Writes a stream of JSON values either one per line or as a single JSON array.
+///
+/// In array mode values are written as they arrive (so nothing is buffered), and the array is
+/// closed by [`JsonWriter::finish`], which writes `[]` if there were no values.
+pub struct JsonWriter<W: Write> {
+    writer: W,
+    array: bool,
+    count: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(writer: W, array: bool) -> Self {
+        JsonWriter {
+            writer,
+            array,
+            count: 0,
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, value: &T) -> serde_json::Result<()> {
+        if self.array {
+            let separator: &[u8] = if self.count == 0 { b"[\n" } else { b",\n" };
+            self.writer
+                .write_all(separator)
+                .map_err(serde_json::Error::io)?;
+            serde_json::to_writer(&mut self.writer, value)?;
+        } else {
+            serde_json::to_writer(&mut self.writer, value)?;
+            self.writer
+                .write_all(b"\n")
+                .map_err(serde_json::Error::io)?;
+        }
+
+        self.count += 1;
+
+        Ok(())
+    }
+
+    /// Close the array (if necessary) and flush, returning the number of values written.
+    pub fn finish(mut self) -> std::io::Result<usize> {
+        if self.array {
+            let end: &[u8] = if self.count == 0 { b"[]\n" } else { b"\n]\n" };
+            self.writer.write_all(end)?;
+        }
+
+        self.writer.flush()?;
+
+        Ok(self.count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JsonWriter;
+    use serde_json::{json, Value};
+
+    fn write_all(values: &[Value], array: bool) -> String {
+        let mut buffer = vec![];
+        let mut writer = JsonWriter::new(&mut buffer, array);
+
+        for value in values {
+            writer.write(value).unwrap();
+        }
+
+        assert_eq!(writer.finish().unwrap(), values.len());
+
+        String::from_utf8(buffer).unwrap()
+    }
+
+    #[test]
+    fn test_json_array() {
+        let values = [
+            json!({"id": 1, "screen_name": "a,b"}),
+            json!({"id": 2, "screen_name": "[c]"}),
+            json!({"id": 3, "screen_name": "d"}),
+        ];
+
+        let output = write_all(&values, true);
+        let parsed: Vec<Value> = serde_json::from_str(&output).unwrap();
+
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed, values);
+    }
+
+    #[test]
+    fn test_json_array_empty() {
+        let output = write_all(&[], true);
+
+        assert_eq!(output, "[]\n");
+        assert_eq!(
+            serde_json::from_str::<Vec<Value>>(&output).unwrap(),
+            Vec::<Value>::new()
+        );
+    }
+
+    #[test]
+    fn test_json_lines() {
+        let values = [json!({"id": 1}), json!({"id": 2})];
+
+        assert_eq!(write_all(&values, false), "{\"id\":1}\n{\"id\":2}\n");
+        assert_eq!(write_all(&[], false), "");
+    }
+}
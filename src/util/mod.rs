@@ -1 +1,101 @@
+pub mod csv;
+pub mod diff;
+pub mod existence;
+pub mod json;
+pub mod screen_names;
 pub mod sqlite;
+
+use lazy_static::lazy_static;
+
+pub(crate) const TWITTER_HOSTS: [&str; 6] = [
+    "twitter.com",
+    "www.twitter.com",
+    "mobile.twitter.com",
+    "x.com",
+    "www.x.com",
+    "mobile.x.com",
+];
+
+/// Parse a reference to a tweet (a bare status ID or a Twitter or X status URL).
+///
+/// URLs may omit the scheme and may include ports, query strings, or fragments, and old-style
+/// `#!/` paths are also supported. User handles (e.g. `@jack`) do not identify a tweet and are
+/// rejected.
+pub fn parse_tweet_ref(input: &str) -> Option<u64> {
+    lazy_static! {
+        static ref STATUS_PATH_RE: regex::Regex =
+            regex::Regex::new(r"^/(?:[^/]+/)*status(?:es)?/(\d+)/?(?:$|/)").unwrap();
+    }
+
+    let input = input.trim();
+
+    if !input.is_empty() && input.bytes().all(|b| b.is_ascii_digit()) {
+        return input.parse().ok();
+    }
+
+    let url = if input.contains("://") {
+        url::Url::parse(input)
+    } else {
+        url::Url::parse(&format!("https://{}", input))
+    }
+    .ok()?;
+
+    let host = url.host_str()?.to_lowercase();
+
+    if !TWITTER_HOSTS.contains(&host.as_str()) {
+        return None;
+    }
+
+    let path = match url.fragment() {
+        Some(fragment) if url.path() == "/" && fragment.starts_with("!/") => &fragment[1..],
+        _ => url.path(),
+    };
+
+    STATUS_PATH_RE
+        .captures(path)
+        .and_then(|captures| captures.get(1))
+        .and_then(|id| id.as_str().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse_tweet_ref() {
+        let cases = vec![
+            ("1170761943067631621", Some(1170761943067631621)),
+            ("  20\n", Some(20)),
+            ("https://twitter.com/jack/status/20", Some(20)),
+            ("https://twitter.com/jack/status/20/", Some(20)),
+            ("https://twitter.com/jack/status/20?lang=en&s=20", Some(20)),
+            ("https://twitter.com/jack/status/20#reply", Some(20)),
+            ("http://twitter.com:80/jack/status/20", Some(20)),
+            ("https://www.twitter.com/jack/statuses/20", Some(20)),
+            ("https://mobile.twitter.com/jack/status/20", Some(20)),
+            ("https://x.com/jack/status/20", Some(20)),
+            ("https://mobile.x.com/jack/status/20/photo/1", Some(20)),
+            ("HTTPS://Twitter.COM/jack/status/20", Some(20)),
+            ("twitter.com/jack/status/20", Some(20)),
+            ("x.com/i/web/status/20", Some(20)),
+            ("https://twitter.com/#!/jack/status/20", Some(20)),
+            ("@jack", None),
+            ("jack", None),
+            ("https://twitter.com/jack", None),
+            ("https://twitter.com/jack/status/abc", None),
+            ("https://twitter.com/jack/status/20abc", None),
+            ("https://example.com/jack/status/20", None),
+            ("https://twitter.com.example.com/jack/status/20", None),
+            ("99999999999999999999999", None),
+            ("-20", None),
+            ("", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                super::parse_tweet_ref(input),
+                expected,
+                "input: {:?}",
+                input
+            );
+        }
+    }
+}
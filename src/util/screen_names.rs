@@ -0,0 +1,205 @@
+use super::sqlite::{SQLiteDateTime, SQLiteId};
+use chrono::{DateTime, Utc};
+use futures::Future;
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Maximum number of screen names to resolve in a single lookup.
+pub const LOOKUP_CHUNK_SIZE: usize = 100;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS screen_name (
+        screen_name TEXT NOT NULL PRIMARY KEY,
+        twitter_id INTEGER NOT NULL,
+        resolved_at INTEGER NOT NULL
+    );
+";
+
+const SELECT: &str = "SELECT twitter_id FROM screen_name WHERE screen_name = ?";
+const UPSERT: &str = "
+    INSERT INTO screen_name (screen_name, twitter_id, resolved_at) VALUES (?, ?, ?)
+        ON CONFLICT (screen_name) DO UPDATE
+            SET twitter_id = excluded.twitter_id, resolved_at = excluded.resolved_at
+";
+
+/// An on-disk record of the user IDs screen names have resolved to.
+///
+/// Screen names are compared case-insensitively.
+pub struct ScreenNameCache {
+    connection: Connection,
+}
+
+impl ScreenNameCache {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<ScreenNameCache> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+
+        Ok(ScreenNameCache { connection })
+    }
+
+    pub fn get(&self, screen_name: &str) -> Result<Option<u64>> {
+        let mut select = self.connection.prepare_cached(SELECT)?;
+
+        select
+            .query_row(params![screen_name.to_lowercase()], |row| {
+                let id: SQLiteId = row.get(0)?;
+                Ok(id.0)
+            })
+            .optional()
+    }
+
+    pub fn record(&self, screen_name: &str, id: u64, resolved_at: DateTime<Utc>) -> Result<()> {
+        let mut upsert = self.connection.prepare_cached(UPSERT)?;
+        upsert.execute(params![
+            screen_name.to_lowercase(),
+            SQLiteId(id),
+            SQLiteDateTime(resolved_at)
+        ])?;
+
+        Ok(())
+    }
+}
+
+/// Resolve screen names to user IDs, looking up at most [`LOOKUP_CHUNK_SIZE`] names at a time.
+///
+/// The lookup function returns the screen names and IDs of the users it finds. Names in the cache
+/// aren't looked up, and newly resolved names are added to it. Names that can't be resolved map
+/// to `None` (and aren't cached, since the name may be taken later).
+pub async fn resolve_screen_names<F, Fut, E>(
+    names: &[String],
+    cache: Option<&ScreenNameCache>,
+    mut lookup: F,
+) -> std::result::Result<HashMap<String, Option<u64>>, E>
+where
+    F: FnMut(Vec<String>) -> Fut,
+    Fut: Future<Output = std::result::Result<Vec<(String, u64)>, E>>,
+    E: From<rusqlite::Error>,
+{
+    let mut result = HashMap::with_capacity(names.len());
+    let mut uncached = vec![];
+
+    for name in names {
+        if result.contains_key(name) {
+            continue;
+        }
+
+        match cache.map(|cache| cache.get(name)).transpose()?.flatten() {
+            Some(id) => {
+                result.insert(name.clone(), Some(id));
+            }
+            None => {
+                result.insert(name.clone(), None);
+                uncached.push(name.clone());
+            }
+        }
+    }
+
+    for chunk in uncached.chunks(LOOKUP_CHUNK_SIZE) {
+        let resolved = lookup(chunk.to_vec())
+            .await?
+            .into_iter()
+            .map(|(screen_name, id)| (screen_name.to_lowercase(), id))
+            .collect::<HashMap<_, _>>();
+        let now = Utc::now();
+
+        for name in chunk {
+            if let Some(id) = resolved.get(&name.to_lowercase()) {
+                result.insert(name.clone(), Some(*id));
+
+                if let Some(cache) = cache {
+                    cache.record(name, *id, now)?;
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ScreenNameCache;
+    use chrono::Utc;
+    use std::cell::RefCell;
+
+    #[tokio::test]
+    async fn test_resolve_screen_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = ScreenNameCache::open(dir.path().join("screen-names.db")).unwrap();
+        cache.record("jdegoes", 346665007, Utc::now()).unwrap();
+
+        let requests = RefCell::new(vec![]);
+        let lookup = |names: Vec<String>| {
+            requests.borrow_mut().push(names.clone());
+
+            async move {
+                Ok::<_, rusqlite::Error>(
+                    names
+                        .into_iter()
+                        .filter(|name| name.to_lowercase() == "travisbrown")
+                        .map(|_| ("travisbrown".to_string(), 6510972))
+                        .collect(),
+                )
+            }
+        };
+
+        let names = ["JDeGoes", "TravisBrown", "nobody_at_all"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect::<Vec<_>>();
+
+        let resolved = super::resolve_screen_names(&names, Some(&cache), lookup)
+            .await
+            .unwrap();
+
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(resolved["JDeGoes"], Some(346665007));
+        assert_eq!(resolved["TravisBrown"], Some(6510972));
+        assert_eq!(resolved["nobody_at_all"], None);
+
+        // The cached handle wasn't requested.
+        assert_eq!(
+            requests.borrow().clone(),
+            vec![vec!["TravisBrown".to_string(), "nobody_at_all".to_string()]]
+        );
+
+        // Newly resolved handles are cached, but unknown ones are looked up again.
+        super::resolve_screen_names(&names, Some(&cache), lookup)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            requests.borrow().last(),
+            Some(&vec!["nobody_at_all".to_string()])
+        );
+        assert_eq!(cache.get("travisbrown").unwrap(), Some(6510972));
+    }
+
+    #[tokio::test]
+    async fn test_resolve_screen_names_chunks() {
+        let names = (0..250).map(|i| format!("user{}", i)).collect::<Vec<_>>();
+        let chunk_sizes = RefCell::new(vec![]);
+
+        let resolved = super::resolve_screen_names(&names, None, |chunk: Vec<String>| {
+            chunk_sizes.borrow_mut().push(chunk.len());
+
+            async move {
+                Ok::<_, rusqlite::Error>(
+                    chunk
+                        .into_iter()
+                        .map(|name| {
+                            let id = name[4..].parse().unwrap();
+                            (name, id)
+                        })
+                        .collect(),
+                )
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(chunk_sizes.into_inner(), vec![100, 100, 50]);
+        assert_eq!(resolved["user249"], Some(249));
+    }
+}
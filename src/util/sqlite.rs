@@ -1,22 +1,102 @@
 use chrono::{DateTime, TimeZone, Utc};
-use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, Value, ValueRef};
-use rusqlite::Result;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, Value, ValueRef};
+use rusqlite::{Connection, Result};
+use std::convert::TryFrom;
+use std::time::Duration;
 
+/// Settings applied to SQLite connections when a store opens its database.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConnectionOptions {
+    /// Use write-ahead logging, so that readers aren't blocked by a writer (and vice versa).
+    pub wal: bool,
+    /// How long to wait for a lock held by another connection before failing.
+    pub busy_timeout: Duration,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        ConnectionOptions {
+            wal: true,
+            busy_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+impl ConnectionOptions {
+    pub(crate) fn apply(&self, connection: &Connection) -> Result<()> {
+        connection.busy_timeout(self.busy_timeout)?;
+
+        if self.wal {
+            connection.pragma_update_and_check(None, "journal_mode", "WAL", |row| {
+                row.get::<_, String>(0)
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A Twitter ID (or other unsigned value) stored in SQLite.
+///
+/// Values that fit in an `i64` are stored as integers. Larger values are stored as 8-byte
+/// big-endian blobs, since SQLite integers are signed and casting would silently wrap.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub(crate) struct SQLiteId(pub(crate) u64);
 
 impl ToSql for SQLiteId {
     fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
-        Ok(ToSqlOutput::Owned(Value::Integer(self.0 as i64)))
+        Ok(ToSqlOutput::Owned(match i64::try_from(self.0) {
+            Ok(value) => Value::Integer(value),
+            Err(_) => Value::Blob(self.0.to_be_bytes().to_vec()),
+        }))
+    }
+}
+
+impl FromSql for SQLiteId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        match value {
+            ValueRef::Integer(value) => u64::try_from(value)
+                .map(SQLiteId)
+                .map_err(|_| FromSqlError::OutOfRange(value)),
+            ValueRef::Blob(bytes) => <[u8; 8]>::try_from(bytes)
+                .map(|bytes| SQLiteId(u64::from_be_bytes(bytes)))
+                .map_err(|_| FromSqlError::InvalidBlobSize {
+                    expected_size: 8,
+                    blob_size: bytes.len(),
+                }),
+            _ => Err(FromSqlError::InvalidType),
+        }
     }
 }
 
+/// A timestamp stored in SQLite as an integer number of milliseconds since the Unix epoch.
+///
+/// Any query that compares a stored timestamp against a literal value should use these
+/// conversions rather than multiplying or dividing by 1000 in SQL.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub(crate) struct SQLiteDateTime(pub(crate) DateTime<Utc>);
 
+impl SQLiteDateTime {
+    /// Milliseconds since the epoch (the stored representation).
+    pub(crate) fn millis(&self) -> i64 {
+        self.0.timestamp_millis()
+    }
+
+    /// Seconds since the epoch, truncating any milliseconds.
+    pub(crate) fn seconds(&self) -> i64 {
+        self.0.timestamp()
+    }
+
+    pub(crate) fn from_millis(millis: i64) -> Option<Self> {
+        Utc.timestamp_millis_opt(millis)
+            .single()
+            .map(SQLiteDateTime)
+    }
+}
+
 impl ToSql for SQLiteDateTime {
     fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
-        Ok(ToSqlOutput::Owned(Value::Integer(
-            self.0.timestamp_millis(),
-        )))
+        Ok(ToSqlOutput::Owned(Value::Integer(self.millis())))
     }
 }
 
@@ -24,6 +104,67 @@ impl FromSql for SQLiteDateTime {
     fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
         let ts: i64 = FromSql::column_result(value)?;
 
-        Ok(SQLiteDateTime(Utc.timestamp_millis(ts)))
+        SQLiteDateTime::from_millis(ts).ok_or(FromSqlError::OutOfRange(ts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SQLiteDateTime, SQLiteId};
+    use chrono::{TimeZone, Utc};
+    use rusqlite::{params, Connection};
+
+    #[test]
+    fn test_sqlite_id_round_trip() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute("CREATE TABLE test (id INTEGER NOT NULL)", [])
+            .unwrap();
+
+        let values = vec![0, 1170761943067631621, i64::MAX as u64, u64::MAX];
+
+        for value in &values {
+            connection
+                .execute(
+                    "INSERT INTO test (id) VALUES (?)",
+                    params![SQLiteId(*value)],
+                )
+                .unwrap();
+        }
+
+        let mut select = connection.prepare("SELECT id FROM test").unwrap();
+        let results = select
+            .query_map([], |row| row.get::<_, SQLiteId>(0))
+            .unwrap()
+            .map(|result| result.unwrap().0)
+            .collect::<Vec<_>>();
+
+        assert_eq!(results, values);
+    }
+
+    #[test]
+    fn test_sqlite_id_rejects_negative() {
+        let connection = Connection::open_in_memory().unwrap();
+        let result = connection.query_row("SELECT -1", [], |row| row.get::<_, SQLiteId>(0));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_sqlite_date_time_round_trip() {
+        let connection = Connection::open_in_memory().unwrap();
+        let value = SQLiteDateTime(Utc.timestamp_millis(1599457984123));
+
+        let stored: i64 = connection
+            .query_row("SELECT ?", params![value], |row| row.get(0))
+            .unwrap();
+        let loaded: SQLiteDateTime = connection
+            .query_row("SELECT ?", params![value], |row| row.get(0))
+            .unwrap();
+
+        assert_eq!(stored, 1599457984123);
+        assert_eq!(loaded, value);
+        assert_eq!(loaded.seconds(), 1599457984);
+        assert_eq!(SQLiteDateTime::from_millis(stored), Some(value));
     }
 }
@@ -1,3 +1,4 @@
+pub mod server;
 pub mod store;
 pub mod tweet;
 pub mod util;
@@ -0,0 +1,142 @@
+use super::store::Store;
+use crate::reports::{
+    store_index::{StoreIndexItem, StoreIndexReport},
+    Report,
+};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{header, Body, Method, Request, Response, Server, StatusCode};
+use std::convert::Infallible;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+/// Serve the contents of a store over HTTP.
+///
+/// The index page lists all items, and each item's (decompressed) content is available at
+/// `/{digest}`. The server is read-only.
+pub async fn serve(store: Arc<Store>, listener: TcpListener) -> Result<(), hyper::Error> {
+    let make_service = make_service_fn(move |_| {
+        let store = store.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let store = store.clone();
+                async move { Ok::<_, Infallible>(handle(&store, request).await) }
+            }))
+        }
+    });
+
+    Server::from_tcp(listener)?.serve(make_service).await
+}
+
+async fn handle(store: &Store, request: Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET {
+        return status_response(StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    match request.uri().path().trim_start_matches('/') {
+        "" => index(store).await,
+        digest => item(store, digest).await,
+    }
+}
+
+async fn index(store: &Store) -> Response<Body> {
+    let mut items = store.filter(|_| true).await;
+    items.sort_unstable_by(|a, b| a.url.cmp(&b.url).then(a.archived_at.cmp(&b.archived_at)));
+
+    let report = StoreIndexReport::new(
+        items
+            .into_iter()
+            .map(|item| StoreIndexItem {
+                timestamp: super::util::format_timestamp(item.archived_at),
+                status: item.status_code(),
+                url: item.url,
+                digest: item.digest,
+                mime_type: item.mime_type,
+            })
+            .collect(),
+    );
+
+    match report.generate() {
+        Ok(html) => Response::builder()
+            .header(header::CONTENT_TYPE, "text/html; charset=utf-8")
+            .body(Body::from(html))
+            .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR)),
+        Err(error) => {
+            log::error!("Error rendering store index: {:?}", error);
+            status_response(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn item(store: &Store, digest: &str) -> Response<Body> {
+    let items = store.items_by_digest(digest).await;
+
+    match items.first() {
+        Some(item) => match store.read(digest) {
+            Ok(Some(content)) => Response::builder()
+                .header(header::CONTENT_TYPE, item.mime_type.as_str())
+                .body(Body::from(content))
+                .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR)),
+            Ok(None) => status_response(StatusCode::NOT_FOUND),
+            Err(error) => {
+                log::error!("Error reading {}: {:?}", digest, error);
+                status_response(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        },
+        None => status_response(StatusCode::NOT_FOUND),
+    }
+}
+
+fn status_response(status: StatusCode) -> Response<Body> {
+    let mut response = Response::new(Body::from(status.to_string()));
+    *response.status_mut() = status;
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::store::Store;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    async fn start_server() -> (Arc<Store>, String) {
+        let store = Arc::new(Store::load("examples/wayback/store/").unwrap());
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+
+        tokio::spawn(super::serve(store.clone(), listener));
+
+        (store, base)
+    }
+
+    #[tokio::test]
+    async fn test_serve_item() {
+        let (store, base) = start_server().await;
+        let digest = "AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O";
+
+        let response = reqwest::get(format!("{}/{}", base, digest)).await.unwrap();
+
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(
+            response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .unwrap(),
+            "text/html"
+        );
+        assert_eq!(
+            response.text().await.unwrap(),
+            store.read(digest).unwrap().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_serve_index_and_missing() {
+        let (_, base) = start_server().await;
+
+        let index = reqwest::get(&base).await.unwrap().text().await.unwrap();
+        assert!(index.contains("href=\"/Y2A3M6COP2G6SKSM4BOHC2MHYS3UW22V\""));
+
+        let missing = reqwest::get(format!("{}/ZZZZ", base)).await.unwrap();
+        assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    }
+}
@@ -1,41 +1,84 @@
 use crate::browser::twitter::parser::{self, BrowserTweet};
 use bytes::Bytes;
+use chrono::NaiveDateTime;
 use csv::{ReaderBuilder, WriterBuilder};
 use data_encoding::BASE32;
 use flate2::read::GzDecoder;
 use flate2::{Compression, GzBuilder};
 use futures::{Future, FutureExt, Stream, StreamExt, TryStreamExt};
-use futures_locks::{Mutex, RwLock};
+use futures_locks::RwLock;
 use itertools::Itertools;
 use sha1::{Digest, Sha1};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File, OpenOptions};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
 use wayback_rs::Item;
 
-use std::fmt::{Debug, Display, Formatter};
+use std::fmt::Debug;
 use tokio::task::JoinError;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
+    #[error("HTTP client error: {0}")]
     ClientError(#[from] reqwest::Error),
+    #[error("Wayback Machine CDX error: {0}")]
+    CdxError(#[from] wayback_rs::cdx::Error),
+    #[error("Invalid Wayback Machine item: {0}")]
     ItemError(#[from] wayback_rs::item::Error),
+    #[error("Unable to parse item: {0}")]
     ItemParsingError(String),
+    #[error("Unable to decode item JSON: {0}")]
     ItemDecodingError(#[from] serde_json::Error),
+    #[error("Store I/O error: {0}")]
     FileIOError(#[from] std::io::Error),
+    #[error("Unable to read store contents file: {0}")]
     StoreContentsDecodingError(#[from] csv::Error),
+    #[error("Unable to write store contents file: {0}")]
     StoreContentsEncodingError(#[from] csv::IntoInnerError<csv::Writer<Vec<u8>>>),
+    #[error("Store task failed: {0}")]
     TaskError(#[from] JoinError),
+    #[error("Invalid store data path: {0}")]
     DataPathError(PathBuf),
+    #[error("Wayback Machine download error: {0}")]
+    DownloaderError(#[from] wayback_rs::downloader::Error),
+    #[error("Digest mismatch for {url}: expected {expected}, got {actual}")]
+    DigestMismatch {
+        url: String,
+        expected: String,
+        actual: String,
+    },
 }
 
-impl Display for Error {
-    fn fmt(&self, f: &mut Formatter) -> Result<(), std::fmt::Error> {
-        Debug::fmt(self, f)
+/// Counts of how incoming files were handled by [`Store::merge_data`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct MergeStats {
+    /// Incoming files with no counterpart in the base directory (kept).
+    pub no_collision: usize,
+    /// Incoming files that are byte-for-byte identical to the base file (excluded).
+    pub identical: usize,
+    /// Collisions where the base file has the correct digest (incoming excluded).
+    pub incoming_bad: usize,
+    /// Collisions where the base file has an incorrect digest (incoming kept).
+    pub base_bad: usize,
+    /// Collisions where neither digest is correct and the incoming file is smaller (excluded).
+    pub smaller_excluded: usize,
+}
+
+impl MergeStats {
+    pub fn excluded(&self) -> usize {
+        self.identical + self.incoming_bad + self.smaller_excluded
     }
 }
 
+/// Counts of URLs processed by [`Store::archive_urls`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ArchiveReport {
+    pub saved: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
 struct Contents {
     by_url: HashMap<String, Vec<Item>>,
     by_digest: HashMap<String, Vec<Item>>,
@@ -60,14 +103,21 @@ impl Contents {
     }
 }
 
+/// Gzip compression level used for new files unless [`Store::with_compression`] is called.
+pub const DEFAULT_COMPRESSION_LEVEL: u32 = 6;
+
 pub struct Store {
     base_dir: PathBuf,
     contents: RwLock<Contents>,
+    compression: Compression,
 }
 
 impl Store {
     const CONTENTS_FILE_NAME: &'static str = "contents.csv";
     const DATA_DIR_NAME: &'static str = "data";
+    const ORPHANS_DIR_NAME: &'static str = "orphans";
+    const MEDIA_DIR_NAME: &'static str = "media";
+    const MEDIA_INDEX_FILE_NAME: &'static str = "media.csv";
 
     pub async fn contains(&self, item: &Item) -> bool {
         let contents = self.contents.read().await;
@@ -79,6 +129,11 @@ impl Store {
         }
     }
 
+    /// Check the index for a digest (without looking at the data directory).
+    pub async fn contains_digest(&self, digest: &str) -> bool {
+        self.contents.read().await.by_digest.contains_key(digest)
+    }
+
     pub async fn count_missing(&self, items: &[Item]) -> usize {
         let contents = self.contents.read().await;
 
@@ -94,6 +149,22 @@ impl Store {
             .count()
     }
 
+    pub async fn missing_items(&self, items: &[Item]) -> Vec<Item> {
+        let contents = self.contents.read().await;
+
+        items
+            .iter()
+            .filter(|item| {
+                if let Some(items) = contents.by_url.get(&item.url) {
+                    !items.contains(item)
+                } else {
+                    true
+                }
+            })
+            .cloned()
+            .collect()
+    }
+
     pub async fn items_by_digest(&self, digest: &str) -> Vec<Item> {
         self.contents
             .read()
@@ -104,6 +175,24 @@ impl Store {
             .unwrap_or_default()
     }
 
+    /// All snapshots of a tweet in the store (under any of its URLs), sorted by archive time.
+    pub async fn snapshots_for_tweet(&self, id: u64) -> Vec<Item> {
+        let contents = self.contents.read().await;
+        let mut items = contents
+            .by_url
+            .iter()
+            .filter(|(url, _)| crate::util::parse_tweet_ref(url) == Some(id))
+            .flat_map(|(_, items)| items.iter().cloned())
+            .collect::<Vec<_>>();
+
+        items.sort_by(|a, b| {
+            a.archived_at
+                .cmp(&b.archived_at)
+                .then_with(|| a.url.cmp(&b.url))
+        });
+        items
+    }
+
     pub async fn add(&self, item: &Item, data: Bytes) -> Result<(), Error> {
         let mut contents = self.contents.write().await;
 
@@ -111,7 +200,7 @@ impl Store {
             let file = File::create(self.data_path(&item.digest))?;
             let mut gz = GzBuilder::new()
                 .filename(item.make_filename())
-                .write(file, Compression::default());
+                .write(file, self.compression);
             gz.write_all(&data)?;
             gz.finish()?;
         }
@@ -125,7 +214,7 @@ impl Store {
         let mut csv = WriterBuilder::new().from_writer(vec![]);
         csv.write_record(&[
             item.url.to_string(),
-            item.timestamp(),
+            super::util::format_timestamp(item.archived_at),
             item.digest.to_string(),
             item.mime_type.to_string(),
             item.status_code(),
@@ -141,6 +230,10 @@ impl Store {
     }
 
     pub fn compute_digest<R: Read>(input: &mut R) -> Result<String, Error> {
+        Ok(Store::compute_digest_io(input)?)
+    }
+
+    fn compute_digest_io<R: Read>(input: &mut R) -> std::io::Result<String> {
         let mut sha1 = Sha1::new();
 
         std::io::copy(input, &mut sha1)?;
@@ -156,6 +249,204 @@ impl Store {
         Store::compute_digest(&mut GzDecoder::new(input))
     }
 
+    /// Download media files (such as tweet images) into the store's media directory.
+    ///
+    /// Files are gzipped and named by the digest of their content, and each URL is recorded in
+    /// the media index with this digest. URLs that are already in the index are skipped, and
+    /// failed downloads are logged. The URL and digest for each newly saved file are returned.
+    pub async fn save_media<F, Fut, E>(
+        &self,
+        urls: &[String],
+        download: F,
+    ) -> std::io::Result<Vec<(String, String)>>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = Result<Bytes, E>>,
+        E: Debug,
+    {
+        let mut known = self.media_index()?;
+        let media_dir_path = self.base_dir.join(Store::MEDIA_DIR_NAME);
+        let mut saved = vec![];
+
+        for url in urls {
+            if known.contains_key(url) {
+                continue;
+            }
+
+            // Downloads happen outside the lock, so that readers aren't blocked on the network.
+            let bytes = match download(url.clone()).await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    log::warn!("Unable to download media {}: {:?}", url, error);
+                    continue;
+                }
+            };
+
+            let digest = Store::compute_digest_io(&mut &bytes[..])?;
+            let path = media_dir_path.join(format!("{}.gz", digest));
+
+            // We don't need the contents, but we don't want to write concurrently.
+            let _contents = self.contents.write().await;
+
+            if !path.is_file() {
+                fs::create_dir_all(&media_dir_path)?;
+                let mut gz = GzBuilder::new().write(File::create(path)?, self.compression);
+                gz.write_all(&bytes)?;
+                gz.finish()?;
+            }
+
+            let index_file = OpenOptions::new()
+                .append(true)
+                .create(true)
+                .open(self.base_dir.join(Store::MEDIA_INDEX_FILE_NAME))?;
+            let mut csv = WriterBuilder::new().from_writer(index_file);
+            csv.write_record([url, &digest])?;
+            csv.flush()?;
+
+            known.insert(url.clone(), digest.clone());
+            saved.push((url.clone(), digest));
+        }
+
+        Ok(saved)
+    }
+
+    /// Capture and store new snapshots of the given URLs.
+    ///
+    /// The `save` function should request a snapshot (e.g. via "Save Page Now") and return its
+    /// timestamp, and `download` should fetch the content for a snapshot. URLs that already have
+    /// any snapshot in the store are skipped, so an interrupted run can simply be restarted.
+    /// Failed saves and downloads are logged and counted, but don't stop the process.
+    pub async fn archive_urls<S, SFut, D, DFut, E>(
+        &self,
+        urls: &[String],
+        save: S,
+        download: D,
+    ) -> Result<ArchiveReport, Error>
+    where
+        S: Fn(String) -> SFut,
+        SFut: Future<Output = Result<Option<NaiveDateTime>, E>>,
+        D: Fn(Item) -> DFut,
+        DFut: Future<Output = Result<Bytes, E>>,
+        E: Debug,
+    {
+        let mut report = ArchiveReport::default();
+
+        for url in urls {
+            if self.contents.read().await.by_url.contains_key(url) {
+                log::info!("Skipping {}", url);
+                report.skipped += 1;
+                continue;
+            }
+
+            let archived_at = match save(url.clone()).await {
+                Ok(Some(archived_at)) => archived_at,
+                Ok(None) => {
+                    log::warn!("Save failed for {}", url);
+                    report.failed += 1;
+                    continue;
+                }
+                Err(error) => {
+                    log::warn!("Save failed for {}: {:?}", url, error);
+                    report.failed += 1;
+                    continue;
+                }
+            };
+
+            // The digest isn't known until we have the content.
+            let mut item = Item::new(
+                url.clone(),
+                archived_at,
+                String::new(),
+                "text/html".to_string(),
+                0,
+                Some(200),
+            );
+
+            let bytes = match download(item.clone()).await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    log::warn!("Unable to download {}: {:?}", url, error);
+                    report.failed += 1;
+                    continue;
+                }
+            };
+
+            item.digest = Store::compute_digest(&mut &bytes[..])?;
+            item.length = bytes.len() as u64;
+
+            self.add(&item, bytes).await?;
+            report.saved += 1;
+        }
+
+        Ok(report)
+    }
+
+    /// Download a single item and add it to the store, returning its digest.
+    ///
+    /// The digest of the downloaded content must match the item's digest (if it has one), and
+    /// nothing is added if it doesn't.
+    pub async fn fetch<D, DFut, E>(&self, item: &Item, download: D) -> Result<String, Error>
+    where
+        D: FnOnce(Item) -> DFut,
+        DFut: Future<Output = Result<Bytes, E>>,
+        Error: From<E>,
+    {
+        let bytes = download(item.clone()).await?;
+        let actual = Store::compute_digest(&mut &bytes[..])?;
+
+        if !item.digest.is_empty() && item.digest != actual {
+            return Err(Error::DigestMismatch {
+                url: item.url.clone(),
+                expected: item.digest.clone(),
+                actual,
+            });
+        }
+
+        let mut item = item.clone();
+        item.digest = actual;
+
+        self.add(&item, bytes).await?;
+
+        Ok(item.digest)
+    }
+
+    /// Read the media index (mapping media URLs to digests).
+    pub fn media_index(&self) -> csv::Result<HashMap<String, String>> {
+        let path = self.base_dir.join(Store::MEDIA_INDEX_FILE_NAME);
+
+        if path.is_file() {
+            ReaderBuilder::new()
+                .has_headers(false)
+                .from_path(path)?
+                .records()
+                .map(|record| {
+                    let row = record?;
+                    Ok((
+                        row.get(0).unwrap_or_default().to_string(),
+                        row.get(1).unwrap_or_default().to_string(),
+                    ))
+                })
+                .collect()
+        } else {
+            Ok(HashMap::new())
+        }
+    }
+
+    /// Compute the digest of content that may or may not be gzipped.
+    ///
+    /// The first value is the digest of the bytes as given, and the second is the digest of the
+    /// decompressed content if the bytes are valid gzip data.
+    pub fn compute_content_digests(bytes: &[u8]) -> std::io::Result<(String, Option<String>)> {
+        let raw = Store::compute_digest_io(&mut &bytes[..])?;
+        let decompressed = if bytes.starts_with(&[0x1f, 0x8b]) {
+            Store::compute_digest_io(&mut GzDecoder::new(bytes)).ok()
+        } else {
+            None
+        };
+
+        Ok((raw, decompressed))
+    }
+
     pub fn compute_item_digest(&self, digest: &str) -> Result<Option<String>, Error> {
         let path = self.data_path(digest);
 
@@ -266,9 +557,53 @@ impl Store {
                 by_digest,
                 file,
             }),
+            compression: Compression::new(DEFAULT_COMPRESSION_LEVEL),
         })
     }
 
+    /// Set the gzip compression level used for new data and media files.
+    ///
+    /// Digests are always computed on the uncompressed content, so this doesn't affect existing
+    /// files.
+    pub fn with_compression(mut self, compression: Compression) -> Store {
+        self.compression = compression;
+        self
+    }
+
+    /// Move data files that aren't listed in the contents file into the orphans directory.
+    ///
+    /// These can be left behind if the process is interrupted between writing the data file and
+    /// appending its row to the contents file in `add`. The compressed files only record the
+    /// digest as their original file name, so we can't recover the item metadata to re-index
+    /// them.
+    ///
+    /// This scans the entire data directory, and should only be run when no other process is
+    /// writing to the store (since a file being added looks like an orphan until it's indexed).
+    pub async fn recover_orphans(&self) -> Result<Vec<PathBuf>, Error> {
+        let contents = self.contents.write().await;
+        let orphans_dir_path = self.base_dir.join(Store::ORPHANS_DIR_NAME);
+        let mut orphans = vec![];
+
+        for entry in fs::read_dir(self.base_dir.join(Store::DATA_DIR_NAME))? {
+            let path = entry?.path();
+
+            if let Some(digest) = Store::extract_digest(&path) {
+                if path.is_file() && !contents.by_digest.contains_key(&digest) {
+                    if !orphans_dir_path.exists() {
+                        fs::create_dir(&orphans_dir_path)?;
+                    }
+
+                    let new_path = orphans_dir_path.join(path.file_name().unwrap_or_default());
+                    log::warn!("Moving unindexed data file to {:?}", new_path);
+                    fs::rename(&path, &new_path)?;
+                    orphans.push(new_path);
+                }
+            }
+        }
+
+        Ok(orphans)
+    }
+
     pub async fn filter<F: Fn(&Item) -> bool>(&self, f: F) -> Vec<Item> {
         let contents = self.contents.read().await;
         contents.filter(f).into_iter().cloned().collect()
@@ -280,42 +615,31 @@ impl Store {
         limit: usize,
     ) -> Result<Vec<(Item, bool)>, Error> {
         let contents = self.contents.read().await;
-        let result = Mutex::new(vec![]);
         let selected = contents.filter(f);
 
         futures::stream::iter(selected.into_iter().cloned())
-            .map(Ok)
-            .try_for_each_concurrent(limit, |item| {
-                let expected = item.digest.clone();
-                let mutex = result.clone();
-
+            .map(|item| {
                 let path = self.data_path(&item.digest);
 
                 tokio::spawn(async move {
                     if path.is_file() {
-                        if let Ok(mut file) = File::open(path) {
-                            if let Ok(actual) = Store::compute_digest_gz(&mut file) {
-                                if actual != expected {
-                                    let mut res = mutex.lock().await;
-                                    res.push((item, false));
-                                }
-                            } else {
-                                let mut res = mutex.lock().await;
-                                res.push((item, true));
-                            }
-                        } else {
-                            let mut res = mutex.lock().await;
-                            res.push((item, true));
+                        match File::open(path)
+                            .and_then(|file| Store::compute_digest_io(&mut GzDecoder::new(file)))
+                        {
+                            Ok(actual) if actual == item.digest => None,
+                            Ok(_) => Some((item, false)),
+                            Err(_) => Some((item, true)),
                         }
                     } else {
-                        let mut res = mutex.lock().await;
-                        res.push((item, true));
+                        Some((item, true))
                     }
                 })
             })
-            .await?;
-
-        Ok(result.lock().await.clone())
+            .buffer_unordered(limit)
+            .try_filter_map(|result| async { Ok(result) })
+            .try_collect()
+            .await
+            .map_err(Error::from)
     }
 
     /// Compute digests for all data files (ignoring the index and logging issues)
@@ -408,7 +732,7 @@ impl Store {
         for item in &selected {
             csv.write_record(&[
                 item.url.to_string(),
-                item.timestamp(),
+                super::util::format_timestamp(item.archived_at),
                 item.digest.to_string(),
                 item.mime_type.to_string(),
                 item.status_code(),
@@ -453,6 +777,62 @@ impl Store {
         Ok(())
     }
 
+    /// Export an archive containing only the items whose digest is in the given set.
+    pub async fn export_digests<W: Write>(
+        &self,
+        name: &str,
+        out: W,
+        digests: &HashSet<String>,
+    ) -> Result<(), Error> {
+        self.export(name, out, |item| digests.contains(&item.digest))
+            .await
+    }
+
+    /// Split the store's digests into `n` groups with roughly equal total file sizes.
+    ///
+    /// Digests are assigned largest first to the group with the smallest total so far, and each
+    /// group is sorted. Digests whose data file is missing count as empty.
+    pub async fn shard_plan(&self, n: usize) -> Vec<Vec<String>> {
+        if n == 0 {
+            return vec![];
+        }
+
+        let mut sized = self
+            .contents
+            .read()
+            .await
+            .by_digest
+            .keys()
+            .map(|digest| {
+                let size = std::fs::metadata(self.data_path(digest))
+                    .map(|metadata| metadata.len())
+                    .unwrap_or(0);
+                (size, digest.clone())
+            })
+            .collect::<Vec<_>>();
+
+        sized.sort_unstable_by(|(size_a, digest_a), (size_b, digest_b)| {
+            size_b.cmp(size_a).then_with(|| digest_a.cmp(digest_b))
+        });
+
+        let mut shards = vec![(0, vec![]); n];
+
+        for (size, digest) in sized {
+            if let Some((total, digests)) = shards.iter_mut().min_by_key(|(total, _)| *total) {
+                *total += size;
+                digests.push(digest);
+            }
+        }
+
+        shards
+            .into_iter()
+            .map(|(_, mut digests)| {
+                digests.sort();
+                digests
+            })
+            .collect()
+    }
+
     fn extract_tweets_from_path<P: AsRef<Path>>(
         p: P,
         mime_type: &str,
@@ -460,19 +840,20 @@ impl Store {
         let path = p.as_ref();
 
         if path.is_file() {
-            let mut file = File::open(path)?;
-
-            if mime_type == "application/json" {
-                let mut doc = String::new();
-                let mut gz = GzDecoder::new(file);
-                gz.read_to_string(&mut doc)?;
-
-                Ok(match parser::extract_tweet_json(&doc) {
-                    Some(tweet) => vec![tweet],
-                    None => vec![],
-                })
+            let mut content = vec![];
+            GzDecoder::new(File::open(path)?).read_to_end(&mut content)?;
+
+            if Store::is_json_content(&content, mime_type) {
+                Ok(
+                    match parser::extract_tweet_json(&String::from_utf8_lossy(&content)) {
+                        Some(tweet) => vec![tweet],
+                        None => vec![],
+                    },
+                )
             } else {
-                match parser::parse_html_gz(&mut file) {
+                let html = super::util::strip_wayback_artifacts(&String::from_utf8_lossy(&content));
+
+                match parser::parse_html(&mut html.as_bytes()) {
                     Ok(doc) => Ok(parser::extract_tweets(&doc)),
                     Err(err) => {
                         log::error!("Failed reading {:?}: {:?}", path, err);
@@ -485,6 +866,29 @@ impl Store {
         }
     }
 
+    /// Decide whether content is JSON, since some items have a wrong or missing mime type.
+    ///
+    /// Content that starts like a JSON object or array is JSON, content that looks like HTML is
+    /// HTML, and otherwise the declared mime type is used.
+    fn is_json_content(content: &[u8], mime_type: &str) -> bool {
+        let without_bom = content.strip_prefix(b"\xef\xbb\xbf").unwrap_or(content);
+        let first = without_bom.iter().find(|byte| !byte.is_ascii_whitespace());
+
+        match first {
+            Some(b'{') | Some(b'[') => true,
+            Some(b'<') => false,
+            _ => {
+                let prefix = String::from_utf8_lossy(&content[..content.len().min(1024)]);
+
+                if prefix.to_lowercase().contains("<html") {
+                    false
+                } else {
+                    mime_type == "application/json"
+                }
+            }
+        }
+    }
+
     pub fn extract_tweets_stream<'a, I: IntoIterator<Item = Item> + 'a>(
         &'a self,
         items: I,
@@ -503,6 +907,49 @@ impl Store {
             .map(|res| res.map_err(From::from).and_then(|inner| inner))
     }
 
+    /// Collect the IDs of all tweets that appear in any item in the store.
+    ///
+    /// This includes tweets embedded in other pages (e.g. replies and quoted tweets), as well as
+    /// the parent of each tweet, if known.
+    pub async fn all_referenced_tweet_ids(&self, limit: usize) -> Result<HashSet<u64>, Error> {
+        let items = self.filter(|_| true).await;
+
+        self.extract_tweets_stream(items, limit)
+            .try_fold(HashSet::new(), |mut acc, (_, tweets)| async move {
+                for tweet in tweets {
+                    acc.insert(tweet.id);
+                    acc.extend(tweet.parent_id);
+                }
+                Ok(acc)
+            })
+            .await
+    }
+
+    /// Collect the IDs of all tweets by the given user that appear in any item in the store.
+    ///
+    /// Screen names are compared case-insensitively.
+    pub async fn author_tweet_ids(
+        &self,
+        screen_name: &str,
+        limit: usize,
+    ) -> Result<HashSet<u64>, Error> {
+        let items = self.filter(|_| true).await;
+        let screen_name = screen_name.to_lowercase();
+        let screen_name = &screen_name;
+
+        self.extract_tweets_stream(items, limit)
+            .try_fold(HashSet::new(), |mut acc, (_, tweets)| async move {
+                acc.extend(
+                    tweets
+                        .into_iter()
+                        .filter(|tweet| tweet.user_screen_name.to_lowercase() == *screen_name)
+                        .map(|tweet| tweet.id),
+                );
+                Ok(acc)
+            })
+            .await
+    }
+
     pub async fn extract_tweets<F: Fn(&Item) -> bool>(
         &self,
         f: F,
@@ -553,16 +1000,19 @@ impl Store {
     }
 
     /// Return a list of paths from the incoming directory that should be excluded
+    ///
+    /// Statistics describing how each collision was resolved are also returned.
     pub fn merge_data<P: AsRef<Path>>(
         base_dir: &P,
         incoming_dir: &P,
-    ) -> Result<Vec<PathBuf>, Error> {
+    ) -> Result<(Vec<PathBuf>, MergeStats), Error> {
         let base_contents = Self::dir_contents_map(base_dir)?;
         let incoming_contents = Self::dir_contents_map(incoming_dir)?;
         let mut incoming_digests = incoming_contents.into_iter().collect::<Vec<_>>();
         incoming_digests.sort_by_key(|p| p.0.clone());
 
         let mut result = vec![];
+        let mut stats = MergeStats::default();
 
         for (digest, (path, size)) in incoming_digests {
             // We only ever consider excluding a path if there's a collision
@@ -580,21 +1030,30 @@ impl Store {
                         if actual != digest {
                             // If neither digest is correct, we always exclude the incoming one if it is smaller
                             if size < *base_size {
+                                stats.smaller_excluded += 1;
                                 result.push(path);
+                            } else {
+                                stats.base_bad += 1;
                             }
+                        } else {
+                            stats.base_bad += 1;
                         }
                     } else {
                         // If the original file has the proper hash, we always exclude the incoming one
+                        stats.incoming_bad += 1;
                         result.push(path);
                     }
                 } else {
                     // If the files are the same we exclude the incoming one
+                    stats.identical += 1;
                     result.push(path);
                 }
+            } else {
+                stats.no_collision += 1;
             }
         }
 
-        Ok(result)
+        Ok((result, stats))
     }
 
     fn dir_contents_map<P: AsRef<Path>>(path: P) -> Result<HashMap<String, (PathBuf, u64)>, Error> {
@@ -652,7 +1111,9 @@ mod tests {
     use bytes::Bytes;
     use chrono::NaiveDate;
     use flate2::{write::GzEncoder, Compression};
+    use std::collections::HashSet;
     use std::fs::File;
+    use std::io::{Read, Write};
     use std::path::PathBuf;
     use wayback_rs::Item;
 
@@ -717,6 +1178,24 @@ mod tests {
         assert_eq!(result, "53SGIJNJMTP6S626CVRCHFTX3OEWXB3E");
     }
 
+    #[test]
+    fn test_store_compute_content_digests() {
+        let raw = std::fs::read("examples/wayback/ZHYT52YPEOCHJD5FZINSDYXGQZI22WJ4").unwrap();
+        let (raw_digest, decompressed_digest) = Store::compute_content_digests(&raw).unwrap();
+
+        assert_eq!(raw_digest, "ZHYT52YPEOCHJD5FZINSDYXGQZI22WJ4");
+        assert_eq!(decompressed_digest, None);
+
+        let gz = std::fs::read("examples/wayback/53SGIJNJMTP6S626CVRCHFTX3OEWXB3E.gz").unwrap();
+        let (raw_digest, decompressed_digest) = Store::compute_content_digests(&gz).unwrap();
+
+        assert_eq!(raw_digest, Store::compute_digest(&mut &gz[..]).unwrap());
+        assert_eq!(
+            decompressed_digest,
+            Some("53SGIJNJMTP6S626CVRCHFTX3OEWXB3E".to_string())
+        );
+    }
+
     #[tokio::test]
     async fn test_store_check_item_digest() {
         let store = Store::load("examples/wayback/store/").unwrap();
@@ -746,6 +1225,22 @@ mod tests {
         assert!(store.contains(&example_item()).await);
     }
 
+    #[tokio::test]
+    async fn test_store_contains_digest() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+
+        assert!(
+            store
+                .contains_digest("AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O")
+                .await
+        );
+        assert!(
+            !store
+                .contains_digest("ZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZZ")
+                .await
+        );
+    }
+
     #[tokio::test]
     async fn test_store_count_missing() {
         let store = Store::load("examples/wayback/store/").unwrap();
@@ -759,6 +1254,336 @@ mod tests {
         assert_eq!(store.count_missing(&items).await, 3);
     }
 
+    #[tokio::test]
+    async fn test_store_missing_items() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+        let items = vec![
+            fake_item("foo"),
+            example_item(),
+            new_example_item(),
+            real_invalid_item(),
+        ];
+
+        assert_eq!(
+            store.missing_items(&items).await,
+            vec![fake_item("foo"), new_example_item()]
+        );
+    }
+
+    #[test]
+    fn test_merge_data_stats() {
+        fn write_gz(dir: &std::path::Path, name: &str, content: &str) {
+            let file = File::create(dir.join(format!("{}.gz", name))).unwrap();
+            let mut gz = GzEncoder::new(file, Compression::default());
+            gz.write_all(content.as_bytes()).unwrap();
+            gz.finish().unwrap();
+        }
+
+        fn digest(content: &str) -> String {
+            Store::compute_digest(&mut content.as_bytes()).unwrap()
+        }
+
+        let base_dir = tempfile::tempdir().unwrap();
+        let incoming_dir = tempfile::tempdir().unwrap();
+        let base = base_dir.path();
+        let incoming = incoming_dir.path();
+
+        // No collision.
+        write_gz(incoming, &digest("new"), "new");
+        // Identical files.
+        write_gz(base, &digest("same"), "same");
+        write_gz(incoming, &digest("same"), "same");
+        // The base file is correct and the incoming one isn't.
+        write_gz(base, &digest("good base"), "good base");
+        write_gz(incoming, &digest("good base"), "bad incoming");
+        // The incoming file is correct and the base one isn't.
+        write_gz(base, &digest("good incoming"), "bad base");
+        write_gz(incoming, &digest("good incoming"), "good incoming");
+        // Neither file is correct, and the incoming one is smaller.
+        let long_content = (0..1000).map(|i| i.to_string()).collect::<String>();
+        write_gz(base, &digest("neither"), &long_content);
+        write_gz(incoming, &digest("neither"), "x");
+
+        let (exclusions, stats) = Store::merge_data(&base, &incoming).unwrap();
+
+        assert_eq!(
+            stats,
+            super::MergeStats {
+                no_collision: 1,
+                identical: 1,
+                incoming_bad: 1,
+                base_bad: 1,
+                smaller_excluded: 1,
+            }
+        );
+        assert_eq!(stats.excluded(), 3);
+
+        let mut expected = vec![
+            incoming.join(format!("{}.gz", digest("same"))),
+            incoming.join(format!("{}.gz", digest("good base"))),
+            incoming.join(format!("{}.gz", digest("neither"))),
+        ];
+        expected.sort();
+        let mut exclusions = exclusions;
+        exclusions.sort();
+
+        assert_eq!(exclusions, expected);
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let missing = "/does/not/exist";
+
+        match Store::load(missing) {
+            Err(error) => assert_eq!(
+                error.to_string(),
+                "Invalid store data path: /does/not/exist"
+            ),
+            Ok(_) => panic!("Expected an error for a missing store directory"),
+        }
+
+        assert_eq!(
+            super::Error::ItemParsingError("foo,bar".to_string()).to_string(),
+            "Unable to parse item: foo,bar"
+        );
+        assert_eq!(
+            super::Error::from(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "no such file"
+            ))
+            .to_string(),
+            "Store I/O error: no such file"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_compression_level() {
+        let item = example_item();
+        let content = Store::load("examples/wayback/store/")
+            .unwrap()
+            .read(&item.digest)
+            .unwrap()
+            .unwrap();
+        let mut sizes = vec![];
+
+        for compression in &[Compression::default(), Compression::best()] {
+            let store_dir = tempfile::tempdir().unwrap();
+            let store = Store::load(store_dir.path())
+                .unwrap()
+                .with_compression(*compression);
+
+            store
+                .add(&item, Bytes::from(content.clone()))
+                .await
+                .unwrap();
+
+            let path = store_dir
+                .path()
+                .join("data")
+                .join(format!("{}.gz", item.digest));
+
+            assert_eq!(store.read(&item.digest).unwrap().as_ref(), Some(&content));
+            assert_eq!(
+                Store::compute_digest_gz(&mut File::open(&path).unwrap()).unwrap(),
+                item.digest
+            );
+
+            sizes.push(std::fs::metadata(&path).unwrap().len());
+        }
+
+        assert!(sizes[1] <= sizes[0]);
+    }
+
+    #[tokio::test]
+    async fn test_store_snapshots_for_tweet() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let store = Store::load(store_dir.path()).unwrap();
+
+        let mut later = example_item();
+        later.url = "https://twitter.com/jdegoes/status/1169217405425455105?lang=en".to_string();
+        later.archived_at = NaiveDate::from_ymd_opt(2020, 1, 1)
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .unwrap();
+        later.digest = "2G3EOT7X6IEQZXKSM3OJJDW6RBCHB7YE".to_string();
+
+        for item in [later.clone(), example_item(), new_example_item()] {
+            store
+                .add(&item, Bytes::from(item.digest.clone()))
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            store.snapshots_for_tweet(1169217405425455105).await,
+            vec![example_item(), later]
+        );
+        assert!(store.snapshots_for_tweet(1).await.is_empty());
+
+        let reloaded = Store::load(store_dir.path()).unwrap();
+        assert_eq!(
+            reloaded
+                .snapshots_for_tweet(1169217405425455105)
+                .await
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_store_fetch() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let store = Store::load(store_dir.path()).unwrap();
+        let content = "<html>A snapshot</html>";
+        let digest = Store::compute_digest(&mut content.as_bytes()).unwrap();
+        let download = |_: Item| async move { Ok::<_, super::Error>(Bytes::from(content)) };
+
+        let mut item = example_item();
+        item.digest = digest.clone();
+
+        assert_eq!(store.fetch(&item, download).await.unwrap(), digest);
+        assert_eq!(store.items_by_digest(&digest).await, vec![item.clone()]);
+        assert_eq!(store.read(&digest).unwrap(), Some(content.to_string()));
+
+        // A snapshot that doesn't match its expected digest isn't added.
+        let mut mismatched = new_example_item();
+        mismatched.digest = "Y2A3M6COP2G6SKSM4BOHC2MHYS3UW22V".to_string();
+
+        match store.fetch(&mismatched, download).await {
+            Err(super::Error::DigestMismatch {
+                expected, actual, ..
+            }) => {
+                assert_eq!(expected, mismatched.digest);
+                assert_eq!(actual, digest);
+            }
+            other => panic!("Expected a digest mismatch, got {:?}", other),
+        }
+
+        assert!(!store.contains(&mismatched).await);
+    }
+
+    #[tokio::test]
+    async fn test_store_archive_urls() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let store = Store::load(store_dir.path()).unwrap();
+        let urls = vec![
+            "https://twitter.com/jdegoes/status/1".to_string(),
+            "https://twitter.com/jdegoes/status/2".to_string(),
+            "https://twitter.com/jdegoes/status/3".to_string(),
+        ];
+        let archived_at = NaiveDate::from_ymd(2021, 1, 2).and_hms(3, 4, 5);
+        let save = |url: String| async move {
+            if url.ends_with('3') {
+                Ok(None)
+            } else {
+                Ok::<_, String>(Some(archived_at))
+            }
+        };
+        let download = |item: Item| async move {
+            if item.url.ends_with('2') {
+                Err("Not found".to_string())
+            } else {
+                Ok(Bytes::from(format!("<html>{}</html>", item.url)))
+            }
+        };
+
+        let report = store.archive_urls(&urls, save, download).await.unwrap();
+
+        assert_eq!(
+            report,
+            super::ArchiveReport {
+                saved: 1,
+                skipped: 0,
+                failed: 2
+            }
+        );
+
+        let expected_content = format!("<html>{}</html>", urls[0]);
+        let expected_digest = Store::compute_digest(&mut expected_content.as_bytes()).unwrap();
+        let items = store.items_by_digest(&expected_digest).await;
+
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].url, urls[0]);
+        assert_eq!(items[0].archived_at, archived_at);
+        assert_eq!(
+            store.read(&expected_digest).unwrap(),
+            Some(expected_content)
+        );
+
+        // A second run only retries the URLs that failed.
+        let calls = std::cell::Cell::new(0);
+        let report = store
+            .archive_urls(
+                &urls,
+                |url| {
+                    calls.set(calls.get() + 1);
+                    save(url)
+                },
+                download,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(calls.get(), 2);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.failed, 2);
+    }
+
+    #[tokio::test]
+    async fn test_store_save_media() {
+        let store_dir = tempfile::tempdir().unwrap();
+        let store = Store::load(store_dir.path()).unwrap();
+        let image_bytes = std::fs::read("examples/1291256191641952256.png").unwrap();
+        let expected_digest = Store::compute_digest(&mut &image_bytes[..]).unwrap();
+        let urls = vec![
+            "https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg".to_string(),
+            "https://pbs.twimg.com/media/missing.jpg".to_string(),
+        ];
+        let download = |url: String| {
+            let image_bytes = image_bytes.clone();
+            async move {
+                if url.contains("missing") {
+                    Err(format!("Not found: {}", url))
+                } else {
+                    Ok(Bytes::from(image_bytes))
+                }
+            }
+        };
+
+        let saved = store.save_media(&urls, download).await.unwrap();
+
+        assert_eq!(saved, vec![(urls[0].clone(), expected_digest.clone())]);
+        assert_eq!(
+            store.media_index().unwrap().get(&urls[0]),
+            Some(&expected_digest)
+        );
+
+        let mut file = File::open(
+            store_dir
+                .path()
+                .join("media")
+                .join(format!("{}.gz", expected_digest)),
+        )
+        .unwrap();
+        assert_eq!(
+            Store::compute_digest_gz(&mut file).unwrap(),
+            expected_digest
+        );
+
+        // Known URLs aren't downloaded again.
+        let calls = std::cell::Cell::new(0);
+        let saved = store
+            .save_media(&urls, |url| {
+                calls.set(calls.get() + 1);
+                download(url)
+            })
+            .await
+            .unwrap();
+
+        assert!(saved.is_empty());
+        assert_eq!(calls.get(), 1);
+        assert_eq!(store.media_index().unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_store_add() {
         let store_dir = tempfile::tempdir().unwrap();
@@ -794,6 +1619,44 @@ mod tests {
         assert_eq!(old_result, vec![example_item()]);
     }
 
+    #[tokio::test]
+    async fn test_store_recover_orphans() {
+        let store_dir = tempfile::tempdir().unwrap();
+        fs_extra::copy_items(
+            &[
+                "examples/wayback/store/contents.csv",
+                "examples/wayback/store/data/",
+            ],
+            store_dir.path(),
+            &fs_extra::dir::CopyOptions::new(),
+        )
+        .unwrap();
+
+        // Simulate an interrupted add, where the data file was written but not indexed.
+        let orphan_name = "53SGIJNJMTP6S626CVRCHFTX3OEWXB3E.gz";
+        std::fs::copy(
+            format!("examples/wayback/{}", orphan_name),
+            store_dir.path().join("data").join(orphan_name),
+        )
+        .unwrap();
+
+        let store = Store::load(store_dir.path()).unwrap();
+
+        // Loading the store doesn't touch the data directory.
+        assert!(store_dir.path().join("data").join(orphan_name).exists());
+
+        let orphans = store.recover_orphans().await.unwrap();
+
+        assert_eq!(
+            orphans,
+            vec![store_dir.path().join("orphans").join(orphan_name)]
+        );
+        assert!(!store_dir.path().join("data").join(orphan_name).exists());
+        assert!(store_dir.path().join("orphans").join(orphan_name).exists());
+        assert!(store.check_item_digest("AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O"));
+        assert_eq!(store.data_paths().count(), 5);
+    }
+
     #[tokio::test]
     async fn test_store_export() {
         let store = Store::load("examples/wayback/store/").unwrap();
@@ -811,6 +1674,133 @@ mod tests {
         assert_eq!(buffer, expected);
     }
 
+    #[tokio::test]
+    async fn test_store_export_digests() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+        let digests: HashSet<String> = [
+            "AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O",
+            "Y2A3M6COP2G6SKSM4BOHC2MHYS3UW22V",
+        ]
+        .iter()
+        .map(|digest| digest.to_string())
+        .collect();
+        let mut buffer = vec![];
+
+        store
+            .export_digests("digests-export-test", &mut buffer, &digests)
+            .await
+            .unwrap();
+
+        let mut archive = tar::Archive::new(&buffer[..]);
+        let mut entries = archive
+            .entries()
+            .unwrap()
+            .map(|entry| {
+                let mut entry = entry.unwrap();
+                let path = entry.path().unwrap().to_string_lossy().to_string();
+                let mut content = String::new();
+                entry.read_to_string(&mut content).unwrap();
+                (path, content)
+            })
+            .collect::<Vec<_>>();
+
+        let (contents_path, contents_csv) = entries.remove(0);
+        assert_eq!(contents_path, "digests-export-test/contents.csv");
+        assert_eq!(contents_csv.lines().count(), 2);
+        assert!(digests.iter().all(|digest| contents_csv.contains(digest)));
+
+        entries.sort();
+        let expected = [
+            "AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O",
+            "Y2A3M6COP2G6SKSM4BOHC2MHYS3UW22V",
+        ]
+        .iter()
+        .map(|digest| {
+            (
+                format!("digests-export-test/data/{}", digest),
+                store.read(digest).unwrap().unwrap(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+        assert_eq!(entries, expected);
+    }
+
+    #[tokio::test]
+    async fn test_store_shard_plan() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+        let shards = store.shard_plan(2).await;
+
+        assert_eq!(shards.len(), 2);
+        assert!(shards.iter().all(|shard| !shard.is_empty()));
+
+        let mut all = shards.concat();
+        all.sort();
+        let mut expected = store
+            .filter(|_| true)
+            .await
+            .into_iter()
+            .map(|item| item.digest)
+            .collect::<Vec<_>>();
+        expected.sort();
+        expected.dedup();
+
+        // The shards don't overlap and cover every item.
+        assert_eq!(all, expected);
+
+        let size = |digest: &String| {
+            std::fs::metadata(format!("examples/wayback/store/data/{}.gz", digest))
+                .unwrap()
+                .len()
+        };
+        let totals = shards
+            .iter()
+            .map(|shard| shard.iter().map(size).sum::<u64>())
+            .collect::<Vec<_>>();
+        let largest = expected.iter().map(size).max().unwrap();
+
+        assert!(totals[0].max(totals[1]) - totals[0].min(totals[1]) <= largest);
+        assert!(store.shard_plan(0).await.is_empty());
+    }
+
+    #[test]
+    fn test_extract_tweets_from_path_mislabeled_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mislabeled.gz");
+        let json = std::fs::read("examples/json/890659426796945408.json").unwrap();
+
+        let mut gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        gz.write_all(&json).unwrap();
+        gz.finish().unwrap();
+
+        for mime_type in ["text/html", "application/json", "warc/revisit"] {
+            let tweets = Store::extract_tweets_from_path(&path, mime_type).unwrap();
+
+            assert_eq!(tweets.len(), 1);
+            assert_eq!(tweets[0].id, 890659426796945408);
+            assert_eq!(tweets[0].user_screen_name, "DrupalLeaks");
+        }
+    }
+
+    #[test]
+    fn test_is_json_content() {
+        assert!(Store::is_json_content(
+            b"\xef\xbb\xbf {\"id\": 1}",
+            "text/html"
+        ));
+        assert!(Store::is_json_content(b"\n[1, 2]", "text/html"));
+        assert!(!Store::is_json_content(
+            b"<!DOCTYPE html><html>",
+            "application/json"
+        ));
+        assert!(!Store::is_json_content(
+            b"garbage <HTML><body></body></HTML>",
+            "application/json"
+        ));
+        assert!(Store::is_json_content(b"", "application/json"));
+        assert!(!Store::is_json_content(b"plain text", "text/plain"));
+    }
+
     #[tokio::test]
     async fn test_store_data_paths() {
         let store = Store::load("examples/wayback/store/").unwrap();
@@ -832,6 +1822,82 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[tokio::test]
+    async fn test_store_all_referenced_tweet_ids() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+        let mut ids = store
+            .all_referenced_tweet_ids(4)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        let expected = vec![
+            1169216072664436736,
+            1169217405425455105,
+            1169224929872621568,
+            1169225920584015873,
+            1169237071623442432,
+            1169241613802950657,
+            1169258519545221120,
+            1169263489082372096,
+            1169322396144287749,
+            1169357904132104192,
+            1169360035740344321,
+            1169361888888655873,
+            1169363008423964673,
+            1169375435353776128,
+            1170420726400212997,
+            1302847271688523778,
+            1304565662661001216,
+            1305511061752344579,
+            1305518012984897536,
+            1305521071278510080,
+        ];
+
+        assert_eq!(ids, expected);
+    }
+
+    #[tokio::test]
+    async fn test_store_author_tweet_ids() {
+        let store = Store::load("examples/wayback/store/").unwrap();
+
+        // This user's only tweet in the store appears in a reply thread on another user's page.
+        let ids = store.author_tweet_ids("UncleBobMartin", 4).await.unwrap();
+
+        assert_eq!(
+            ids.into_iter().collect::<Vec<_>>(),
+            vec![1169241613802950657]
+        );
+        assert!(store
+            .filter(|item| item.url.contains("unclebobmartin"))
+            .await
+            .is_empty());
+
+        let mut ids = store
+            .author_tweet_ids("jdegoes", 4)
+            .await
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        ids.sort_unstable();
+
+        assert_eq!(
+            ids,
+            vec![
+                1169216072664436736,
+                1169217405425455105,
+                1169225920584015873,
+                1169357904132104192,
+                1169361888888655873,
+                1170420726400212997,
+                1305511061752344579,
+                1305518012984897536
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_store_extract_tweets() {
         let store = Store::load("examples/wayback/store/").unwrap();
@@ -855,6 +1921,48 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_store_invalid_digest_items_parallelism() {
+        let store_dir = tempfile::tempdir().unwrap();
+        fs_extra::copy_items(
+            &[
+                "examples/wayback/store/contents.csv",
+                "examples/wayback/store/data/",
+            ],
+            store_dir.path(),
+            &fs_extra::dir::CopyOptions::new(),
+        )
+        .unwrap();
+
+        let store = Store::load(store_dir.path()).unwrap();
+        let new_item_bytes = Bytes::from(
+            std::fs::read("examples/wayback/ZHYT52YPEOCHJD5FZINSDYXGQZI22WJ4").unwrap(),
+        );
+
+        store.add(&fake_item("foo"), new_item_bytes).await.unwrap();
+        std::fs::remove_file(
+            store_dir
+                .path()
+                .join("data")
+                .join("AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O.gz"),
+        )
+        .unwrap();
+
+        for limit in &[1, 2, 16] {
+            let mut result = store.invalid_digest_items(|_| true, *limit).await.unwrap();
+            result.sort_by_key(|(item, _)| item.url.clone());
+
+            assert_eq!(
+                result,
+                vec![
+                    (fake_item("foo"), false),
+                    (example_item(), true),
+                    (real_invalid_item(), false)
+                ]
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_store_invalid_digest_items() {
         let store_dir = tempfile::tempdir().unwrap();
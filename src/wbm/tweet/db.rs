@@ -1,11 +1,15 @@
 use crate::browser::twitter::parser::BrowserTweet;
-use crate::util::sqlite::{SQLiteDateTime, SQLiteId};
-use chrono::{DateTime, Utc};
+use crate::util::diff::{word_diff, DiffSegment};
+use crate::util::sqlite::{ConnectionOptions, SQLiteDateTime, SQLiteId};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
 use futures_locks::RwLock;
-use rusqlite::{params, Connection, DropBehavior, OptionalExtension, Transaction};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::io::Write;
 use std::path::Path;
+use unicode_normalization::UnicodeNormalization;
 
 const USER_SELECT: &str = "
     SELECT id
@@ -15,7 +19,16 @@ const USER_SELECT: &str = "
 const USER_INSERT: &str = "INSERT INTO user (twitter_id, screen_name, name) VALUES (?, ?, ?)";
 
 const FILE_SELECT: &str = "SELECT id FROM file WHERE digest = ?";
-const FILE_INSERT: &str = "INSERT INTO file (digest, primary_twitter_id) VALUES (?, ?)";
+const FILE_INSERT: &str = "INSERT INTO file (digest, primary_twitter_id, url) VALUES (?, ?, ?)";
+
+const FILE_SELECT_BY_TWEET_ID: &str = "
+    SELECT DISTINCT file.digest, file.url
+        FROM tweet
+        JOIN tweet_file ON tweet_file.tweet_id = tweet.id
+        JOIN file ON file.id = tweet_file.file_id
+        WHERE tweet.twitter_id = ?
+        ORDER BY file.digest
+";
 
 const TWEET_SELECT_BY_ID: &str = "
     SELECT parent_twitter_id, ts, user_twitter_id, screen_name, name, content, digest
@@ -63,14 +76,26 @@ const GET_USER_KNOWN_ACTIVE_RANGE: &str = "
         WHERE user.twitter_id = ? AND user.screen_name LIKE ?;
 ";
 
+const GET_SCREEN_NAME_HISTORY: &str = "
+    SELECT user.screen_name, MIN(tweet.ts) AS first_seen, MAX(tweet.ts)
+        FROM user
+        JOIN tweet_file ON tweet_file.user_id = user.id
+        JOIN tweet ON tweet.id = tweet_file.tweet_id AND tweet.user_twitter_id = user.twitter_id
+        WHERE user.twitter_id = ?
+        GROUP BY user.screen_name
+        ORDER BY first_seen;
+";
+
 const GET_REPLIES: &str = "
-    SELECT tweet.twitter_id, reply_tweet.twitter_id, reply_user.twitter_id, reply_user.screen_name FROM tweet
+    SELECT DISTINCT tweet.twitter_id, reply_tweet.twitter_id, reply_user.twitter_id, reply_user.screen_name FROM tweet
         JOIN tweet_file ON tweet_file.tweet_id = tweet.id
         JOIN user ON user.id = tweet_file.user_id
         JOIN tweet AS reply_tweet ON reply_tweet.parent_twitter_id = tweet.twitter_id
         JOIN tweet_file AS reply_tweet_file ON reply_tweet_file.tweet_id = reply_tweet.id
         JOIN user as reply_user ON reply_user.id = reply_tweet_file.user_id
-        WHERE tweet.twitter_id != reply_tweet.twitter_id AND user.twitter_id = ? AND user.screen_name like ?;
+        WHERE tweet.twitter_id != reply_tweet.twitter_id AND user.twitter_id = ? AND user.screen_name like ?
+        ORDER BY 1, 2, 3, 4
+        LIMIT ? OFFSET ?;
 ";
 
 const GET_REPLIES_TO: &str = "
@@ -100,14 +125,67 @@ const GET_MOST_COMMON_SCREEN_NAME: &str = "
 ";
 
 const GET_USER_TWEETS: &str = "
-    SELECT DISTINCT tweet.twitter_id, tweet.ts / 1000 AS ts, user_twitter_id, screen_name, content
+    SELECT DISTINCT tweet.twitter_id, tweet.ts, user_twitter_id, screen_name, content
         FROM tweet
         JOIN tweet_file ON tweet_id = tweet.id
         JOIN user ON user.id = user_id
         WHERE user_twitter_id = ?
-        AND tweet.ts >= ? and tweet.ts <= ?;
+        AND tweet.ts >= ? and tweet.ts <= ?
+        ORDER BY 1, 2, 4, 5
+        LIMIT ? OFFSET ?;
+";
+
+const GET_ALL_TWEET_FILES: &str = "
+    SELECT tweet.twitter_id, parent_twitter_id, ts, user_twitter_id, screen_name, name, content, digest
+        FROM tweet
+        JOIN tweet_file ON tweet_file.tweet_id = tweet.id
+        JOIN file ON file.id = tweet_file.file_id
+        JOIN user on user.id = tweet_file.user_id
+        ORDER BY tweet.twitter_id, digest;
+";
+
+const GET_DUPLICATE_CANDIDATES: &str = "
+    SELECT tweet.id, tweet.twitter_id, tweet.user_twitter_id, tweet.ts, tweet.content
+        FROM tweet
+        JOIN (
+            SELECT twitter_id, user_twitter_id, ts
+                FROM tweet
+                GROUP BY twitter_id, user_twitter_id, ts
+                HAVING COUNT(*) > 1
+        ) AS duplicate
+        ON duplicate.twitter_id = tweet.twitter_id
+            AND duplicate.user_twitter_id = tweet.user_twitter_id
+            AND duplicate.ts = tweet.ts
+        ORDER BY tweet.id;
+";
+
+const GET_VARIANT_TWEET_IDS: &str = "
+    SELECT twitter_id
+        FROM tweet
+        GROUP BY twitter_id
+        HAVING COUNT(DISTINCT content) > 1
+        ORDER BY twitter_id;
+";
+
+const GET_ALL_TWEETS: &str = "
+    SELECT twitter_id, parent_twitter_id, ts, user_twitter_id, content
+        FROM tweet
+        ORDER BY twitter_id, id;
 ";
 
+const SCHEMA: &str = include_str!("../../../schemas/tweet.sql");
+
+const SCHEMA_VERSION_CREATE: &str =
+    "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)";
+
+/// Schema changes, in order, where step `i` upgrades a version `i + 1` database.
+///
+/// The schema file always describes the latest version, so any change there needs a step here.
+const MIGRATIONS: &[&str] = &["ALTER TABLE file ADD COLUMN url TEXT NULL"];
+
+/// The version of the schema described by the schema file.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64 + 1;
+
 pub type TweetStoreResult<T> = Result<T, TweetStoreError>;
 
 #[derive(thiserror::Error, Debug)]
@@ -116,6 +194,31 @@ pub enum TweetStoreError {
     FileMissing(#[from] std::io::Error),
     #[error("SQLite error for TweetStore")]
     DbFailure(#[from] rusqlite::Error),
+    #[error("JSON error for TweetStore")]
+    JsonFailure(#[from] serde_json::Error),
+    #[error("Unsupported schema version for TweetStore: {0}")]
+    UnsupportedSchemaVersion(i64),
+}
+
+/// A reply from one user to another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ConversationEdge {
+    pub parent_id: u64,
+    pub parent_ts: DateTime<Utc>,
+    pub parent_user_id: u64,
+    pub reply_id: u64,
+    pub reply_ts: DateTime<Utc>,
+    pub reply_user_id: u64,
+}
+
+/// A row of the tweet table (there may be several for a tweet with multiple versions).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TweetRow {
+    pub id: u64,
+    pub parent_id: Option<u64>,
+    pub ts: DateTime<Utc>,
+    pub user_id: u64,
+    pub text: String,
 }
 
 #[derive(Debug, Eq, PartialEq)]
@@ -155,6 +258,168 @@ impl PartialOrd for UserRecord {
     }
 }
 
+/// A single tweet as found in a single file, with user and file information.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct TweetExport {
+    pub id: u64,
+    pub parent_id: Option<u64>,
+    /// Epoch second.
+    pub ts: i64,
+    pub user_id: u64,
+    pub screen_name: String,
+    pub name: String,
+    pub text: String,
+    pub digest: String,
+}
+
+/// Text normalization steps applied to tweets when they are stored.
+///
+/// By default no normalization is performed and text is stored as it was found.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct NormalizeOptions {
+    /// Collapse runs of spaces and tabs into a single space and trim the text.
+    pub collapse_spaces: bool,
+    /// Convert `\r\n` and `\r` line endings to `\n`.
+    pub normalize_newlines: bool,
+    /// Apply Unicode NFC normalization.
+    pub unicode_nfc: bool,
+}
+
+impl NormalizeOptions {
+    pub fn normalize(&self, text: &str) -> String {
+        let mut result = if self.normalize_newlines {
+            text.replace("\r\n", "\n").replace('\r', "\n")
+        } else {
+            text.to_string()
+        };
+
+        if self.collapse_spaces {
+            result = result
+                .split('\n')
+                .map(|line| {
+                    line.split([' ', '\t'])
+                        .filter(|part| !part.is_empty())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+                .trim()
+                .to_string();
+        }
+
+        if self.unicode_nfc {
+            result = result.nfc().collect();
+        }
+
+        result
+    }
+}
+
+/// A range of results for queries that may return many rows.
+///
+/// The default is all rows.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Page {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Page {
+    pub fn new(limit: usize, offset: usize) -> Page {
+        Page {
+            limit: Some(limit),
+            offset,
+        }
+    }
+
+    /// SQLite treats a negative limit as no limit.
+    fn limit_param(&self) -> i64 {
+        self.limit.map(|limit| limit as i64).unwrap_or(-1)
+    }
+
+    fn offset_param(&self) -> i64 {
+        self.offset as i64
+    }
+}
+
+/// Summary of a [`TweetStore::compact`] run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct CompactReport {
+    /// Sets of near-duplicate rows that were merged.
+    pub groups: usize,
+    /// Redundant tweet rows that were deleted.
+    pub removed: usize,
+}
+
+/// Summary of a [`TweetStore::delete_user`] run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DeleteReport {
+    /// Tweet rows posted by the user.
+    pub tweets: usize,
+    /// Links between the user's tweets and files.
+    pub tweet_files: usize,
+    /// Files that no longer contained any tweets.
+    pub files: usize,
+    /// Rows for the user's screen names and display names.
+    pub users: usize,
+}
+
+/// The difference between two consecutive archived versions of a tweet's text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VersionDiff {
+    pub from_digest: String,
+    pub from_archived_at: Option<NaiveDateTime>,
+    pub to_digest: String,
+    pub to_archived_at: Option<NaiveDateTime>,
+    pub segments: Vec<DiffSegment>,
+}
+
+impl VersionDiff {
+    pub fn added(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                DiffSegment::Added(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn removed(&self) -> Vec<&str> {
+        self.segments
+            .iter()
+            .filter_map(|segment| match segment {
+                DiffSegment::Removed(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Determine whether a set of archived versions of a tweet indicates an edit.
+///
+/// Two versions are considered different if their text differs after whitespace normalization,
+/// and differences between versions archived within `min_gap` of each other are ignored as
+/// archival noise.
+pub fn is_edited(versions: &[(NaiveDateTime, &str)], min_gap: chrono::Duration) -> bool {
+    let normalized = versions
+        .iter()
+        .map(|(archived_at, text)| {
+            (
+                *archived_at,
+                text.split_whitespace().collect::<Vec<_>>().join(" "),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    normalized.iter().enumerate().any(|(i, (a_time, a_text))| {
+        normalized[i + 1..].iter().any(|(b_time, b_text)| {
+            a_text != b_text && (*a_time - *b_time).num_seconds().abs() > min_gap.num_seconds()
+        })
+    })
+}
+
 #[derive(Clone)]
 pub struct TweetStore {
     connection: RwLock<Connection>,
@@ -162,8 +427,18 @@ pub struct TweetStore {
 
 impl TweetStore {
     pub fn new<P: AsRef<Path>>(path: P, recreate: bool) -> TweetStoreResult<TweetStore> {
+        Self::with_options(path, recreate, ConnectionOptions::default())
+    }
+
+    /// Open a store with the given connection settings (`new` uses the defaults).
+    pub fn with_options<P: AsRef<Path>>(
+        path: P,
+        recreate: bool,
+        options: ConnectionOptions,
+    ) -> TweetStoreResult<TweetStore> {
         let exists = path.as_ref().is_file();
         let mut connection = Connection::open(path)?;
+        options.apply(&connection)?;
 
         if exists {
             if recreate {
@@ -172,13 +447,16 @@ impl TweetStore {
                 tx.execute("DROP TABLE IF EXISTS user", [])?;
                 tx.execute("DROP TABLE IF EXISTS file", [])?;
                 tx.execute("DROP TABLE IF EXISTS tweet_file", [])?;
-                let schema = Self::load_schema()?;
-                tx.execute_batch(&schema)?;
+                tx.execute("DROP TABLE IF EXISTS schema_version", [])?;
+                Self::create_schema(&tx)?;
                 tx.commit()?;
+            } else {
+                Self::migrate(&mut connection)?;
             }
         } else {
-            let schema = Self::load_schema()?;
-            connection.execute_batch(&schema)?;
+            let tx = connection.transaction()?;
+            Self::create_schema(&tx)?;
+            tx.commit()?;
         }
 
         Ok(TweetStore {
@@ -186,6 +464,113 @@ impl TweetStore {
         })
     }
 
+    /// Open a new store in memory (mostly useful for testing).
+    pub fn in_memory() -> TweetStoreResult<TweetStore> {
+        let mut connection = Connection::open_in_memory()?;
+        let tx = connection.transaction()?;
+        Self::create_schema(&tx)?;
+        tx.commit()?;
+
+        Ok(TweetStore {
+            connection: RwLock::new(connection),
+        })
+    }
+
+    /// Open an existing store without write access (writes fail with a SQLite error).
+    ///
+    /// The schema isn't migrated, so this is only useful for databases created by this version.
+    pub fn open_readonly<P: AsRef<Path>>(path: P) -> TweetStoreResult<TweetStore> {
+        let connection = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        connection.busy_timeout(ConnectionOptions::default().busy_timeout)?;
+
+        Ok(TweetStore {
+            connection: RwLock::new(connection),
+        })
+    }
+
+    fn create_schema(tx: &Transaction) -> TweetStoreResult<()> {
+        tx.execute_batch(SCHEMA)?;
+        tx.execute(SCHEMA_VERSION_CREATE, [])?;
+        Self::set_schema_version(tx, SCHEMA_VERSION)
+    }
+
+    fn set_schema_version(tx: &Transaction, version: i64) -> TweetStoreResult<()> {
+        tx.execute("DELETE FROM schema_version", [])?;
+        tx.execute(
+            "INSERT INTO schema_version (version) VALUES (?)",
+            params![version],
+        )?;
+        Ok(())
+    }
+
+    /// Get the version of an existing database.
+    ///
+    /// Databases created before the version table was added are identified by their columns.
+    fn schema_version(connection: &Connection) -> TweetStoreResult<i64> {
+        connection.execute(SCHEMA_VERSION_CREATE, [])?;
+
+        let version =
+            connection.query_row("SELECT MAX(version) FROM schema_version", [], |row| {
+                row.get::<usize, Option<i64>>(0)
+            })?;
+
+        match version {
+            Some(version) => Ok(version),
+            None => {
+                let mut select =
+                    connection.prepare("SELECT name FROM pragma_table_info('file')")?;
+                let columns = select
+                    .query_map(params![], |row| row.get::<usize, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(if columns.iter().any(|column| column == "url") {
+                    2
+                } else {
+                    1
+                })
+            }
+        }
+    }
+
+    /// Update a database created with an older version of the schema.
+    ///
+    /// An existing file without the schema (e.g. an empty file) is initialized instead.
+    fn migrate(connection: &mut Connection) -> TweetStoreResult<()> {
+        let tx = connection.transaction()?;
+
+        let has_file_table = tx.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'file'",
+            [],
+            |row| row.get::<usize, i64>(0),
+        )? > 0;
+
+        if !has_file_table {
+            Self::create_schema(&tx)?;
+            tx.commit()?;
+
+            return Ok(());
+        }
+
+        let version = Self::schema_version(&tx)?;
+
+        if !(1..=SCHEMA_VERSION).contains(&version) {
+            return Err(TweetStoreError::UnsupportedSchemaVersion(version));
+        }
+
+        for (i, step) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+            log::info!("Migrating TweetStore to schema version {}", i + 2);
+            tx.execute_batch(step)?;
+        }
+
+        Self::set_schema_version(&tx, SCHEMA_VERSION)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
     pub async fn check_digest(&self, digest: &str) -> TweetStoreResult<Option<i64>> {
         let connection = self.connection.read().await;
         let mut select = connection.prepare_cached(FILE_SELECT)?;
@@ -199,64 +584,67 @@ impl TweetStore {
         &self,
         digest: &str,
         primary_twitter_id: Option<u64>,
+        url: Option<&str>,
         tweets: &[BrowserTweet],
+        normalize: &NormalizeOptions,
     ) -> TweetStoreResult<()> {
         let mut connection = self.connection.write().await;
-        let mut tx = connection.transaction()?;
-        tx.set_drop_behavior(DropBehavior::Commit);
-
-        let mut insert_file = tx.prepare_cached(FILE_INSERT)?;
-        insert_file.execute(params![digest, primary_twitter_id.map(SQLiteId)])?;
-        let file_id = tx.last_insert_rowid();
-
-        let mut select_tweet = tx.prepare_cached(TWEET_SELECT_FULL)?;
-        let mut insert_tweet = tx.prepare_cached(TWEET_INSERT)?;
-        let mut insert_tweet_file = tx.prepare_cached(TWEET_FILE_INSERT)?;
-
-        for tweet in tweets {
-            let user_id = Self::add_user(
-                &tx,
-                tweet.user_id,
-                &tweet.user_screen_name,
-                &tweet.user_name,
-            )?;
+        // The transaction is rolled back on drop, so a failure doesn't leave a partial file.
+        let tx = connection.transaction()?;
 
-            let existing_id: Option<i64> = select_tweet
-                .query_row(
-                    params![
-                        SQLiteId(tweet.id),
-                        SQLiteId(tweet.parent_id.unwrap_or(tweet.id)),
-                        SQLiteDateTime(tweet.time),
-                        SQLiteId(tweet.user_id),
-                        tweet.text
-                    ],
-                    |row| row.get(0),
-                )
-                .optional()?;
-
-            let tweet_id = match existing_id {
-                None => {
-                    insert_tweet.execute(params![
-                        SQLiteId(tweet.id),
-                        SQLiteId(tweet.parent_id.unwrap_or(tweet.id)),
-                        SQLiteDateTime(tweet.time),
-                        SQLiteId(tweet.user_id),
-                        tweet.text
-                    ])?;
-
-                    tx.last_insert_rowid()
-                }
-                Some(id) => id,
-            };
+        {
+            let mut insert_file = tx.prepare_cached(FILE_INSERT)?;
+            insert_file.execute(params![digest, primary_twitter_id.map(SQLiteId), url])?;
+            let file_id = tx.last_insert_rowid();
+
+            let mut select_tweet = tx.prepare_cached(TWEET_SELECT_FULL)?;
+            let mut insert_tweet = tx.prepare_cached(TWEET_INSERT)?;
+            let mut insert_tweet_file = tx.prepare_cached(TWEET_FILE_INSERT)?;
+
+            for tweet in tweets {
+                let user_id = Self::add_user(
+                    &tx,
+                    tweet.user_id,
+                    &tweet.user_screen_name,
+                    &tweet.user_name,
+                )?;
+                let text = normalize.normalize(&tweet.text);
+
+                let existing_id: Option<i64> = select_tweet
+                    .query_row(
+                        params![
+                            SQLiteId(tweet.id),
+                            SQLiteId(tweet.parent_id.unwrap_or(tweet.id)),
+                            SQLiteDateTime(tweet.time),
+                            SQLiteId(tweet.user_id),
+                            text
+                        ],
+                        |row| row.get(0),
+                    )
+                    .optional()?;
+
+                let tweet_id = match existing_id {
+                    None => {
+                        insert_tweet.execute(params![
+                            SQLiteId(tweet.id),
+                            SQLiteId(tweet.parent_id.unwrap_or(tweet.id)),
+                            SQLiteDateTime(tweet.time),
+                            SQLiteId(tweet.user_id),
+                            text
+                        ])?;
+
+                        tx.last_insert_rowid()
+                    }
+                    Some(id) => id,
+                };
 
-            insert_tweet_file.execute(params![tweet_id, file_id, user_id])?;
+                insert_tweet_file.execute(params![tweet_id, file_id, user_id])?;
+            }
         }
 
-        Ok(())
-    }
+        tx.commit()?;
 
-    fn load_schema() -> std::io::Result<String> {
-        std::fs::read_to_string("schemas/tweet.sql")
+        Ok(())
     }
 
     fn add_user(
@@ -292,9 +680,9 @@ impl TweetStore {
 
         for id in status_ids {
             match select.query_row(params![SQLiteId(*id)], |row| {
-                let parent_twitter_id = row.get::<usize, i64>(0)? as u64;
+                let parent_twitter_id = row.get::<usize, SQLiteId>(0)?.0;
                 let ts: SQLiteDateTime = row.get(1)?;
-                let user_twitter_id = row.get::<usize, i64>(2)? as u64;
+                let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
                 let screen_name: String = row.get(3)?;
                 let name: String = row.get(4)?;
                 let content: String = row.get(5)?;
@@ -335,9 +723,9 @@ impl TweetStore {
 
         for id in status_ids {
             match select.query_and_then(params![SQLiteId(*id)], |row| {
-                let parent_twitter_id = row.get::<usize, i64>(0)? as u64;
+                let parent_twitter_id = row.get::<usize, SQLiteId>(0)?.0;
                 let ts: SQLiteDateTime = row.get(1)?;
-                let user_twitter_id = row.get::<usize, i64>(2)? as u64;
+                let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
                 let screen_name: String = row.get(3)?;
                 let name: String = row.get(4)?;
                 let content: String = row.get(5)?;
@@ -374,40 +762,145 @@ impl TweetStore {
         Ok(result)
     }
 
+    /// List the digest and source URL (if known) of every file the tweet was found in.
+    pub async fn files_for_tweet(
+        &self,
+        status_id: u64,
+    ) -> TweetStoreResult<Vec<(String, Option<String>)>> {
+        let connection = self.connection.read().await;
+        let mut select = connection.prepare_cached(FILE_SELECT_BY_TWEET_ID)?;
+
+        let files = select
+            .query_map(params![SQLiteId(status_id)], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(files)
+    }
+
+    /// Compare the distinct archived versions of a tweet's text.
+    ///
+    /// The database doesn't record when files were archived, so the caller provides the archive
+    /// time for each digest. Versions are ordered by this time (with unknown times first), and
+    /// a diff is returned for each pair of consecutive versions whose text differs.
+    pub async fn diff_versions<F: Fn(&str) -> Option<NaiveDateTime>>(
+        &self,
+        status_id: u64,
+        archived_at: F,
+    ) -> TweetStoreResult<Vec<VersionDiff>> {
+        let mut versions = self
+            .get_multi_tweets(&[status_id])
+            .await?
+            .into_iter()
+            .map(|(tweet, digest)| (archived_at(&digest), digest, tweet.text))
+            .collect::<Vec<_>>();
+
+        versions.sort();
+        versions.dedup_by(|next, previous| next.2 == previous.2);
+
+        Ok(versions
+            .windows(2)
+            .map(|pair| VersionDiff {
+                from_digest: pair[0].1.clone(),
+                from_archived_at: pair[0].0,
+                to_digest: pair[1].1.clone(),
+                to_archived_at: pair[1].0,
+                segments: word_diff(&pair[0].2, &pair[1].2),
+            })
+            .collect())
+    }
+
+    /// List status IDs whose archived versions indicate that the tweet was edited.
+    ///
+    /// Versions with an unknown archive time are ignored (see [`is_edited`]).
+    pub async fn find_edited<F: Fn(&str) -> Option<NaiveDateTime>>(
+        &self,
+        min_gap: chrono::Duration,
+        archived_at: F,
+    ) -> TweetStoreResult<Vec<u64>> {
+        let candidates = {
+            let connection = self.connection.read().await;
+            let mut select = connection.prepare_cached(GET_VARIANT_TWEET_IDS)?;
+            let candidates = select
+                .query_map(params![], |row| Ok(row.get::<usize, SQLiteId>(0)?.0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            candidates
+        };
+
+        let mut timed_versions = HashMap::<u64, Vec<(NaiveDateTime, String)>>::new();
+
+        for (tweet, digest) in self.get_multi_tweets(&candidates).await? {
+            if let Some(time) = archived_at(&digest) {
+                timed_versions
+                    .entry(tweet.id)
+                    .or_default()
+                    .push((time, tweet.text));
+            }
+        }
+
+        Ok(candidates
+            .into_iter()
+            .filter(|status_id| match timed_versions.get(status_id) {
+                Some(versions) => {
+                    let versions = versions
+                        .iter()
+                        .map(|(time, text)| (*time, text.as_str()))
+                        .collect::<Vec<_>>();
+
+                    is_edited(&versions, min_gap)
+                }
+                None => false,
+            })
+            .collect())
+    }
+
+    /// Get the distinct replies to a user's tweets, ordered by parent status ID.
     pub async fn get_replies(
         &self,
         twitter_id: u64,
         screen_name: &str,
+        page: Page,
     ) -> TweetStoreResult<Vec<(u64, u64, u64, String)>> {
         let connection = self.connection.read().await;
         let mut select = connection.prepare_cached(GET_REPLIES)?;
 
-        let mut result: Vec<_> = select
-            .query_and_then(params![SQLiteId(twitter_id), screen_name], |row| {
-                let parent_twitter_id = row.get::<usize, i64>(0)? as u64;
-                let status_twitter_id = row.get::<usize, i64>(1)? as u64;
-                let user_twitter_id = row.get::<usize, i64>(2)? as u64;
-                let screen_name: String = row.get(3)?;
-
-                Ok((
-                    parent_twitter_id,
-                    status_twitter_id,
-                    user_twitter_id,
+        let result = select
+            .query_and_then(
+                params![
+                    SQLiteId(twitter_id),
                     screen_name,
-                ))
-            })?
+                    page.limit_param(),
+                    page.offset_param()
+                ],
+                |row| {
+                    let parent_twitter_id = row.get::<usize, SQLiteId>(0)?.0;
+                    let status_twitter_id = row.get::<usize, SQLiteId>(1)?.0;
+                    let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
+                    let screen_name: String = row.get(3)?;
+
+                    Ok((
+                        parent_twitter_id,
+                        status_twitter_id,
+                        user_twitter_id,
+                        screen_name,
+                    ))
+                },
+            )?
             .collect::<Result<Vec<_>, rusqlite::Error>>()?;
 
-        result.sort();
-        result.dedup();
-
         Ok(result)
     }
 
-    pub async fn for_each_interaction<F: Fn((u64, u64, u64, String), (u64, u64, u64, String))>(
+    /// Call the given function on each reply to or from the given user.
+    ///
+    /// The second component of each tuple is a timestamp in epoch milliseconds.
+    pub async fn for_each_interaction<
+        F: FnMut((u64, u64, u64, String), (u64, u64, u64, String)),
+    >(
         &self,
         twitter_id: u64,
-        f: F,
+        mut f: F,
     ) -> TweetStoreResult<()> {
         let connection = self.connection.read().await;
         let mut select = connection.prepare_cached(GET_REPLIES_TO)?;
@@ -415,19 +908,19 @@ impl TweetStore {
         let mut seen = std::collections::HashSet::<u64>::new();
 
         let results = select.query_and_then(params![SQLiteId(twitter_id)], |row| {
-            let reply_twitter_id = row.get::<usize, i64>(3)? as u64;
+            let reply_twitter_id = row.get::<usize, SQLiteId>(3)?.0;
 
             let result: TweetStoreResult<_> = if seen.contains(&reply_twitter_id) {
                 Ok(None)
             } else {
                 seen.insert(reply_twitter_id);
-                let twitter_id = row.get::<usize, i64>(0)? as u64;
-                let twitter_ts = row.get::<usize, i64>(1)? as u64;
-                let user_twitter_id = row.get::<usize, i64>(2)? as u64;
+                let twitter_id = row.get::<usize, SQLiteId>(0)?.0;
+                let twitter_ts = row.get::<usize, SQLiteDateTime>(1)?.millis() as u64;
+                let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
                 //let screen_name: String = row.get(3)?;
 
-                let reply_twitter_ts = row.get::<usize, i64>(4)? as u64;
-                let reply_user_twitter_id = row.get::<usize, i64>(5)? as u64;
+                let reply_twitter_ts = row.get::<usize, SQLiteDateTime>(4)?.millis() as u64;
+                let reply_user_twitter_id = row.get::<usize, SQLiteId>(5)?.0;
                 //let reply_screen_name: String = row.get(7)?;
 
                 Ok(Some((
@@ -453,19 +946,19 @@ impl TweetStore {
         let mut select = connection.prepare_cached(GET_REPLIES_FROM)?;
 
         let results = select.query_and_then(params![SQLiteId(twitter_id)], |row| {
-            let reply_twitter_id = row.get::<usize, i64>(3)? as u64;
+            let reply_twitter_id = row.get::<usize, SQLiteId>(3)?.0;
 
             let result: TweetStoreResult<_> = if seen.contains(&reply_twitter_id) {
                 Ok(None)
             } else {
                 seen.insert(reply_twitter_id);
-                let twitter_id = row.get::<usize, i64>(0)? as u64;
-                let twitter_ts = row.get::<usize, i64>(1)? as u64;
-                let user_twitter_id = row.get::<usize, i64>(2)? as u64;
+                let twitter_id = row.get::<usize, SQLiteId>(0)?.0;
+                let twitter_ts = row.get::<usize, SQLiteDateTime>(1)?.millis() as u64;
+                let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
                 //let screen_name: String = row.get(3)?;
 
-                let reply_twitter_ts = row.get::<usize, i64>(4)? as u64;
-                let reply_user_twitter_id = row.get::<usize, i64>(5)? as u64;
+                let reply_twitter_ts = row.get::<usize, SQLiteDateTime>(4)?.millis() as u64;
+                let reply_user_twitter_id = row.get::<usize, SQLiteId>(5)?.0;
                 //let reply_screen_name: String = row.get(7)?;
 
                 Ok(Some((
@@ -491,22 +984,93 @@ impl TweetStore {
         Ok(())
     }
 
+    /// Get the replies from either of two users to the other, ordered by the time of the reply.
+    pub async fn conversations_between(
+        &self,
+        user_a: u64,
+        user_b: u64,
+    ) -> TweetStoreResult<Vec<ConversationEdge>> {
+        let mut edges = vec![];
+
+        self.for_each_interaction(
+            user_a,
+            |(parent_id, parent_ts, parent_user_id, _), (reply_id, reply_ts, reply_user_id, _)| {
+                if (parent_user_id == user_a && reply_user_id == user_b)
+                    || (parent_user_id == user_b && reply_user_id == user_a)
+                {
+                    // These timestamps were read from valid dates, so conversion can't fail.
+                    edges.push(ConversationEdge {
+                        parent_id,
+                        parent_ts: Utc.timestamp_millis_opt(parent_ts as i64).unwrap(),
+                        parent_user_id,
+                        reply_id,
+                        reply_ts: Utc.timestamp_millis_opt(reply_ts as i64).unwrap(),
+                        reply_user_id,
+                    });
+                }
+            },
+        )
+        .await?;
+
+        edges.sort_by_key(|edge| (edge.reply_ts, edge.reply_id));
+
+        Ok(edges)
+    }
+
+    /// Call the given function on every row of the tweet table, ordered by status ID.
+    ///
+    /// Rows are read one at a time, so this can be used for full scans of large databases. Returns
+    /// the number of rows.
+    pub async fn for_each_tweet<F: FnMut(TweetRow)>(&self, mut f: F) -> TweetStoreResult<usize> {
+        let connection = self.connection.read().await;
+        let mut select = connection.prepare_cached(GET_ALL_TWEETS)?;
+        let mut rows = select.query(params![])?;
+        let mut count = 0;
+
+        while let Some(row) = rows.next()? {
+            let id = row.get::<usize, SQLiteId>(0)?.0;
+            let parent_id = row
+                .get::<usize, Option<SQLiteId>>(1)?
+                .map(|parent_id| parent_id.0)
+                .filter(|parent_id| *parent_id != id);
+
+            f(TweetRow {
+                id,
+                parent_id,
+                ts: row.get::<usize, SQLiteDateTime>(2)?.0,
+                user_id: row.get::<usize, SQLiteId>(3)?.0,
+                text: row.get(4)?,
+            });
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Get a user's tweets posted in the given (inclusive) range, ordered by status ID.
     pub async fn get_tweets_for_user(
         &self,
         user_id: u64,
-        start_ts: u64,
-        end_ts: u64,
-    ) -> TweetStoreResult<Vec<(u64, u64, u64, String, String)>> {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        page: Page,
+    ) -> TweetStoreResult<Vec<(u64, DateTime<Utc>, u64, String, String)>> {
         let connection = self.connection.read().await;
         let mut stmt = connection.prepare_cached(GET_USER_TWEETS)?;
 
         let tweets = stmt
             .query_map(
-                params![SQLiteId(user_id), SQLiteId(start_ts), SQLiteId(end_ts)],
+                params![
+                    SQLiteId(user_id),
+                    SQLiteDateTime(start),
+                    SQLiteDateTime(end),
+                    page.limit_param(),
+                    page.offset_param()
+                ],
                 |row| {
-                    let twitter_id = row.get::<usize, i64>(0)? as u64;
-                    let ts = row.get::<usize, i64>(1)? as u64;
-                    let user_twitter_id = row.get::<usize, i64>(2)? as u64;
+                    let twitter_id = row.get::<usize, SQLiteId>(0)?.0;
+                    let ts = row.get::<usize, SQLiteDateTime>(1)?.0;
+                    let user_twitter_id = row.get::<usize, SQLiteId>(2)?.0;
                     let screen_name = row.get(3)?;
                     let contents = row.get(4)?;
                     Ok((twitter_id, ts, user_twitter_id, screen_name, contents))
@@ -613,18 +1177,200 @@ impl TweetStore {
         Ok(result)
     }
 
+    /// List every screen name observed for a user, with the times of the first and last tweets
+    /// seen under it (in order of first appearance).
+    ///
+    /// The timestamps are tweet creation times, since the database doesn't record archive times.
+    pub async fn screen_name_history(
+        &self,
+        user_id: u64,
+    ) -> TweetStoreResult<Vec<(String, DateTime<Utc>, DateTime<Utc>)>> {
+        let connection = self.connection.read().await;
+        let mut select = connection.prepare_cached(GET_SCREEN_NAME_HISTORY)?;
+
+        let history = select
+            .query_map(params![SQLiteId(user_id)], |row| {
+                let screen_name: String = row.get(0)?;
+                let first: SQLiteDateTime = row.get(1)?;
+                let last: SQLiteDateTime = row.get(2)?;
+
+                Ok((screen_name, first.0, last.0))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(history)
+    }
+
     pub async fn tweet_ids_by_user_id(&self, user_id: u64) -> TweetStoreResult<Vec<u64>> {
         let connection = self.connection.read().await;
         let mut select = connection
             .prepare_cached("SELECT tweet.twitter_id FROM tweet WHERE user_twitter_id = ?")?;
 
         let result = select
-            .query_map(params![user_id], |row| Ok(row.get::<usize, i64>(0)? as u64))?
+            .query_map(params![SQLiteId(user_id)], |row| {
+                Ok(row.get::<usize, SQLiteId>(0)?.0)
+            })?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(result)
     }
 
+    /// Merge tweet rows that differ only in whitespace.
+    ///
+    /// Rows with the same status ID, user, and timestamp whose text is identical after whitespace
+    /// normalization are collapsed into the row with the longest text, and file links to the
+    /// redundant rows are moved to this canonical row.
+    pub async fn compact(&self) -> TweetStoreResult<CompactReport> {
+        let mut connection = self.connection.write().await;
+        let tx = connection.transaction()?;
+        let mut groups = HashMap::<(u64, u64, i64, String), Vec<(i64, String)>>::new();
+
+        {
+            let mut select = tx.prepare(GET_DUPLICATE_CANDIDATES)?;
+            let rows = select.query_map(params![], |row| {
+                Ok((
+                    row.get::<usize, i64>(0)?,
+                    row.get::<usize, SQLiteId>(1)?.0,
+                    row.get::<usize, SQLiteId>(2)?.0,
+                    row.get::<usize, i64>(3)?,
+                    row.get::<usize, String>(4)?,
+                ))
+            })?;
+
+            for row in rows {
+                let (id, twitter_id, user_twitter_id, ts, content) = row?;
+                let normalized = content.split_whitespace().collect::<Vec<_>>().join(" ");
+
+                groups
+                    .entry((twitter_id, user_twitter_id, ts, normalized))
+                    .or_default()
+                    .push((id, content));
+            }
+        }
+
+        let mut report = CompactReport::default();
+
+        {
+            let mut update = tx.prepare("UPDATE tweet_file SET tweet_id = ? WHERE tweet_id = ?")?;
+            let mut delete = tx.prepare("DELETE FROM tweet WHERE id = ?")?;
+
+            for rows in groups.values().filter(|rows| rows.len() > 1) {
+                // Rows are in ID order, so ties are resolved in favor of the oldest row.
+                let canonical_id = rows
+                    .iter()
+                    .rev()
+                    .max_by_key(|(_, content)| content.len())
+                    .map(|(id, _)| *id)
+                    .unwrap();
+
+                for (id, _) in rows.iter().filter(|(id, _)| *id != canonical_id) {
+                    update.execute(params![canonical_id, id])?;
+                    delete.execute(params![id])?;
+                    report.removed += 1;
+                }
+
+                report.groups += 1;
+            }
+        }
+
+        tx.commit()?;
+
+        Ok(report)
+    }
+
+    /// Remove all data for the given user.
+    ///
+    /// Files are shared between users, so a file is only removed if all of the tweets linked to
+    /// it belonged to this user (files that never had any tweets are left alone).
+    pub async fn delete_user(&self, user_id: u64) -> TweetStoreResult<DeleteReport> {
+        let mut connection = self.connection.write().await;
+        let tx = connection.transaction()?;
+        let mut report = DeleteReport::default();
+
+        let file_ids = {
+            let mut select = tx.prepare(
+                "SELECT DISTINCT tweet_file.file_id
+                    FROM tweet_file
+                    JOIN tweet ON tweet.id = tweet_file.tweet_id
+                    WHERE tweet.user_twitter_id = ?",
+            )?;
+            let file_ids = select
+                .query_map(params![SQLiteId(user_id)], |row| row.get::<usize, i64>(0))?
+                .collect::<Result<Vec<_>, _>>()?;
+            file_ids
+        };
+
+        report.tweet_files = tx.execute(
+            "DELETE FROM tweet_file
+                WHERE tweet_id IN (SELECT id FROM tweet WHERE user_twitter_id = ?)",
+            params![SQLiteId(user_id)],
+        )?;
+        report.tweets = tx.execute(
+            "DELETE FROM tweet WHERE user_twitter_id = ?",
+            params![SQLiteId(user_id)],
+        )?;
+
+        {
+            let mut delete = tx.prepare(
+                "DELETE FROM file
+                    WHERE id = ?1 AND NOT EXISTS (SELECT 1 FROM tweet_file WHERE file_id = ?1)",
+            )?;
+
+            for file_id in file_ids {
+                report.files += delete.execute(params![file_id])?;
+            }
+        }
+
+        report.users = tx.execute(
+            "DELETE FROM user
+                WHERE twitter_id = ? AND id NOT IN (SELECT user_id FROM tweet_file)",
+            params![SQLiteId(user_id)],
+        )?;
+
+        tx.commit()?;
+
+        Ok(report)
+    }
+
+    /// Write every tweet (one row per tweet and file) as newline-delimited JSON.
+    ///
+    /// Rows are written as they are read from the database, and the number of rows is returned.
+    pub async fn export_json<W: Write>(&self, mut writer: W) -> TweetStoreResult<usize> {
+        let connection = self.connection.read().await;
+        let mut select = connection.prepare_cached(GET_ALL_TWEET_FILES)?;
+        let mut rows = select.query(params![])?;
+        let mut count = 0;
+
+        while let Some(row) = rows.next()? {
+            let id = row.get::<usize, SQLiteId>(0)?.0;
+            let parent_id = row.get::<usize, SQLiteId>(1)?.0;
+            let ts: SQLiteDateTime = row.get(2)?;
+
+            let export = TweetExport {
+                id,
+                parent_id: if parent_id == id {
+                    None
+                } else {
+                    Some(parent_id)
+                },
+                ts: ts.seconds(),
+                user_id: row.get::<usize, SQLiteId>(3)?.0,
+                screen_name: row.get(4)?,
+                name: row.get(5)?,
+                text: row.get(6)?,
+                digest: row.get(7)?,
+            };
+
+            serde_json::to_writer(&mut writer, &export)?;
+            writeln!(writer)?;
+            count += 1;
+        }
+
+        writer.flush()?;
+
+        Ok(count)
+    }
+
     pub async fn check_linkable(&self, digests_path: &str) -> TweetStoreResult<()> {
         use std::io::{self, BufRead};
         let file = std::fs::File::open(digests_path)?;
@@ -642,7 +1388,7 @@ impl TweetStore {
 
         select
             .query_map(params![], |row| {
-                let twitter_id = row.get::<usize, i64>(0)? as u64;
+                let twitter_id = row.get::<usize, SQLiteId>(0)?.0;
                 let digest: String = row.get(1)?;
 
                 if twitter_id != current_twitter_id {
@@ -682,3 +1428,1065 @@ impl TweetStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::TweetStore;
+    use crate::browser::twitter::parser::BrowserTweet;
+    use chrono::{Duration, NaiveDate, TimeZone, Utc};
+
+    fn example_tweet(text: &str) -> BrowserTweet {
+        BrowserTweet::new(
+            1302847271688523778,
+            None,
+            Utc.timestamp_millis_opt(1599457984000).unwrap(),
+            1051208286,
+            "ChiefScientist".to_string(),
+            "Ed Kmett".to_string(),
+            text.to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_diff_versions() {
+        let store = TweetStore::in_memory().unwrap();
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(1302847271688523778),
+                None,
+                &[example_tweet("This is a tweet.")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        store
+            .add_tweets(
+                "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB",
+                Some(1302847271688523778),
+                None,
+                &[example_tweet("This is a tweet. With another sentence.")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let first = NaiveDate::from_ymd_opt(2020, 9, 7)
+            .and_then(|date| date.and_hms_opt(5, 53, 4))
+            .unwrap();
+        let second = NaiveDate::from_ymd_opt(2020, 9, 8)
+            .and_then(|date| date.and_hms_opt(1, 0, 0))
+            .unwrap();
+
+        let diffs = store
+            .diff_versions(1302847271688523778, |digest| {
+                if digest.starts_with('A') {
+                    Some(first)
+                } else {
+                    Some(second)
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].from_digest, "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA");
+        assert_eq!(diffs[0].to_archived_at, Some(second));
+        assert_eq!(diffs[0].added(), vec!["With another sentence."]);
+        assert!(diffs[0].removed().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_files_for_tweet() {
+        let store = TweetStore::in_memory().unwrap();
+        let url = "https://twitter.com/ChiefScientist/status/1302847271688523778";
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(1302847271688523778),
+                Some(url),
+                &[example_tweet("This is a tweet.")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        store
+            .add_tweets(
+                "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB",
+                None,
+                None,
+                &[example_tweet("This is a tweet.")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            store.files_for_tweet(1302847271688523778).await.unwrap(),
+            vec![
+                (
+                    "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string(),
+                    Some(url.to_string())
+                ),
+                ("BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB".to_string(), None)
+            ]
+        );
+        assert!(store.files_for_tweet(1).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_export_json() {
+        let store = TweetStore::in_memory().unwrap();
+        let mut reply = example_tweet("@someone Hi.");
+        reply.id = 1302847271688523779;
+        reply.parent_id = Some(1302847271688523778);
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(1302847271688523778),
+                None,
+                &[example_tweet("This is a tweet."), reply],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let mut buffer = vec![];
+        let count = store.export_json(&mut buffer).await.unwrap();
+
+        let exports = String::from_utf8(buffer)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<super::TweetExport>(line).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            exports[0],
+            super::TweetExport {
+                id: 1302847271688523778,
+                parent_id: None,
+                ts: 1599457984,
+                user_id: 1051208286,
+                screen_name: "ChiefScientist".to_string(),
+                name: "Ed Kmett".to_string(),
+                text: "This is a tweet.".to_string(),
+                digest: "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".to_string(),
+            }
+        );
+        assert_eq!(exports[1].parent_id, Some(1302847271688523778));
+    }
+
+    #[tokio::test]
+    async fn test_large_ids() {
+        let store = TweetStore::in_memory().unwrap();
+        let mut tweet = example_tweet("This is a tweet.");
+        tweet.id = u64::MAX;
+        tweet.parent_id = Some(u64::MAX - 1);
+        tweet.user_id = u64::MAX - 2;
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(u64::MAX),
+                None,
+                &[tweet],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let stored = store.get_tweet(&[u64::MAX]).await.unwrap();
+
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].0.id, u64::MAX);
+        assert_eq!(stored[0].0.parent_id, Some(u64::MAX - 1));
+        assert_eq!(stored[0].0.user_id, u64::MAX - 2);
+
+        let mut buffer = vec![];
+        store.export_json(&mut buffer).await.unwrap();
+
+        let export = serde_json::from_slice::<super::TweetExport>(&buffer).unwrap();
+
+        assert_eq!(export.id, u64::MAX);
+        assert_eq!(export.parent_id, Some(u64::MAX - 1));
+        assert_eq!(export.user_id, u64::MAX - 2);
+    }
+
+    #[tokio::test]
+    async fn test_compact() {
+        let store = TweetStore::in_memory().unwrap();
+        let texts = [
+            "This is a tweet.",
+            "This is  a tweet.\n",
+            "This is a tweet.",
+            "This is a different tweet.",
+        ];
+
+        for (i, text) in texts.iter().enumerate() {
+            store
+                .add_tweets(
+                    &format!("{}AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", i),
+                    Some(1302847271688523778),
+                    None,
+                    &[example_tweet(text)],
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let count = |table: &str| {
+            let connection = futures::executor::block_on(store.connection.read());
+            connection
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get::<usize, i64>(0)
+                })
+                .unwrap()
+        };
+
+        assert_eq!(count("tweet"), 3);
+        assert_eq!(count("tweet_file"), 4);
+
+        let report = store.compact().await.unwrap();
+
+        assert_eq!(
+            report,
+            super::CompactReport {
+                groups: 1,
+                removed: 1
+            }
+        );
+        assert_eq!(count("tweet"), 2);
+        assert_eq!(count("tweet_file"), 4);
+
+        let mut texts = store
+            .get_multi_tweets(&[1302847271688523778])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(tweet, _)| tweet.text)
+            .collect::<Vec<_>>();
+        texts.sort();
+        texts.dedup();
+
+        assert_eq!(
+            texts,
+            vec!["This is  a tweet.\n", "This is a different tweet."]
+        );
+        assert_eq!(
+            store.compact().await.unwrap(),
+            super::CompactReport::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_find_edited() {
+        let store = TweetStore::in_memory().unwrap();
+        let versions = [
+            ("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", "This is a tweet."),
+            (
+                "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB",
+                "This is an edited tweet.",
+            ),
+            ("CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC", "This is a tweet!"),
+        ];
+
+        for (digest, text) in &versions {
+            store
+                .add_tweets(
+                    digest,
+                    None,
+                    None,
+                    &[example_tweet(text)],
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let archived_at = |digest: &str| {
+            let day = match digest {
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA" => 1,
+                "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB" => 3,
+                _ => return None,
+            };
+            NaiveDate::from_ymd_opt(2020, 9, day).and_then(|date| date.and_hms_opt(0, 0, 0))
+        };
+
+        assert_eq!(
+            store
+                .find_edited(Duration::days(1), archived_at)
+                .await
+                .unwrap(),
+            vec![1302847271688523778]
+        );
+        assert!(store
+            .find_edited(Duration::days(7), archived_at)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_add_tweets_rolls_back_on_error() {
+        let store = TweetStore::in_memory().unwrap();
+        let other = BrowserTweet::new(
+            1302847271688523779,
+            Some(1302847271688523778),
+            Utc.timestamp_millis_opt(1599458000000).unwrap(),
+            1,
+            "jack".to_string(),
+            "jack".to_string(),
+            "@ChiefScientist Hi".to_string(),
+        );
+
+        // Make the second tweet fail after the file and first tweet have been inserted.
+        store
+            .connection
+            .read()
+            .await
+            .execute_batch(
+                "CREATE TRIGGER fail_jack BEFORE INSERT ON tweet
+                    WHEN NEW.user_twitter_id = 1
+                    BEGIN SELECT RAISE(ABORT, 'failing for test'); END;",
+            )
+            .unwrap();
+
+        let tweets = [example_tweet("Hello"), other];
+        let digest = "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA";
+
+        assert!(store
+            .add_tweets(digest, None, None, &tweets, &Default::default())
+            .await
+            .is_err());
+        assert_eq!(store.check_digest(digest).await.unwrap(), None);
+        assert!(store
+            .get_multi_tweets(&[1302847271688523778])
+            .await
+            .unwrap()
+            .is_empty());
+
+        store
+            .connection
+            .read()
+            .await
+            .execute_batch("DROP TRIGGER fail_jack")
+            .unwrap();
+
+        store
+            .add_tweets(digest, None, None, &tweets, &Default::default())
+            .await
+            .unwrap();
+
+        assert!(store.check_digest(digest).await.unwrap().is_some());
+        assert_eq!(
+            store
+                .get_multi_tweets(&[1302847271688523778, 1302847271688523779])
+                .await
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_user() {
+        let store = TweetStore::in_memory().unwrap();
+        let other = BrowserTweet::new(
+            1302847271688523779,
+            Some(1302847271688523778),
+            Utc.timestamp_millis_opt(1599458000000).unwrap(),
+            1,
+            "jack".to_string(),
+            "jack".to_string(),
+            "@ChiefScientist Hi".to_string(),
+        );
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(1302847271688523779),
+                None,
+                &[example_tweet("Hello"), other],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        store
+            .add_tweets(
+                "BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB",
+                Some(1302847271688523778),
+                None,
+                &[example_tweet("Hello!")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+        store
+            .add_tweets(
+                "CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC",
+                None,
+                None,
+                &[],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let report = store.delete_user(1051208286).await.unwrap();
+
+        assert_eq!(
+            report,
+            super::DeleteReport {
+                tweets: 2,
+                tweet_files: 2,
+                files: 1,
+                users: 1
+            }
+        );
+
+        assert!(store
+            .check_digest("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(store
+            .check_digest("BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(store
+            .check_digest("CCCCCCCCCCCCCCCCCCCCCCCCCCCCCCCC")
+            .await
+            .unwrap()
+            .is_some());
+
+        let mut rows = vec![];
+        store.for_each_tweet(|row| rows.push(row)).await.unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].id, 1302847271688523779);
+        assert_eq!(rows[0].user_id, 1);
+        assert_eq!(
+            store
+                .files_for_tweet(1302847271688523779)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|(digest, _)| digest)
+                .collect::<Vec<_>>(),
+            vec!["AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"]
+        );
+        assert_eq!(store.get_users(&[1]).await.unwrap().len(), 1);
+        assert!(store.get_users(&[1051208286]).await.unwrap().is_empty());
+        assert_eq!(
+            store.delete_user(1051208286).await.unwrap(),
+            Default::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_in_memory() {
+        let store = TweetStore::in_memory().unwrap();
+        let tweet = example_tweet("Hello");
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                Some(tweet.id),
+                None,
+                &[example_tweet("Hello")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let tweets = store.get_multi_tweets(&[tweet.id]).await.unwrap();
+
+        assert_eq!(tweets.len(), 1);
+        assert_eq!(tweets[0].0.text, "Hello");
+        assert_eq!(tweets[0].0.user_screen_name, "ChiefScientist");
+        assert_eq!(tweets[0].0.time, tweet.time);
+    }
+
+    #[test]
+    fn test_migrate_file_url() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.db");
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE file (
+                    id INTEGER NOT NULL PRIMARY KEY,
+                    digest TEXT UNIQUE NOT NULL,
+                    primary_twitter_id INTEGER NULL
+                );",
+            )
+            .unwrap();
+        drop(connection);
+
+        TweetStore::new(&path, false).unwrap();
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute(
+                "INSERT INTO file (digest, url) VALUES ('AAAA', 'https://example.com')",
+                [],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_empty_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.db");
+        std::fs::File::create(&path).unwrap();
+
+        TweetStore::new(&path, false).unwrap();
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let version = connection
+            .query_row("SELECT version FROM schema_version", [], |row| {
+                row.get::<usize, i64>(0)
+            })
+            .unwrap();
+
+        assert_eq!(version, super::SCHEMA_VERSION);
+        connection
+            .execute(
+                "INSERT INTO file (digest, url) VALUES ('AAAA', 'https://example.com')",
+                [],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_migrate_schema_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.db");
+        let version = |path: &std::path::Path| {
+            rusqlite::Connection::open(path)
+                .unwrap()
+                .query_row("SELECT version FROM schema_version", [], |row| {
+                    row.get::<usize, i64>(0)
+                })
+                .unwrap()
+        };
+
+        // A version 1 database, with a version table.
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE file (
+                    id INTEGER NOT NULL PRIMARY KEY,
+                    digest TEXT UNIQUE NOT NULL,
+                    primary_twitter_id INTEGER NULL
+                );
+                INSERT INTO file (digest) VALUES ('AAAA');
+                CREATE TABLE schema_version (version INTEGER NOT NULL);
+                INSERT INTO schema_version (version) VALUES (1);",
+            )
+            .unwrap();
+        drop(connection);
+
+        TweetStore::new(&path, false).unwrap();
+
+        assert_eq!(version(&path), 2);
+        assert_eq!(super::SCHEMA_VERSION, 2);
+
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        let (digest, url) = connection
+            .query_row("SELECT digest, url FROM file", [], |row| {
+                Ok((
+                    row.get::<usize, String>(0)?,
+                    row.get::<usize, Option<String>>(1)?,
+                ))
+            })
+            .unwrap();
+
+        assert_eq!(digest, "AAAA");
+        assert_eq!(url, None);
+        drop(connection);
+
+        // Opening a current database doesn't change it.
+        TweetStore::new(&path, false).unwrap();
+        assert_eq!(version(&path), 2);
+
+        let new_path = dir.path().join("new.db");
+        TweetStore::new(&new_path, false).unwrap();
+        assert_eq!(version(&new_path), 2);
+
+        rusqlite::Connection::open(&new_path)
+            .unwrap()
+            .execute("UPDATE schema_version SET version = 3", [])
+            .unwrap();
+
+        assert!(matches!(
+            TweetStore::new(&new_path, false),
+            Err(super::TweetStoreError::UnsupportedSchemaVersion(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_wal_reader_during_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.db");
+        let options = crate::util::sqlite::ConnectionOptions {
+            wal: true,
+            busy_timeout: std::time::Duration::from_millis(100),
+        };
+
+        let writer = TweetStore::with_options(&path, false, options).unwrap();
+        writer
+            .add_tweets(
+                "ABC",
+                None,
+                None,
+                &[example_tweet("Hello")],
+                &super::NormalizeOptions::default(),
+            )
+            .await
+            .unwrap();
+
+        let reader = TweetStore::with_options(&path, false, options).unwrap();
+
+        {
+            let connection = writer.connection.read().await;
+            let mode: String = connection
+                .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+                .unwrap();
+            assert_eq!(mode, "wal");
+
+            // Hold the write lock with an uncommitted change.
+            connection
+                .execute_batch(
+                    "BEGIN IMMEDIATE;
+                     INSERT INTO file (digest, primary_twitter_id, url) VALUES ('DEF', NULL, NULL);",
+                )
+                .unwrap();
+
+            assert_eq!(reader.check_digest("ABC").await.unwrap(), Some(1));
+            assert_eq!(reader.check_digest("DEF").await.unwrap(), None);
+
+            connection.execute_batch("COMMIT").unwrap();
+        }
+
+        assert!(reader.check_digest("DEF").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_open_readonly() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tweets.db");
+        let store = TweetStore::new(&path, false).unwrap();
+        let normalize = super::NormalizeOptions::default();
+
+        store
+            .add_tweets("ABC", None, None, &[example_tweet("Hello")], &normalize)
+            .await
+            .unwrap();
+
+        let readonly = TweetStore::open_readonly(&path).unwrap();
+
+        assert_eq!(readonly.check_digest("ABC").await.unwrap(), Some(1));
+        assert_eq!(
+            readonly
+                .get_tweet(&[1302847271688523778])
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let result = readonly
+            .add_tweets("DEF", None, None, &[example_tweet("World")], &normalize)
+            .await;
+
+        assert!(matches!(result, Err(super::TweetStoreError::DbFailure(_))));
+        assert_eq!(store.check_digest("DEF").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_for_each_tweet() {
+        let store = TweetStore::in_memory().unwrap();
+        let normalize = super::NormalizeOptions::default();
+        let mut reply = example_tweet("@jdegoes Reply");
+        reply.id = 1302847271688523779;
+        reply.parent_id = Some(1302847271688523778);
+
+        store
+            .add_tweets("ABC", None, None, &[example_tweet("Hello")], &normalize)
+            .await
+            .unwrap();
+        store
+            .add_tweets("DEF", None, None, &[reply], &normalize)
+            .await
+            .unwrap();
+        store
+            .add_tweets("GHI", None, None, &[example_tweet("Hello!")], &normalize)
+            .await
+            .unwrap();
+
+        let mut rows = vec![];
+        let count = store.for_each_tweet(|row| rows.push(row)).await.unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(
+            rows.iter()
+                .map(|row| (row.id, row.parent_id, row.text.as_str()))
+                .collect::<Vec<_>>(),
+            vec![
+                (1302847271688523778, None, "Hello"),
+                (1302847271688523778, None, "Hello!"),
+                (
+                    1302847271688523779,
+                    Some(1302847271688523778),
+                    "@jdegoes Reply"
+                )
+            ]
+        );
+        assert_eq!(rows[0].ts, Utc.timestamp_millis_opt(1599457984000).unwrap());
+        assert_eq!(rows[0].user_id, 1051208286);
+    }
+
+    #[tokio::test]
+    async fn test_screen_name_history() {
+        let store = TweetStore::in_memory().unwrap();
+        let tweets = vec![
+            (1302847271688523778, 1599457984000, "ChiefScientist"),
+            (1302847271688523779, 1599467984000, "ChiefScientist"),
+            (1402847271688523778, 1623300000000, "kmett"),
+            (1402847271688523779, 1623400000000, "kmett"),
+        ];
+
+        for (i, (id, ts, screen_name)) in tweets.into_iter().enumerate() {
+            let mut tweet = example_tweet("Hello");
+            tweet.id = id;
+            tweet.time = Utc.timestamp_millis_opt(ts).unwrap();
+            tweet.user_screen_name = screen_name.to_string();
+
+            store
+                .add_tweets(
+                    &format!("{}AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", i),
+                    Some(id),
+                    None,
+                    &[tweet],
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            store.screen_name_history(1051208286).await.unwrap(),
+            vec![
+                (
+                    "ChiefScientist".to_string(),
+                    Utc.timestamp_millis_opt(1599457984000).unwrap(),
+                    Utc.timestamp_millis_opt(1599467984000).unwrap()
+                ),
+                (
+                    "kmett".to_string(),
+                    Utc.timestamp_millis_opt(1623300000000).unwrap(),
+                    Utc.timestamp_millis_opt(1623400000000).unwrap()
+                )
+            ]
+        );
+        assert!(store.screen_name_history(1).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_normalize_options() {
+        let text = "Caf\u{0065}\u{0301}  is\topen\r\n\r\n  today ";
+
+        assert_eq!(super::NormalizeOptions::default().normalize(text), text);
+
+        let collapse_spaces = super::NormalizeOptions {
+            collapse_spaces: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            collapse_spaces.normalize(text),
+            "Caf\u{0065}\u{0301} is open\r\n\r\ntoday"
+        );
+
+        let normalize_newlines = super::NormalizeOptions {
+            normalize_newlines: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            normalize_newlines.normalize(text),
+            "Caf\u{0065}\u{0301}  is\topen\n\n  today "
+        );
+
+        let unicode_nfc = super::NormalizeOptions {
+            unicode_nfc: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            unicode_nfc.normalize(text),
+            "Caf\u{00e9}  is\topen\r\n\r\n  today "
+        );
+
+        let all = super::NormalizeOptions {
+            collapse_spaces: true,
+            normalize_newlines: true,
+            unicode_nfc: true,
+        };
+        assert_eq!(all.normalize(text), "Caf\u{00e9} is open\n\ntoday");
+    }
+
+    #[tokio::test]
+    async fn test_add_tweets_normalized() {
+        let store = TweetStore::in_memory().unwrap();
+        let normalize = super::NormalizeOptions {
+            collapse_spaces: true,
+            normalize_newlines: true,
+            unicode_nfc: false,
+        };
+
+        for (digest, text) in &[
+            ("AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", "This is  a tweet.\r\n"),
+            ("BBBBBBBBBBBBBBBBBBBBBBBBBBBBBBBB", "This is a tweet."),
+        ] {
+            store
+                .add_tweets(digest, None, None, &[example_tweet(text)], &normalize)
+                .await
+                .unwrap();
+        }
+
+        let tweets = store
+            .get_multi_tweets(&[1302847271688523778])
+            .await
+            .unwrap();
+
+        assert_eq!(tweets.len(), 2);
+        assert!(tweets
+            .iter()
+            .all(|(tweet, _)| tweet.text == "This is a tweet."));
+        assert_eq!(
+            store.compact().await.unwrap(),
+            super::CompactReport::default()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_tweets_for_user_range() {
+        let store = TweetStore::in_memory().unwrap();
+        let tweet = example_tweet("This is a tweet.");
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                None,
+                None,
+                &[example_tweet("This is a tweet.")],
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let exact = store
+            .get_tweets_for_user(tweet.user_id, tweet.time, tweet.time, Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(exact.len(), 1);
+        assert_eq!(exact[0].0, tweet.id);
+        assert_eq!(exact[0].1, tweet.time);
+
+        let day = store
+            .get_tweets_for_user(
+                tweet.user_id,
+                Utc.with_ymd_and_hms(2020, 9, 7, 0, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2020, 9, 7, 23, 59, 59).unwrap(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(day.len(), 1);
+
+        let after = store
+            .get_tweets_for_user(
+                tweet.user_id,
+                tweet.time + Duration::seconds(1),
+                tweet.time + Duration::days(1),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+
+        assert!(after.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_paging() {
+        let store = TweetStore::in_memory().unwrap();
+        let parent_id = 1302847271688523778;
+        let replies = (1..=5)
+            .map(|i| {
+                BrowserTweet::new(
+                    parent_id + i,
+                    Some(parent_id),
+                    Utc.timestamp_millis_opt(1599457984000 + i as i64 * 1000)
+                        .unwrap(),
+                    1,
+                    "jack".to_string(),
+                    "jack".to_string(),
+                    format!("Reply {}", i),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // The replies are added out of order and in duplicate.
+        for (i, reply) in replies.into_iter().rev().enumerate() {
+            store
+                .add_tweets(
+                    &format!("{}AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA", i),
+                    None,
+                    None,
+                    &[example_tweet("Hello"), reply],
+                    &Default::default(),
+                )
+                .await
+                .unwrap();
+        }
+
+        let all = store
+            .get_replies(1051208286, "ChiefScientist", Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            all.iter().map(|(_, id, _, _)| *id).collect::<Vec<_>>(),
+            (1..=5).map(|i| parent_id + i).collect::<Vec<_>>()
+        );
+
+        let page = store
+            .get_replies(1051208286, "ChiefScientist", super::Page::new(2, 1))
+            .await
+            .unwrap();
+
+        assert_eq!(page, all[1..3]);
+        assert_eq!(
+            store
+                .get_replies(1051208286, "ChiefScientist", super::Page::new(2, 4))
+                .await
+                .unwrap(),
+            all[4..]
+        );
+
+        let start = Utc.timestamp_millis_opt(1599457984000).unwrap();
+        let end = start + Duration::seconds(10);
+        let all = store
+            .get_tweets_for_user(1, start, end, Default::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            all.iter().map(|(id, _, _, _, _)| *id).collect::<Vec<_>>(),
+            (1..=5).map(|i| parent_id + i).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            store
+                .get_tweets_for_user(1, start, end, super::Page::new(2, 1))
+                .await
+                .unwrap(),
+            all[1..3]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_conversations_between() {
+        let store = TweetStore::in_memory().unwrap();
+        let root_id = 1302847271688523778;
+        let reply = |id: u64, parent_id: u64, user_id: u64, screen_name: &str| {
+            BrowserTweet::new(
+                id,
+                Some(parent_id),
+                Utc.timestamp_millis_opt(1599457984000 + (id - root_id) as i64 * 1000)
+                    .unwrap(),
+                user_id,
+                screen_name.to_string(),
+                screen_name.to_string(),
+                "Reply".to_string(),
+            )
+        };
+
+        // ChiefScientist posts, and jack and ev reply to that tweet and to each other.
+        let tweets = vec![
+            example_tweet("Hello"),
+            reply(root_id + 1, root_id, 1, "jack"),
+            reply(root_id + 2, root_id, 2, "ev"),
+            reply(root_id + 3, root_id + 1, 1051208286, "ChiefScientist"),
+            reply(root_id + 4, root_id + 1, 2, "ev"),
+            reply(root_id + 5, root_id + 3, 1, "jack"),
+        ];
+
+        store
+            .add_tweets(
+                "AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA",
+                None,
+                None,
+                &tweets,
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+
+        let edges = store.conversations_between(1, 1051208286).await.unwrap();
+
+        assert_eq!(
+            edges
+                .iter()
+                .map(|edge| (
+                    edge.parent_id,
+                    edge.parent_user_id,
+                    edge.reply_id,
+                    edge.reply_user_id
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (root_id, 1051208286, root_id + 1, 1),
+                (root_id + 1, 1, root_id + 3, 1051208286),
+                (root_id + 3, 1051208286, root_id + 5, 1)
+            ]
+        );
+        assert_eq!(
+            edges[0].reply_ts,
+            Utc.timestamp_millis_opt(1599457985000).unwrap()
+        );
+        assert_eq!(
+            store.conversations_between(1051208286, 1).await.unwrap(),
+            edges
+        );
+    }
+
+    #[test]
+    fn test_is_edited() {
+        let first = NaiveDate::from_ymd_opt(2022, 10, 1)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+        let noise = NaiveDate::from_ymd_opt(2022, 10, 1)
+            .and_then(|date| date.and_hms_opt(12, 2, 0))
+            .unwrap();
+        let later = NaiveDate::from_ymd_opt(2022, 10, 1)
+            .and_then(|date| date.and_hms_opt(15, 0, 0))
+            .unwrap();
+
+        let edited = vec![(first, "Hello wrold"), (later, "Hello world")];
+        let noisy = vec![(first, "Hello wrold"), (noise, "Hello world")];
+        let whitespace = vec![(first, "Hello world"), (later, "Hello  world\n")];
+
+        assert!(super::is_edited(&edited, Duration::minutes(10)));
+        assert!(!super::is_edited(&noisy, Duration::minutes(10)));
+        assert!(super::is_edited(&noisy, Duration::minutes(1)));
+        assert!(!super::is_edited(&whitespace, Duration::minutes(10)));
+    }
+}
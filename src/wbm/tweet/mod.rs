@@ -2,10 +2,21 @@ pub mod db;
 
 use super::valid::ValidStore;
 use crate::browser::twitter::parser::{self, BrowserTweet};
+use chrono::{DateTime, Utc};
 use flate2::read::GzDecoder;
+use futures::{Stream, TryStreamExt};
+use std::convert::TryFrom;
 use std::fs::File;
+use std::future::Future;
 use std::io::Read;
 use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How many times adding a file's tweets is attempted before the file is skipped.
+const ADD_TWEETS_ATTEMPTS: u32 = 4;
+/// The wait after the first failure (this doubles after each later failure).
+const ADD_TWEETS_RETRY_DELAY: Duration = Duration::from_millis(250);
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -21,9 +32,37 @@ pub enum Error {
 
 type Result<T> = std::result::Result<T, Error>;
 
-fn extract_tweets_from_path<P: AsRef<Path>>(
-    p: P,
-) -> Result<Option<(Option<u64>, Vec<BrowserTweet>)>> {
+/// Counts of work done so far by [`export_tweets_with_progress`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ExportProgress {
+    /// Files that have been read (including those that couldn't be parsed).
+    pub files: usize,
+    /// Tweets added to the database.
+    pub tweets: usize,
+    /// Files that couldn't be read or parsed, or whose tweets couldn't be added.
+    pub failures: usize,
+}
+
+#[derive(Default)]
+struct ProgressCounter {
+    files: AtomicUsize,
+    tweets: AtomicUsize,
+    failures: AtomicUsize,
+}
+
+impl ProgressCounter {
+    fn snapshot(&self) -> ExportProgress {
+        ExportProgress {
+            files: self.files.load(Ordering::SeqCst),
+            tweets: self.tweets.load(Ordering::SeqCst),
+            failures: self.failures.load(Ordering::SeqCst),
+        }
+    }
+}
+
+type ExtractedTweets = (Option<u64>, Option<String>, Vec<BrowserTweet>);
+
+fn extract_tweets_from_path<P: AsRef<Path>>(p: P) -> Result<Option<ExtractedTweets>> {
     let path = p.as_ref();
 
     if path.is_file() {
@@ -33,26 +72,115 @@ fn extract_tweets_from_path<P: AsRef<Path>>(
         gz.read_to_string(&mut doc)?;
 
         Ok(match parser::extract_tweet_json(&doc) {
-            Some(tweet) => Some((Some(tweet.id), vec![tweet])),
-            None => match parser::parse_html(&mut doc.as_bytes()) {
-                Ok(doc) => Some((
-                    parser::extract_canonical_status_id(&doc),
-                    parser::extract_tweets(&doc),
-                )),
-                Err(err) => {
-                    log::error!("Failed reading {:?}: {:?}", path, err);
-                    None
+            Some(tweet) => Some((Some(tweet.id), None, vec![tweet])),
+            None => {
+                match parser::parse_html(&mut super::util::strip_wayback_artifacts(&doc).as_bytes())
+                {
+                    Ok(doc) => Some((
+                        parser::extract_canonical_status_id(&doc),
+                        parser::extract_canonical_url(&doc),
+                        parser::extract_tweets(&doc),
+                    )),
+                    Err(err) => {
+                        log::error!("Failed reading {:?}: {:?}", path, err);
+                        None
+                    }
                 }
-            },
+            }
         })
     } else {
         Ok(None)
     }
 }
 
+/// Run an operation until it succeeds or has been attempted the given number of times.
+///
+/// The `sleep` function is called with the delay before each retry (e.g. `tokio::time::sleep`).
+async fn retry<T, E, F, Fut, S, SFut>(
+    attempts: u32,
+    delay: Duration,
+    mut sleep: S,
+    mut f: F,
+) -> std::result::Result<T, E>
+where
+    E: std::fmt::Debug,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = std::result::Result<T, E>>,
+    S: FnMut(Duration) -> SFut,
+    SFut: Future<Output = ()>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Err(error) if attempt < attempts => {
+                let wait = delay * 2u32.pow(attempt - 1);
+                log::warn!(
+                    "Attempt {} failed, retrying in {:?}: {:?}",
+                    attempt,
+                    wait,
+                    error
+                );
+                sleep(wait).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
 pub async fn export_tweets(store: &ValidStore, tweet_store: &db::TweetStore) -> Result<()> {
+    export_tweets_with_progress(store, tweet_store, &Default::default(), 0, |_| {}).await?;
+
+    Ok(())
+}
+
+/// Export tweets, calling the observer with the current counts after every `interval` files.
+///
+/// Tweet text is normalized according to the given options. An interval of zero disables the
+/// observer. The final counts are returned.
+///
+/// Database errors when adding a file's tweets (for example because another process holds a
+/// lock) are retried, and if they persist the file is logged and skipped.
+pub async fn export_tweets_with_progress<F: Fn(ExportProgress)>(
+    store: &ValidStore,
+    tweet_store: &db::TweetStore,
+    normalize: &db::NormalizeOptions,
+    interval: usize,
+    observer: F,
+) -> Result<ExportProgress> {
+    export_tweets_with_sleep(
+        store,
+        tweet_store,
+        normalize,
+        interval,
+        observer,
+        tokio::time::sleep,
+    )
+    .await
+}
+
+/// Export tweets, calling `sleep` with the delay before retrying a failed database write.
+async fn export_tweets_with_sleep<F, S, SFut>(
+    store: &ValidStore,
+    tweet_store: &db::TweetStore,
+    normalize: &db::NormalizeOptions,
+    interval: usize,
+    observer: F,
+    sleep: S,
+) -> Result<ExportProgress>
+where
+    F: Fn(ExportProgress),
+    S: Fn(Duration) -> SFut,
+    SFut: Future<Output = ()>,
+{
     use futures::{FutureExt, StreamExt, TryStreamExt};
 
+    let counter = ProgressCounter::default();
+    let counter_ref = &counter;
+    let observer_ref = &observer;
+    let sleep_ref = &sleep;
+
     futures::stream::iter(store.paths().map(|result| result.map_err(Error::from)))
         .filter_map(|res| async {
             match res {
@@ -67,19 +195,26 @@ pub async fn export_tweets(store: &ValidStore, tweet_store: &db::TweetStore) ->
                     Ok(Some(
                         tokio::task::spawn(async move {
                             extract_tweets_from_path(path).map(|outer_option| {
-                                outer_option
-                                    .map(|(status_id, tweets)| (digest_clone, status_id, tweets))
+                                outer_option.map(|(status_id, url, tweets)| {
+                                    (digest_clone, status_id, url, tweets)
+                                })
                             })
                         })
                         .then(move |res| async move {
-                            match res {
+                            let result = match res {
                                 Ok(Err(Error::IOError(underlying))) => {
                                     log::warn!("Error parsing {}: {:?}", digest, underlying);
                                     Ok(None)
                                 }
                                 Ok(inner_res) => inner_res,
                                 Err(error) => Err(Error::from(error)),
+                            };
+
+                            if let Ok(None) = result {
+                                counter_ref.failures.fetch_add(1, Ordering::SeqCst);
                             }
+
+                            result
                         }),
                     ))
                 } else {
@@ -88,10 +223,359 @@ pub async fn export_tweets(store: &ValidStore, tweet_store: &db::TweetStore) ->
             }
         })
         .try_buffer_unordered(4)
-        .try_filter_map(|maybe_content| async { Ok(maybe_content) })
-        .try_for_each(|(digest, status_id, tweets)| async move {
-            tweet_store.add_tweets(&digest, status_id, &tweets).await?;
+        .try_for_each(|maybe_content| async move {
+            if let Some((digest, status_id, url, tweets)) = maybe_content {
+                match retry(
+                    ADD_TWEETS_ATTEMPTS,
+                    ADD_TWEETS_RETRY_DELAY,
+                    sleep_ref,
+                    || {
+                        tweet_store.add_tweets(
+                            &digest,
+                            status_id,
+                            url.as_deref(),
+                            &tweets,
+                            normalize,
+                        )
+                    },
+                )
+                .await
+                {
+                    Ok(()) => {
+                        counter_ref.tweets.fetch_add(tweets.len(), Ordering::SeqCst);
+                    }
+                    Err(error) => {
+                        log::error!(
+                            "Skipping {} after failing to add tweets: {:?}",
+                            digest,
+                            error
+                        );
+                        counter_ref.failures.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+
+            let files = counter_ref.files.fetch_add(1, Ordering::SeqCst) + 1;
+
+            if interval > 0 && files % interval == 0 {
+                observer_ref(counter_ref.snapshot());
+            }
+
             Ok(())
         })
+        .await?;
+
+    Ok(counter.snapshot())
+}
+
+/// Counts for a [`backfill_live_tweets`] run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct BackfillReport {
+    /// Tweets that were still available and were added to the database.
+    pub stored: usize,
+    /// Tweets that are no longer available.
+    pub unavailable: usize,
+}
+
+/// The synthetic digest used for a tweet captured from the API instead of the Wayback Machine.
+pub fn live_digest(status_id: u64, captured_at: DateTime<Utc>) -> String {
+    format!("live-{}-{}", status_id, captured_at.timestamp())
+}
+
+/// Add tweets that are still available to the database.
+///
+/// The results of a lookup are provided as a stream (in the format returned by the Twitter
+/// client), and each available tweet is stored as its own file with a synthetic digest, so
+/// later captures are recorded as new versions.
+pub async fn backfill_live_tweets<S, E>(
+    tweet_store: &db::TweetStore,
+    lookups: S,
+    captured_at: DateTime<Utc>,
+    normalize: &db::NormalizeOptions,
+) -> std::result::Result<BackfillReport, E>
+where
+    S: Stream<Item = std::result::Result<(u64, Option<egg_mode::tweet::Tweet>), E>>,
+    E: From<db::TweetStoreError>,
+{
+    let mut report = BackfillReport::default();
+
+    futures::pin_mut!(lookups);
+
+    while let Some((id, result)) = lookups.try_next().await? {
+        let tweet = match result.as_ref().map(BrowserTweet::try_from) {
+            Some(Ok(tweet)) => tweet,
+            Some(Err(error)) => {
+                log::warn!("Skipping {}: {}", id, error);
+                continue;
+            }
+            None => {
+                report.unavailable += 1;
+                continue;
+            }
+        };
+
+        let digest = live_digest(id, captured_at);
+
+        if tweet_store.check_digest(&digest).await?.is_none() {
+            let url = format!(
+                "https://twitter.com/{}/status/{}",
+                tweet.user_screen_name, tweet.id
+            );
+
+            tweet_store
+                .add_tweets(&digest, Some(id), Some(&url), &[tweet], normalize)
+                .await?;
+            report.stored += 1;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{db::TweetStore, ExportProgress, ValidStore};
+    use std::path::Path;
+    use std::sync::Mutex;
+
+    /// Create a valid store under `dir` containing the example data files, and return it with the
+    /// number of files copied.
+    fn fixture_valid_store(dir: &Path) -> (ValidStore, usize) {
+        let store = ValidStore::create(dir.join("store")).unwrap();
+
+        let mut file_count = 0;
+        for entry in std::fs::read_dir("examples/wayback/store/data/").unwrap() {
+            let path = entry.unwrap().path();
+            let digest = path.file_stem().unwrap().to_str().unwrap();
+            std::fs::copy(&path, store.location(digest).unwrap()).unwrap();
+            file_count += 1;
+        }
+
+        (store, file_count)
+    }
+
+    #[tokio::test]
+    async fn test_export_tweets_retries_locked_database() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let (store, file_count) = fixture_valid_store(base_dir.path());
+        let db_path = base_dir.path().join("tweets.db");
+
+        // Without a busy timeout, writes fail immediately while another connection has a lock.
+        let tweet_store = TweetStore::with_options(
+            &db_path,
+            false,
+            crate::util::sqlite::ConnectionOptions {
+                wal: true,
+                busy_timeout: std::time::Duration::ZERO,
+            },
+        )
+        .unwrap();
+
+        let lock = rusqlite::Connection::open(&db_path).unwrap();
+        lock.execute_batch("BEGIN EXCLUSIVE").unwrap();
+        let lock = Mutex::new(Some(lock));
+        let waits = Mutex::new(vec![]);
+
+        // The first write fails, and the lock is released before the retry.
+        let progress = super::export_tweets_with_sleep(
+            &store,
+            &tweet_store,
+            &Default::default(),
+            0,
+            |_| {},
+            |delay| {
+                waits.lock().unwrap().push(delay);
+                if let Some(lock) = lock.lock().unwrap().take() {
+                    lock.execute_batch("COMMIT").unwrap();
+                }
+                async {}
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            waits.into_inner().unwrap(),
+            vec![super::ADD_TWEETS_RETRY_DELAY]
+        );
+
+        assert_eq!(progress.files, file_count);
+        assert!(progress.tweets > 0);
+
+        let reference_store = TweetStore::in_memory().unwrap();
+        let reference = super::export_tweets_with_progress(
+            &store,
+            &reference_store,
+            &Default::default(),
+            0,
+            |_| {},
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(progress, reference);
+    }
+
+    #[tokio::test]
+    async fn test_retry() {
+        let mut calls = 0;
+        let mut waits = vec![];
+        let result = super::retry(
+            3,
+            std::time::Duration::from_millis(1),
+            |delay| {
+                waits.push(delay);
+                async {}
+            },
+            || {
+                calls += 1;
+                let call = calls;
+                async move {
+                    if call == 1 {
+                        Err("locked")
+                    } else {
+                        Ok(call)
+                    }
+                }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(waits, vec![std::time::Duration::from_millis(1)]);
+
+        let mut calls = 0;
+        let mut waits = vec![];
+        let result: Result<(), _> = super::retry(
+            3,
+            std::time::Duration::from_millis(1),
+            |delay| {
+                waits.push(delay);
+                async {}
+            },
+            || {
+                calls += 1;
+                async { Err("locked") }
+            },
+        )
+        .await;
+
+        assert_eq!(result, Err("locked"));
+        assert_eq!(
+            waits,
+            vec![
+                std::time::Duration::from_millis(1),
+                std::time::Duration::from_millis(2)
+            ]
+        );
+        assert_eq!(calls, 3);
+    }
+
+    #[tokio::test]
+    async fn test_backfill_live_tweets() {
+        use chrono::TimeZone;
+
+        let tweet_store = TweetStore::in_memory().unwrap();
+        let contents = std::fs::read_to_string("examples/json/890661113871495168.json").unwrap();
+        let tweet = serde_json::from_str::<egg_mode::tweet::Tweet>(&contents).unwrap();
+        let captured_at = chrono::Utc.with_ymd_and_hms(2021, 1, 2, 3, 4, 5).unwrap();
+        let lookups = || {
+            futures::stream::iter(vec![
+                Ok((890661113871495168, Some(tweet.clone()))),
+                Ok((890659426796945408, None)),
+            ])
+        };
+
+        let report = super::backfill_live_tweets::<_, super::db::TweetStoreError>(
+            &tweet_store,
+            lookups(),
+            captured_at,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            report,
+            super::BackfillReport {
+                stored: 1,
+                unavailable: 1
+            }
+        );
+
+        let stored = tweet_store
+            .get_multi_tweets(&[890661113871495168])
+            .await
+            .unwrap();
+
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].0.parent_id, Some(890659426796945408));
+        assert!(stored[0].0.text.ends_with("before the end of August."));
+        assert_eq!(stored[0].1, "live-890661113871495168-1609556645");
+        assert_eq!(
+            tweet_store
+                .files_for_tweet(890661113871495168)
+                .await
+                .unwrap(),
+            vec![(
+                "live-890661113871495168-1609556645".to_string(),
+                Some("https://twitter.com/DrupalLeaks/status/890661113871495168".to_string())
+            )]
+        );
+
+        // Running again with the same capture time doesn't add anything.
+        let again = super::backfill_live_tweets::<_, super::db::TweetStoreError>(
+            &tweet_store,
+            lookups(),
+            captured_at,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(again.stored, 0);
+    }
+
+    #[tokio::test]
+    async fn test_export_tweets_with_progress() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let (store, file_count) = fixture_valid_store(base_dir.path());
+        let tweet_store = TweetStore::new(base_dir.path().join("tweets.db"), false).unwrap();
+
+        let observed = Mutex::new(vec![]);
+        let progress = super::export_tweets_with_progress(
+            &store,
+            &tweet_store,
+            &Default::default(),
+            2,
+            |progress| observed.lock().unwrap().push(progress),
+        )
         .await
+        .unwrap();
+
+        let observed = observed.into_inner().unwrap();
+
+        assert_eq!(progress.files, file_count);
+        assert_eq!(observed.len(), file_count / 2);
+        assert_eq!(
+            observed
+                .iter()
+                .map(|progress| progress.files)
+                .collect::<Vec<_>>(),
+            (1..=file_count / 2).map(|i| i * 2).collect::<Vec<_>>()
+        );
+
+        // Files that are already in the database are skipped.
+        let second = super::export_tweets_with_progress(
+            &store,
+            &tweet_store,
+            &Default::default(),
+            1,
+            |_| panic!("Unexpected progress"),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(second, ExportProgress::default());
+    }
 }
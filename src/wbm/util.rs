@@ -1,8 +1,14 @@
+use chrono::NaiveDateTime;
+use futures::Future;
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Write};
+use wayback_rs::Item;
 
-const TWEET_URL_PATTERN: &str = r"^http[s]?://twitter\.com/([^/]+)/status/(\d+)(?:\?.+)?$";
-const TWEET_REDIRECT_HTML_PATTERN: &str = r#"^<html><body>You are being <a href="http[s]?://twitter\.com/([^/]+)/status/(\d+)(?:\?.+)?">redirected</a>\.</body></html>$"#;
+const WAYBACK_TIMESTAMP_FMT: &str = "%Y%m%d%H%M%S";
+const TWEET_URL_PATTERN: &str = r"^http[s]?://(?:twitter|x)\.com/([^/]+)/status/(\d+)(?:\?.+)?$";
+const TWEET_REDIRECT_HTML_PATTERN: &str = r#"^<html><body>You are being <a href="http[s]?://(?:twitter|x)\.com/([^/]+)/status/(\d+)(?:\?.+)?">redirected</a>\.</body></html>$"#;
 
 pub fn parse_tweet_url(url: &str) -> Option<(String, u64)> {
     lazy_static! {
@@ -30,8 +36,393 @@ pub fn parse_tweet_redirect_html(content: &str) -> Option<(String, u64)> {
     })
 }
 
+/// Parse a 14-digit Wayback Machine timestamp (e.g. `20150826223146`).
+pub fn parse_timestamp(input: &str) -> Option<NaiveDateTime> {
+    if input.len() == 14 && input.bytes().all(|b| b.is_ascii_digit()) {
+        NaiveDateTime::parse_from_str(input, WAYBACK_TIMESTAMP_FMT).ok()
+    } else {
+        None
+    }
+}
+
+/// Parse a Wayback Machine snapshot URL into its timestamp and the original URL.
+///
+/// Timestamps may have a modifier suffix (e.g. `id_`), which is ignored.
+pub fn parse_wayback_url(url: &str) -> Option<(NaiveDateTime, String)> {
+    lazy_static! {
+        static ref WAYBACK_URL_RE: Regex =
+            Regex::new(r"^(?:https?://)?web\.archive\.org/web/(\d{14})(?:[a-z]{2}_)?/(.+)$")
+                .unwrap();
+    }
+
+    let captures = WAYBACK_URL_RE.captures(url.trim())?;
+    let timestamp = parse_timestamp(captures.get(1)?.as_str())?;
+
+    Some((timestamp, captures.get(2)?.as_str().to_string()))
+}
+
+/// Remove the toolbar, scripts, and comments the Wayback Machine injects into archived HTML, and
+/// rewrite archived URLs back to the original URLs.
+///
+/// Content downloaded with the `id_` modifier doesn't have these, and is returned unchanged.
+pub fn strip_wayback_artifacts(html: &str) -> String {
+    lazy_static! {
+        static ref INJECTED_BLOCK_RE: Regex = Regex::new(concat!(
+            r"(?s)<!-- BEGIN WAYBACK TOOLBAR INSERT -->.*?<!-- END WAYBACK TOOLBAR INSERT -->\s*",
+            r"|<script[^>]*/_static/.*?<!-- End Wayback Rewrite JS Include -->\s*",
+            r"|<!--\s*FILE ARCHIVED ON .*?-->\s*",
+            r"|<!--\s*playback timings .*?-->\s*"
+        ))
+        .unwrap();
+        static ref STATIC_TAG_RE: Regex = Regex::new(concat!(
+            r#"<script[^>]*src="[^"]*/_static/[^"]*"[^>]*>\s*</script>\s*"#,
+            r#"|<link[^>]*href="[^"]*/_static/[^"]*"[^>]*>\s*"#
+        ))
+        .unwrap();
+        static ref ARCHIVED_URL_RE: Regex = Regex::new(
+            r"(?:(?:https?:)?//web\.archive\.org)?/web/\d{1,14}(?:[a-z]{2}_)?/((?:https?:)?//)"
+        )
+        .unwrap();
+    }
+
+    let stripped = INJECTED_BLOCK_RE.replace_all(html, "");
+    let stripped = STATIC_TAG_RE.replace_all(&stripped, "");
+
+    ARCHIVED_URL_RE.replace_all(&stripped, "$1").into_owned()
+}
+
+/// Format a UTC date-time (e.g. an item's `archived_at`) as a 14-digit Wayback Machine timestamp.
+pub fn format_timestamp(value: NaiveDateTime) -> String {
+    value.format(WAYBACK_TIMESTAMP_FMT).to_string()
+}
+
+/// Bounds on when a tweet was deleted, according to the Wayback Machine.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DeletionWindow {
+    /// The most recent snapshot in which the tweet was available.
+    pub last_seen_alive: NaiveDateTime,
+    /// The first 404 snapshot after `last_seen_alive`, if there is one.
+    pub first_seen_gone: Option<NaiveDateTime>,
+}
+
+/// Compute the deletion window for the CDX rows of a single tweet URL.
+///
+/// Snapshots with no status or a 200 status are treated as showing the tweet, while 404 snapshots
+/// are treated as showing it as deleted. Returns `None` if the tweet was never seen alive.
+pub fn deletion_window<'a, I: IntoIterator<Item = &'a Item>>(items: I) -> Option<DeletionWindow> {
+    let items = items.into_iter().collect::<Vec<_>>();
+
+    let last_seen_alive = items
+        .iter()
+        .filter(|item| item.status.is_none() || item.status == Some(200))
+        .map(|item| item.archived_at)
+        .max()?;
+
+    let first_seen_gone = items
+        .iter()
+        .filter(|item| item.status == Some(404) && item.archived_at > last_seen_alive)
+        .map(|item| item.archived_at)
+        .min();
+
+    Some(DeletionWindow {
+        last_seen_alive,
+        first_seen_gone,
+    })
+}
+
+/// Map each digest to the earliest time it was archived.
+pub fn archive_times<'a, I: IntoIterator<Item = &'a Item>>(
+    items: I,
+) -> HashMap<String, NaiveDateTime> {
+    let mut result: HashMap<String, NaiveDateTime> = HashMap::new();
+
+    for item in items {
+        let archived_at = result
+            .entry(item.digest.clone())
+            .or_insert(item.archived_at);
+
+        if item.archived_at < *archived_at {
+            *archived_at = item.archived_at;
+        }
+    }
+
+    result
+}
+
+/// The kind of Twitter page an archived item represents.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ItemKind {
+    Status(u64),
+    /// A user's profile (including their replies, media, and likes tabs).
+    Profile(String),
+    /// A search, with its query.
+    Search(String),
+    Other,
+}
+
+/// Top-level paths that look like screen names but aren't profiles.
+const RESERVED_PATHS: [&str; 16] = [
+    "account",
+    "compose",
+    "explore",
+    "hashtag",
+    "home",
+    "i",
+    "intent",
+    "login",
+    "logout",
+    "messages",
+    "notifications",
+    "privacy",
+    "search",
+    "settings",
+    "share",
+    "tos",
+];
+
+/// Determine what kind of Twitter page an archived item is from its URL.
+pub fn classify_item(item: &Item) -> ItemKind {
+    lazy_static! {
+        static ref PROFILE_PATH_RE: Regex =
+            Regex::new(r"^/([A-Za-z0-9_]{1,15})(?:/(?:with_replies|media|likes))?/?$").unwrap();
+    }
+
+    if let Some(id) = crate::util::parse_tweet_ref(&item.url) {
+        return ItemKind::Status(id);
+    }
+
+    let url = match url::Url::parse(&item.url) {
+        Ok(url) => url,
+        Err(_) => return ItemKind::Other,
+    };
+
+    match url.host_str().map(|host| host.to_lowercase()) {
+        Some(host) if crate::util::TWITTER_HOSTS.contains(&host.as_str()) => {}
+        _ => return ItemKind::Other,
+    }
+
+    if url.path().trim_end_matches('/') == "/search" {
+        return url
+            .query_pairs()
+            .find(|(key, _)| key == "q")
+            .map(|(_, query)| ItemKind::Search(query.into_owned()))
+            .unwrap_or(ItemKind::Other);
+    }
+
+    PROFILE_PATH_RE
+        .captures(url.path())
+        .and_then(|captures| captures.get(1))
+        .map(|name| name.as_str())
+        .filter(|name| !RESERVED_PATHS.contains(&name.to_lowercase().as_str()))
+        .map(|name| ItemKind::Profile(name.to_string()))
+        .unwrap_or(ItemKind::Other)
+}
+
+/// The earliest successful snapshot time for each tweet in a set of CDX results.
+///
+/// Redirects (which represent retweets) and error responses are excluded.
+pub fn first_valid_snapshots<'a, I: IntoIterator<Item = &'a Item>>(
+    items: I,
+) -> HashMap<u64, NaiveDateTime> {
+    let mut result = HashMap::new();
+
+    for item in items {
+        if item.status.is_none() || item.status == Some(200) {
+            if let Some(id) = crate::util::parse_tweet_ref(&item.url) {
+                let first = result.entry(id).or_insert(item.archived_at);
+                *first = (*first).min(item.archived_at);
+            }
+        }
+    }
+
+    result
+}
+
+/// Counts of archived tweets and how many of them have been deleted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DeletionCounts {
+    pub total: usize,
+    pub deleted: usize,
+}
+
+impl DeletionCounts {
+    /// The fraction of tweets that have been deleted (zero if there are none).
+    pub fn rate(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.deleted as f64 / self.total as f64
+        }
+    }
+}
+
+/// Aggregate deletion status by the year each tweet was first archived.
+pub fn deletion_counts_by_year<I: IntoIterator<Item = (NaiveDateTime, bool)>>(
+    candidates: I,
+) -> BTreeMap<i32, DeletionCounts> {
+    use chrono::Datelike;
+
+    let mut result = BTreeMap::new();
+
+    for (archived_at, deleted) in candidates {
+        let counts: &mut DeletionCounts = result.entry(archived_at.year()).or_default();
+        counts.total += 1;
+
+        if deleted {
+            counts.deleted += 1;
+        }
+    }
+
+    result
+}
+
+/// Read status IDs (or tweet URLs), one per line, ignoring blank lines and `#` comments.
+pub fn read_status_ids<R: BufRead>(reader: R) -> std::io::Result<Vec<u64>> {
+    let mut ids = vec![];
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        match crate::util::parse_tweet_ref(line) {
+            Some(id) => ids.push(id),
+            None => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Invalid status ID on line {}: {}", i + 1, line),
+                ))
+            }
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Collect snapshots of specific tweets, searching the CDX index for each tweet's URL instead of
+/// scanning all of the user's status URLs.
+pub async fn status_snapshots<F, Fut, E>(
+    screen_name: &str,
+    ids: &[u64],
+    search: F,
+) -> Result<Vec<Item>, E>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Vec<Item>, E>>,
+{
+    let mut items = vec![];
+
+    for id in ids {
+        items.extend(search(format!("twitter.com/{}/status/{}", screen_name, id)).await?);
+    }
+
+    Ok(items)
+}
+
+/// Write a "link dump" of Wayback Machine URLs for tweets, one per line.
+///
+/// If `include_live` is set, each snapshot URL is followed by the tweet's twitter.com URL.
+/// Returns the number of lines written.
+pub fn write_link_dump<'a, W: Write, I: IntoIterator<Item = (u64, &'a Item)>>(
+    mut writer: W,
+    screen_name: &str,
+    items: I,
+    include_live: bool,
+) -> std::io::Result<usize> {
+    let mut count = 0;
+
+    for (id, item) in items {
+        writeln!(
+            writer,
+            "https://web.archive.org/web/{}/{}",
+            format_timestamp(item.archived_at),
+            item.url
+        )?;
+        count += 1;
+
+        if include_live {
+            writeln!(writer, "https://twitter.com/{}/status/{}", screen_name, id)?;
+            count += 1;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(count)
+}
+
 #[cfg(test)]
 mod tests {
+    use chrono::NaiveDate;
+    use wayback_rs::Item;
+
+    fn cdx_row(year: i32, month: u32, day: u32, status: Option<u16>) -> Item {
+        Item::new(
+            "https://twitter.com/jdegoes/status/1169217405425455105".to_string(),
+            NaiveDate::from_ymd(year, month, day).and_hms(12, 0, 0),
+            "AJBB7D3QXQOBZ3BQUPGZ6UIZPVVCH2SF".to_string(),
+            "text/html".to_string(),
+            0,
+            status,
+        )
+    }
+
+    #[test]
+    fn test_classify_item() {
+        use super::ItemKind;
+
+        let cases = [
+            (
+                "https://twitter.com/jdegoes/status/1169217405425455105",
+                ItemKind::Status(1169217405425455105),
+            ),
+            (
+                "http://twitter.com:80/martinshkreli/status/446273988780904448?lang=da",
+                ItemKind::Status(446273988780904448),
+            ),
+            (
+                "https://mobile.twitter.com/i/web/status/1270099974559154177",
+                ItemKind::Status(1270099974559154177),
+            ),
+            (
+                "https://twitter.com/jdegoes",
+                ItemKind::Profile("jdegoes".to_string()),
+            ),
+            (
+                "http://twitter.com:80/jdegoes/",
+                ItemKind::Profile("jdegoes".to_string()),
+            ),
+            (
+                "https://twitter.com/jdegoes/with_replies?lang=en",
+                ItemKind::Profile("jdegoes".to_string()),
+            ),
+            (
+                "https://twitter.com/search?q=%23scala&src=hash",
+                ItemKind::Search("#scala".to_string()),
+            ),
+            (
+                "https://twitter.com/search?q=from%3Ajdegoes+scala",
+                ItemKind::Search("from:jdegoes scala".to_string()),
+            ),
+            ("https://twitter.com/search?src=typd", ItemKind::Other),
+            ("https://twitter.com/hashtag/scala", ItemKind::Other),
+            ("https://twitter.com/home", ItemKind::Other),
+            ("https://twitter.com/jdegoes/followers", ItemKind::Other),
+            ("https://example.com/jdegoes", ItemKind::Other),
+            ("not a url", ItemKind::Other),
+        ];
+
+        for (url, expected) in cases {
+            let mut item = cdx_row(2020, 1, 1, Some(200));
+            item.url = url.to_string();
+
+            assert_eq!(super::classify_item(&item), expected, "{}", url);
+        }
+    }
+
     #[test]
     fn test_parse_tweet_url() {
         let pairs = vec![
@@ -43,6 +434,10 @@ mod tests {
                 "https://twitter.com/ChiefScientist/status/1270099974559154177",
                 Some(("ChiefScientist".to_string(), 1270099974559154177)),
             ),
+            (
+                "https://x.com/ChiefScientist/status/1270099974559154177",
+                Some(("ChiefScientist".to_string(), 1270099974559154177)),
+            ),
             ("abcdef", None),
         ];
 
@@ -60,4 +455,331 @@ mod tests {
             Some(("brithume".to_string(), 1283385533415206914))
         );
     }
+
+    #[test]
+    fn test_strip_wayback_artifacts() {
+        let html = "<html><head><script type=\"text/javascript\" \
+            src=\"/_static/js/bundle-playback.js?v=1WaXNDFE\" charset=\"utf-8\"></script>
+<script type=\"text/javascript\">
+  __wm.init(\"https://web.archive.org/web\");
+</script>
+<link rel=\"stylesheet\" type=\"text/css\" href=\"/_static/css/banner-styles.css?v=S1zqJCYt\" />
+<!-- End Wayback Rewrite JS Include -->
+<title>Tweet</title></head><body>
+<!-- BEGIN WAYBACK TOOLBAR INSERT -->
+<div id=\"wm-ipp-base\"><div class=\"tweet\">Not a tweet</div></div>
+<!-- END WAYBACK TOOLBAR INSERT -->
+<a href=\"https://web.archive.org/web/20190909163817/https://twitter.com/jdegoes\">jdegoes</a>
+<img src=\"/web/20190909163817im_/https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg\">
+</body></html>
+<!--
+     FILE ARCHIVED ON 16:38:17 Sep 09, 2019 AND RETRIEVED FROM THE
+     INTERNET ARCHIVE ON 23:32:35 Sep 16, 2019.
+-->
+<!--
+playback timings (ms):
+  captures_list: 1.0
+-->";
+
+        assert_eq!(
+            super::strip_wayback_artifacts(html),
+            "<html><head><title>Tweet</title></head><body>
+<a href=\"https://twitter.com/jdegoes\">jdegoes</a>
+<img src=\"https://pbs.twimg.com/media/EEAJ5hFXYAAwGOi.jpg\">
+</body></html>
+"
+        );
+
+        let original = "<html><body><a href=\"https://twitter.com/\">Home</a></body></html>";
+
+        assert_eq!(super::strip_wayback_artifacts(original), original);
+    }
+
+    #[test]
+    fn test_strip_wayback_artifacts_tweets() {
+        use crate::browser::twitter::parser;
+        use flate2::read::GzDecoder;
+        use std::io::Read;
+
+        let file =
+            std::fs::File::open("examples/wayback/53SGIJNJMTP6S626CVRCHFTX3OEWXB3E.gz").unwrap();
+        let mut html = String::new();
+        GzDecoder::new(file).read_to_string(&mut html).unwrap();
+
+        // Simulate a capture downloaded without the `id_` modifier.
+        let injected = html
+            .replacen(
+                "<head>",
+                "<head><script src=\"/_static/js/bundle-playback.js\"></script>\
+                 <!-- End Wayback Rewrite JS Include -->",
+                1,
+            )
+            .replacen(
+                "<body",
+                "<!-- BEGIN WAYBACK TOOLBAR INSERT --><div class=\"tweet\" \
+                 data-tweet-id=\"1\" data-user-id=\"1\" data-screen-name=\"wm\" \
+                 data-name=\"wm\"><small class=\"time\"><span class=\"_timestamp\" \
+                 data-time-ms=\"1568047236000\"></span></small><p class=\"tweet-text\">\
+                 Wayback Machine</p></div><!-- END WAYBACK TOOLBAR INSERT --><body",
+                1,
+            )
+            .replace(
+                "https://twitter.com/",
+                "https://web.archive.org/web/20190909163817/https://twitter.com/",
+            );
+
+        let expected = parser::extract_tweets(&parser::parse_html(&mut html.as_bytes()).unwrap());
+        let unstripped =
+            parser::extract_tweets(&parser::parse_html(&mut injected.as_bytes()).unwrap());
+        let stripped = super::strip_wayback_artifacts(&injected);
+        let tweets = parser::extract_tweets(&parser::parse_html(&mut stripped.as_bytes()).unwrap());
+
+        assert_eq!(expected.len(), 11);
+        assert_eq!(unstripped.len(), 12);
+        assert_eq!(tweets, expected);
+    }
+
+    #[test]
+    fn test_parse_wayback_url() {
+        let timestamp = NaiveDate::from_ymd_opt(2019, 9, 16)
+            .and_then(|date| date.and_hms_opt(23, 32, 35))
+            .unwrap();
+        let original = "https://twitter.com/jdegoes/status/1169217405425455105";
+
+        assert_eq!(
+            super::parse_wayback_url(
+                "https://web.archive.org/web/20190916233235/https://twitter.com/jdegoes/status/1169217405425455105"
+            ),
+            Some((timestamp, original.to_string()))
+        );
+        assert_eq!(
+            super::parse_wayback_url(
+                "http://web.archive.org/web/20190916233235id_/https://twitter.com/jdegoes/status/1169217405425455105"
+            ),
+            Some((timestamp, original.to_string()))
+        );
+        assert_eq!(
+            super::parse_wayback_url("https://web.archive.org/web/2019/https://twitter.com/"),
+            None
+        );
+        assert_eq!(super::parse_wayback_url(original), None);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        let expected = NaiveDate::from_ymd_opt(2015, 8, 26)
+            .and_then(|date| date.and_hms_opt(22, 31, 46))
+            .unwrap();
+
+        assert_eq!(super::parse_timestamp("20150826223146"), Some(expected));
+        assert_eq!(super::format_timestamp(expected), "20150826223146");
+        assert_eq!(
+            super::parse_timestamp("20151231235959").map(super::format_timestamp),
+            Some("20151231235959".to_string())
+        );
+
+        for invalid in &[
+            "",
+            "2015082622314",
+            "201508262231460",
+            "20150826223161",
+            "20150826243146",
+            "20151326223146",
+            "2015082622314a",
+            "+0150826223146",
+        ] {
+            assert_eq!(super::parse_timestamp(invalid), None, "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_deletion_window() {
+        let rows = vec![
+            cdx_row(2019, 9, 4, Some(200)),
+            cdx_row(2019, 9, 10, None),
+            cdx_row(2019, 9, 1, Some(404)),
+            cdx_row(2019, 9, 20, Some(404)),
+            cdx_row(2019, 9, 15, Some(302)),
+            cdx_row(2019, 9, 12, Some(404)),
+        ];
+
+        assert_eq!(
+            super::deletion_window(&rows),
+            Some(super::DeletionWindow {
+                last_seen_alive: NaiveDate::from_ymd(2019, 9, 10).and_hms(12, 0, 0),
+                first_seen_gone: Some(NaiveDate::from_ymd(2019, 9, 12).and_hms(12, 0, 0)),
+            })
+        );
+    }
+
+    #[test]
+    fn test_deletion_window_without_404() {
+        let rows = vec![cdx_row(2019, 9, 4, Some(200))];
+
+        assert_eq!(
+            super::deletion_window(&rows).and_then(|window| window.first_seen_gone),
+            None
+        );
+        assert_eq!(super::deletion_window(&rows[0..0]), None);
+    }
+
+    #[test]
+    fn test_first_valid_snapshots() {
+        let mut redirect = cdx_row(2019, 1, 1, Some(302));
+        redirect.url = "https://twitter.com/jdegoes/status/2".to_string();
+
+        let items = [
+            cdx_row(2020, 3, 1, Some(200)),
+            cdx_row(2020, 2, 1, None),
+            cdx_row(2020, 1, 1, Some(404)),
+            redirect,
+        ];
+
+        let snapshots = super::first_valid_snapshots(&items);
+        let expected = NaiveDate::from_ymd_opt(2020, 2, 1)
+            .and_then(|date| date.and_hms_opt(12, 0, 0))
+            .unwrap();
+
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots.get(&1169217405425455105), Some(&expected));
+    }
+
+    #[test]
+    fn test_deletion_counts_by_year() {
+        let at = |y, m, d, h, min, sec| {
+            NaiveDate::from_ymd_opt(y, m, d)
+                .and_then(|date| date.and_hms_opt(h, min, sec))
+                .unwrap()
+        };
+        let candidates = [
+            (at(2019, 1, 1, 0, 0, 0), true),
+            (at(2019, 6, 1, 0, 0, 0), false),
+            (at(2019, 12, 31, 23, 59, 59), false),
+            (at(2019, 3, 1, 0, 0, 0), false),
+            (at(2020, 1, 1, 0, 0, 0), true),
+            (at(2020, 2, 1, 0, 0, 0), true),
+            (at(2020, 3, 1, 0, 0, 0), false),
+        ];
+
+        let counts = super::deletion_counts_by_year(candidates)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            counts,
+            vec![
+                (
+                    2019,
+                    super::DeletionCounts {
+                        total: 4,
+                        deleted: 1
+                    }
+                ),
+                (
+                    2020,
+                    super::DeletionCounts {
+                        total: 3,
+                        deleted: 2
+                    }
+                )
+            ]
+        );
+        assert_eq!(counts[0].1.rate(), 0.25);
+        assert_eq!(super::DeletionCounts::default().rate(), 0.0);
+    }
+
+    #[test]
+    fn test_read_status_ids() {
+        let input = "# Deleted tweets\n1169217405425455105\n\n  https://twitter.com/jdegoes/status/1169241613802950657  \n";
+
+        assert_eq!(
+            super::read_status_ids(input.as_bytes()).unwrap(),
+            vec![1169217405425455105, 1169241613802950657]
+        );
+
+        let error = super::read_status_ids("1\njdegoes\n".as_bytes()).unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("line 2"));
+    }
+
+    #[tokio::test]
+    async fn test_status_snapshots() {
+        let queries = std::cell::RefCell::new(vec![]);
+        let search = |query: String| {
+            queries.borrow_mut().push(query.clone());
+
+            async move {
+                let mut item = cdx_row(2020, 1, 1, Some(200));
+                item.url = format!("https://{}", query);
+
+                // The CDX index may not have anything for some tweets.
+                if query.ends_with('3') {
+                    Ok::<_, String>(vec![])
+                } else {
+                    Ok(vec![item])
+                }
+            }
+        };
+
+        let items = super::status_snapshots("jdegoes", &[1, 2, 3], search)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            queries.into_inner(),
+            vec![
+                "twitter.com/jdegoes/status/1",
+                "twitter.com/jdegoes/status/2",
+                "twitter.com/jdegoes/status/3"
+            ]
+        );
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| item.url.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "https://twitter.com/jdegoes/status/1",
+                "https://twitter.com/jdegoes/status/2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_link_dump() {
+        let first = cdx_row(2020, 1, 1, Some(200));
+        let mut second = cdx_row(2020, 2, 1, None);
+        second.url = "https://twitter.com/jdegoes/status/1169217405425455106".to_string();
+
+        let items = vec![
+            (1169217405425455105, &first),
+            (1169217405425455106, &second),
+        ];
+
+        let mut urls_only = vec![];
+        let count =
+            super::write_link_dump(&mut urls_only, "jdegoes", items.clone(), false).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(
+            String::from_utf8(urls_only).unwrap(),
+            "https://web.archive.org/web/20200101120000/https://twitter.com/jdegoes/status/1169217405425455105\n\
+             https://web.archive.org/web/20200201120000/https://twitter.com/jdegoes/status/1169217405425455106\n"
+        );
+
+        let mut with_live = vec![];
+        let count = super::write_link_dump(&mut with_live, "jdegoes", items, true).unwrap();
+        let with_live = String::from_utf8(with_live).unwrap();
+
+        assert_eq!(count, 4);
+        assert_eq!(
+            with_live.lines().collect::<Vec<_>>(),
+            vec![
+                "https://web.archive.org/web/20200101120000/https://twitter.com/jdegoes/status/1169217405425455105",
+                "https://twitter.com/jdegoes/status/1169217405425455105",
+                "https://web.archive.org/web/20200201120000/https://twitter.com/jdegoes/status/1169217405425455106",
+                "https://twitter.com/jdegoes/status/1169217405425455106",
+            ]
+        );
+    }
 }
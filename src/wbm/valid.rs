@@ -1,9 +1,9 @@
 use flate2::read::GzDecoder;
-use futures::{FutureExt, Stream, TryStreamExt};
+use futures::{FutureExt, Stream, StreamExt, TryStreamExt};
 use lazy_static::lazy_static;
 use std::collections::HashSet;
 use std::fs::{read_dir, DirEntry, File};
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::iter::once;
 use std::path::{Path, PathBuf};
 
@@ -32,6 +32,93 @@ lazy_static! {
     };
 }
 
+/// Summary of an attempt to promote files from another directory into a valid store.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PromoteReport {
+    /// Files whose digest matched their name and which were moved into the store.
+    pub promoted: usize,
+    /// Files whose digest didn't match their name (or which couldn't be read or moved).
+    pub failed: usize,
+    /// Files that were already present in the store (these are left in place).
+    pub skipped: usize,
+}
+
+/// The outcome of checking a single file's digest.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CheckStatus {
+    Valid,
+    Invalid,
+    Error,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Valid => "valid",
+            CheckStatus::Invalid => "invalid",
+            CheckStatus::Error => "error",
+        }
+    }
+}
+
+/// The result of checking a single file's digest against its name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CheckResult {
+    /// The digest indicated by the file name.
+    pub expected: String,
+    /// The computed digest (if the file could be read).
+    pub actual: Option<String>,
+    pub status: CheckStatus,
+}
+
+impl CheckResult {
+    fn new(expected: String, actual: std::result::Result<String, io::Error>) -> Self {
+        match actual {
+            Ok(actual) => CheckResult {
+                status: if actual == expected {
+                    CheckStatus::Valid
+                } else {
+                    CheckStatus::Invalid
+                },
+                expected,
+                actual: Some(actual),
+            },
+            Err(_) => CheckResult {
+                expected,
+                actual: None,
+                status: CheckStatus::Error,
+            },
+        }
+    }
+}
+
+/// Write check results as CSV rows (expected digest, computed digest, and status).
+pub fn write_check_report<'a, W: Write, I: IntoIterator<Item = &'a CheckResult>>(
+    writer: W,
+    results: I,
+) -> csv::Result<()> {
+    let mut writer = csv::WriterBuilder::new().from_writer(writer);
+    writer.write_record(["expected", "actual", "status"])?;
+
+    for result in results {
+        writer.write_record([
+            result.expected.as_str(),
+            result.actual.as_deref().unwrap_or(""),
+            result.status.as_str(),
+        ])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+enum PromoteOutcome {
+    Promoted,
+    Invalid(String),
+    AlreadyPresent,
+}
+
 pub struct ValidStore {
     base: Box<Path>,
 }
@@ -81,6 +168,120 @@ impl ValidStore {
             .try_buffer_unordered(n)
     }
 
+    /// Verify the digests of the files in another (flat) directory and move valid ones into the
+    /// store.
+    ///
+    /// Only files named `{digest}.gz` are considered. Files with incorrect digests are left in
+    /// place and counted as failures.
+    pub async fn promote_from<P: AsRef<Path>>(
+        &self,
+        other: P,
+        parallelism: usize,
+    ) -> Result<PromoteReport> {
+        let mut candidates = vec![];
+
+        for entry in read_dir(other)? {
+            let path = entry?.path();
+
+            match path
+                .file_stem()
+                .and_then(|os| os.to_str())
+                .zip(path.extension().and_then(|os| os.to_str()))
+                .filter(|(_, ext)| *ext == "gz")
+                .and_then(|(name, _)| self.location(name).map(|location| (name, location)))
+            {
+                Some((name, location)) => {
+                    candidates.push((name.to_string(), path.clone(), location));
+                }
+                None => log::warn!("Skipping unexpected file: {:?}", path),
+            }
+        }
+
+        let results = futures::stream::iter(candidates)
+            .map(|(name, path, location)| {
+                tokio::spawn(async move {
+                    let outcome = Self::promote_file(&name, &path, &location);
+                    (name, outcome)
+                })
+            })
+            .buffer_unordered(parallelism)
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut report = PromoteReport::default();
+
+        for result in results {
+            match result {
+                Ok((_, Ok(PromoteOutcome::Promoted))) => report.promoted += 1,
+                Ok((_, Ok(PromoteOutcome::AlreadyPresent))) => report.skipped += 1,
+                Ok((name, Ok(PromoteOutcome::Invalid(actual)))) => {
+                    log::error!("Invalid digest: expected {}, got {}", name, actual);
+                    report.failed += 1;
+                }
+                Ok((name, Err(error))) => {
+                    log::error!("Error promoting {}: {:?}", name, error);
+                    report.failed += 1;
+                }
+                Err(_) => return Err(Error::DigestComputationError),
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn promote_file(name: &str, path: &Path, location: &Path) -> Result<PromoteOutcome> {
+        if location.is_file() {
+            return Ok(PromoteOutcome::AlreadyPresent);
+        }
+
+        let mut file = File::open(path)?;
+        let digest = wayback_rs::digest::compute_digest_gz(&mut file)?;
+
+        if digest == name {
+            // Renaming fails across file systems, so we fall back to copying.
+            if std::fs::rename(path, location).is_err() {
+                std::fs::copy(path, location)?;
+                std::fs::remove_file(path)?;
+            }
+
+            Ok(PromoteOutcome::Promoted)
+        } else {
+            Ok(PromoteOutcome::Invalid(digest))
+        }
+    }
+
+    /// Check the digest of every file in the store, one at a time.
+    ///
+    /// Errors indicate unexpected files or directories.
+    pub fn check_all(&self) -> impl Iterator<Item = Result<CheckResult>> {
+        self.paths().map(|result| {
+            result.map(|(expected, path)| {
+                let actual = File::open(path)
+                    .and_then(|mut file| wayback_rs::digest::compute_digest_gz(&mut file));
+
+                if let Err(error) = &actual {
+                    log::error!("Error reading file: {} ({:?})", expected, error);
+                }
+
+                CheckResult::new(expected, actual)
+            })
+        })
+    }
+
+    /// Check the digest of every file in the store, computing up to `n` digests concurrently.
+    ///
+    /// Results are not returned in any particular order.
+    pub fn check_all_parallel(&self, n: usize) -> impl Stream<Item = Result<CheckResult>> {
+        self.compute_digests(None, n).map(|result| match result {
+            Ok((expected, actual)) => Ok(CheckResult::new(expected, Ok(actual))),
+            Err(Error::ItemIOError { digest, error }) => {
+                log::error!("Error reading file: {} ({:?})", digest, error);
+                Ok(CheckResult::new(digest, Err(error)))
+            }
+            Err(error) => Err(error),
+        })
+    }
+
     pub fn paths(&self) -> impl Iterator<Item = Result<(String, PathBuf)>> {
         match read_dir(&self.base).and_then(|it| it.collect::<std::result::Result<Vec<_>, _>>()) {
             Err(error) => Box::new(once(Err(error.into())))
@@ -278,3 +479,128 @@ impl ValidStore {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckStatus, PromoteReport, ValidStore};
+
+    const EXAMPLE_DIGESTS: [&str; 5] = [
+        "2G3EOT7X6IEQZXKSM3OJJDW6RBCHB7YE",
+        "3KQVYC56SMX4LL6QGQEZZGXMOVNZR2XX",
+        "5DECQVIU7Y3F276SIBAKKCRGDMVXJYFV",
+        "AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O",
+        "Y2A3M6COP2G6SKSM4BOHC2MHYS3UW22V",
+    ];
+
+    fn example_valid_store(base: &std::path::Path) -> ValidStore {
+        let store = ValidStore::create(base).unwrap();
+
+        for digest in &EXAMPLE_DIGESTS {
+            std::fs::copy(
+                format!("examples/wayback/store/data/{}.gz", digest),
+                store.location(digest).unwrap(),
+            )
+            .unwrap();
+        }
+
+        store
+    }
+
+    #[test]
+    fn test_check_report() {
+        let base_dir = tempfile::tempdir().unwrap();
+        let store = example_valid_store(base_dir.path());
+
+        let results = store.check_all().collect::<Result<Vec<_>, _>>().unwrap();
+        let mut buffer = vec![];
+        super::write_check_report(&mut buffer, &results).unwrap();
+
+        let report = String::from_utf8(buffer).unwrap();
+        let lines = report.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), EXAMPLE_DIGESTS.len() + 1);
+        assert_eq!(lines[0], "expected,actual,status");
+
+        for digest in &EXAMPLE_DIGESTS {
+            assert!(lines.iter().any(|line| line.starts_with(digest)));
+        }
+
+        assert!(lines.contains(
+            &"5DECQVIU7Y3F276SIBAKKCRGDMVXJYFV,5BPR3OBK6O7KJ6PKFNJRNUICXWNZ46QG,invalid"
+        ));
+        assert_eq!(
+            results
+                .iter()
+                .filter(|result| result.status == CheckStatus::Valid)
+                .count(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_all_parallel() {
+        use futures::TryStreamExt;
+
+        let base_dir = tempfile::tempdir().unwrap();
+        let store = example_valid_store(base_dir.path());
+
+        let count = |results: &[super::CheckResult], status: CheckStatus| {
+            results
+                .iter()
+                .filter(|result| result.status == status)
+                .count()
+        };
+
+        let serial = store.check_all().collect::<Result<Vec<_>, _>>().unwrap();
+        let parallel = store
+            .check_all_parallel(4)
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+
+        assert_eq!(parallel.len(), serial.len());
+
+        for status in &[CheckStatus::Valid, CheckStatus::Invalid, CheckStatus::Error] {
+            assert_eq!(count(&parallel, *status), count(&serial, *status));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_promote_from() {
+        let valid_digest = "AJBB526CEZFOBT3FCQYLRMXQ2MSFHE3O";
+        let invalid_digest = "5DECQVIU7Y3F276SIBAKKCRGDMVXJYFV";
+
+        let base_dir = tempfile::tempdir().unwrap();
+        let other_dir = tempfile::tempdir().unwrap();
+        let store = ValidStore::create(base_dir.path()).unwrap();
+
+        for digest in &[valid_digest, invalid_digest] {
+            std::fs::copy(
+                format!("examples/wayback/store/data/{}.gz", digest),
+                other_dir.path().join(format!("{}.gz", digest)),
+            )
+            .unwrap();
+        }
+
+        let report = store.promote_from(other_dir.path(), 2).await.unwrap();
+
+        assert_eq!(
+            report,
+            PromoteReport {
+                promoted: 1,
+                failed: 1,
+                skipped: 0
+            }
+        );
+        assert!(store.contains(valid_digest));
+        assert!(!store.contains(invalid_digest));
+        assert!(!other_dir
+            .path()
+            .join(format!("{}.gz", valid_digest))
+            .exists());
+        assert!(other_dir
+            .path()
+            .join(format!("{}.gz", invalid_digest))
+            .exists());
+    }
+}
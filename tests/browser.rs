@@ -1,4 +1,13 @@
-use cancel_culture::browser::make_client;
+use cancel_culture::browser::{
+    make_client,
+    twitter::{
+        crop_tweet_with_mode, hide_cookie_layer, screenshot_tweet, shoot_tweet,
+        shoot_tweet_element_bytes, wait_for_element, CropMode, ScreenshotOptions,
+        DEFAULT_WAIT_TIMEOUT,
+    },
+};
+use fantoccini::{error::CmdError, Locator};
+use std::time::Duration;
 
 #[ignore]
 #[tokio::test]
@@ -7,3 +16,115 @@ async fn test_make_client() {
 
     assert!(client.is_ok());
 }
+
+#[ignore]
+#[tokio::test]
+async fn test_wait_for_element_timeout() {
+    let client = make_client("chrome", true, None, None).await.unwrap();
+    client
+        .goto("data:text/html,<p>Nothing to see here</p>")
+        .await
+        .unwrap();
+
+    let result = wait_for_element(
+        &client,
+        Locator::Css("#never-appearing"),
+        Duration::from_millis(500),
+    )
+    .await;
+
+    assert!(matches!(result, Err(CmdError::WaitTimeout)));
+
+    client.close().await.unwrap();
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_hide_cookie_layer_without_layers() {
+    let client = make_client("chrome", true, None, None).await.unwrap();
+
+    client
+        .goto("data:text/html,<p>No layers here</p>")
+        .await
+        .unwrap();
+    assert!(!hide_cookie_layer(&client).await.unwrap());
+    assert!(!client.screenshot().await.unwrap().is_empty());
+
+    client
+        .goto("data:text/html,<div id='layers'></div>")
+        .await
+        .unwrap();
+    assert!(!hide_cookie_layer(&client).await.unwrap());
+
+    client
+        .goto("data:text/html,<div id='layers'><div>Cookies</div></div>")
+        .await
+        .unwrap();
+    assert!(hide_cookie_layer(&client).await.unwrap());
+
+    client.close().await.unwrap();
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_element_screenshot_matches_crop() {
+    let status_id = 703033780689199104;
+    let mut client = make_client("chrome", true, None, None).await.unwrap();
+    let delay = Some(Duration::from_millis(1500));
+
+    let element_bytes = shoot_tweet_element_bytes(
+        &mut client,
+        status_id,
+        800,
+        4000,
+        delay,
+        DEFAULT_WAIT_TIMEOUT,
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    let element_image = image::load_from_memory(&element_bytes).unwrap();
+
+    let full_image = shoot_tweet(
+        &mut client,
+        status_id,
+        800,
+        4000,
+        delay,
+        DEFAULT_WAIT_TIMEOUT,
+    )
+    .await
+    .unwrap()
+    .into_rgba8();
+    // The element includes the action bar, so we compare with the crop that keeps it.
+    let (_, _, w, h) = crop_tweet_with_mode(&full_image, CropMode::IncludeActions).unwrap();
+
+    // The element includes the border that the cropping heuristic excludes.
+    assert!((element_image.width() as i64 - w as i64).abs() <= 4);
+    assert!((element_image.height() as i64 - h as i64).abs() <= 8);
+
+    client.close().await.unwrap();
+}
+
+#[ignore]
+#[tokio::test]
+async fn test_auto_width_fits_wide_content() {
+    let status = "1302847271688523778";
+    let max_width = 1600;
+
+    let narrow = screenshot_tweet(status, ScreenshotOptions::default())
+        .await
+        .unwrap();
+    let fitted = screenshot_tweet(
+        status,
+        ScreenshotOptions {
+            max_auto_width: Some(max_width),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+
+    assert!(fitted.image.width() >= narrow.image.width());
+    assert!(fitted.image.width() <= max_width);
+}